@@ -0,0 +1,103 @@
+//! Compare the latest `decode` benchmark results with the committed baseline.
+//!
+//! ```sh
+//! cargo bench --bench decode
+//! cargo bench --bench compare            # fails if a benchmark got slower than allowed
+//! cargo bench --bench compare -- update  # store the latest results as the new baseline
+//! ```
+//!
+//! Timings only compare well on the same machine. To check a change, run the benchmarks and
+//! `update` on the base commit, then the benchmarks and the check on the change. The allowed
+//! slowdown is 10% and can be set with `ADC_BENCH_THRESHOLD` (in percent).
+
+use serde_json::{Map, Value};
+use std::{env, fs, path::Path, process::ExitCode};
+
+const BASELINE: &str = "benches/baseline.json";
+const RESULTS: &str = "target/criterion/decode";
+
+/// Median time per iteration in nanoseconds of every benchmark in the latest run.
+fn latest(root: &Path) -> Map<String, Value> {
+    let mut results = Map::new();
+    let dir = root.join(RESULTS);
+    let entries = fs::read_dir(&dir).unwrap_or_else(|err| {
+        panic!(
+            "{}: {}, run `cargo bench --bench decode` first",
+            dir.display(),
+            err
+        )
+    });
+
+    for entry in entries {
+        let path = entry.unwrap().path().join("new/estimates.json");
+        let estimates = match fs::read_to_string(&path) {
+            Ok(estimates) => estimates,
+            Err(_) => continue,
+        };
+        let estimates: Value = serde_json::from_str(&estimates).unwrap();
+        let name = path
+            .parent()
+            .unwrap()
+            .parent()
+            .unwrap()
+            .file_name()
+            .unwrap();
+        results.insert(
+            name.to_string_lossy().into_owned(),
+            estimates["median"]["point_estimate"].clone(),
+        );
+    }
+    results
+}
+
+fn main() -> ExitCode {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let results = latest(root);
+    let baseline_path = root.join(BASELINE);
+
+    if env::args().any(|arg| arg == "update") {
+        let json = serde_json::to_string_pretty(&results).unwrap();
+        fs::write(&baseline_path, json + "\n").unwrap();
+        println!("stored {} results in {}", results.len(), BASELINE);
+        return ExitCode::SUCCESS;
+    }
+
+    let threshold: f64 = env::var("ADC_BENCH_THRESHOLD")
+        .ok()
+        .map_or(10.0, |t| t.parse().expect("invalid ADC_BENCH_THRESHOLD"));
+    let baseline: Map<String, Value> =
+        serde_json::from_str(&fs::read_to_string(&baseline_path).unwrap()).unwrap();
+
+    let mut regressed = 0;
+    for (name, old) in &baseline {
+        let old = old.as_f64().unwrap();
+        let new = match results.get(name).and_then(Value::as_f64) {
+            Some(new) => new,
+            None => {
+                println!("{:<24} missing from the latest run", name);
+                continue;
+            }
+        };
+
+        let change = (new / old - 1.0) * 100.0;
+        let verdict = if change > threshold {
+            regressed += 1;
+            "REGRESSED"
+        } else {
+            "ok"
+        };
+        println!(
+            "{:<24} {:>12.0} ns -> {:>12.0} ns  {:>+7.1}%  {}",
+            name, old, new, change, verdict
+        );
+    }
+
+    if regressed > 0 {
+        println!(
+            "{} benchmarks regressed by more than {}%",
+            regressed, threshold
+        );
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
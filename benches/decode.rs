@@ -0,0 +1,52 @@
+//! Decoder throughput on synthetic streams of different data classes.
+//!
+//! See `benches/compare.rs` for checking results against the committed baseline.
+
+use adc::{generator::StreamGenerator, AdcDecoder, AdcSliceDecoder};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use std::io::{self, Read};
+
+const LEN: usize = 1 << 20;
+
+fn decode(c: &mut Criterion) {
+    let cases = [
+        ("zeros", StreamGenerator::new(1).zeros(LEN).finish()),
+        ("text", StreamGenerator::new(2).text(LEN).finish()),
+        ("random", StreamGenerator::new(3).random(LEN).finish()),
+        (
+            "pathological",
+            StreamGenerator::new(4)
+                .random(1 << 16)
+                .pathological(LEN - (1 << 16))
+                .finish(),
+        ),
+    ];
+
+    let mut group = c.benchmark_group("decode");
+    for (name, stream) in &cases {
+        group.throughput(Throughput::Bytes(stream.decompressed.len() as u64));
+        group.bench_function(*name, |b| {
+            b.iter(|| {
+                let mut decoder = AdcDecoder::new(&stream.compressed[..]);
+                let mut buf = [0; 64 * 1024];
+                while decoder.read(&mut buf).unwrap() > 0 {}
+            })
+        });
+        group.bench_function(format!("{}_copy", name), |b| {
+            b.iter(|| {
+                io::copy(
+                    &mut AdcDecoder::new(&stream.compressed[..]),
+                    &mut io::sink(),
+                )
+            })
+        });
+        group.bench_function(format!("{}_slice", name), |b| {
+            let mut output = vec![0; stream.decompressed.len()];
+            b.iter(|| AdcSliceDecoder::decompress_into(&stream.compressed, &mut output).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
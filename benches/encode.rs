@@ -0,0 +1,47 @@
+//! Encoder throughput for each compression level and chain length.
+
+use adc::{generator::StreamGenerator, AdcEncoder, CompressionLevel};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use std::io::{self, Write};
+
+const LEN: usize = 1 << 20;
+
+fn encode(c: &mut Criterion) {
+    let cases = [
+        ("zeros", StreamGenerator::new(1).zeros(LEN).finish()),
+        ("text", StreamGenerator::new(2).text(LEN).finish()),
+        ("random", StreamGenerator::new(3).random(LEN).finish()),
+    ];
+    let levels = [
+        ("fastest", CompressionLevel::Fastest),
+        ("default", CompressionLevel::Default),
+        ("best", CompressionLevel::Best),
+    ];
+
+    let mut group = c.benchmark_group("encode");
+    for (name, stream) in &cases {
+        group.throughput(Throughput::Bytes(stream.decompressed.len() as u64));
+        for (level_name, level) in &levels {
+            group.bench_function(format!("{}_{}", name, level_name), |b| {
+                b.iter(|| {
+                    let mut encoder = AdcEncoder::with_level(io::sink(), *level);
+                    encoder.write_all(&stream.decompressed).unwrap();
+                    encoder.finish().unwrap();
+                })
+            });
+        }
+        for chain_length in [4, 64] {
+            group.bench_function(format!("{}_chain_{}", name, chain_length), |b| {
+                b.iter(|| {
+                    let mut encoder = AdcEncoder::new(io::sink()).chain_length(chain_length);
+                    encoder.write_all(&stream.decompressed).unwrap();
+                    encoder.finish().unwrap();
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, encode);
+criterion_main!(benches);
@@ -0,0 +1,45 @@
+//! Decode arbitrary bytes with small, varying read sizes.
+//!
+//! The first input byte selects the read sizes, so chunks get split across reads in many
+//! different ways. Decoding must never panic, the output must stay within the maximum
+//! expansion of the format, and the result must not depend on how the output is read.
+
+#![no_main]
+
+use adc::AdcDecoder;
+use libfuzzer_sys::fuzz_target;
+use std::io::Read;
+
+/// Best case compression of the format: a 3 byte chunk producing 67 bytes.
+fn max_output(input_len: usize) -> usize {
+    input_len * 67 / 3
+}
+
+fn read_all<R: Read>(mut r: R, sizes: &[usize]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    let mut buf = [0; 256];
+    for &size in sizes.iter().cycle() {
+        match r.read(&mut buf[..size]) {
+            Ok(0) => return Ok(out),
+            Ok(n) => out.extend_from_slice(&buf[..n]),
+            Err(err) => return Err(err.to_string()),
+        }
+    }
+    unreachable!()
+}
+
+fuzz_target!(|data: &[u8]| {
+    let (seed, input) = match data.split_first() {
+        Some((&seed, input)) => (seed as usize, input),
+        None => return,
+    };
+    let sizes = [1 + seed % 7, 1 + seed % 64, 256, 1 + seed % 3];
+
+    let split = read_all(AdcDecoder::new(input), &sizes);
+    let whole = read_all(AdcDecoder::new(input), &[256]);
+    assert_eq!(split, whole);
+
+    if let Ok(out) = whole {
+        assert!(out.len() <= max_output(input.len()));
+    }
+});
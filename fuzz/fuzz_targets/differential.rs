@@ -0,0 +1,13 @@
+//! Compare the decoder with the C reference implementation on arbitrary input.
+//!
+//! Needs the `reference` feature and `ADC_REFERENCE_C`, see `reference/build.rs`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Err(err) = adc_reference::compare(data) {
+        panic!("{}", err);
+    }
+});
@@ -0,0 +1,79 @@
+//! Decode arbitrary bytes with the push-based `stream` decoder and with `AdcDecoder`.
+//!
+//! `AdcDecoder` has its own chunk loop instead of driving `stream::decompress`, so the two
+//! implementations are checked against each other here. The first two input bytes select
+//! how the input and output buffers of the `stream` decoder are split. Both must produce the
+//! same output and fail on the same kind of error.
+
+#![no_main]
+
+use adc::{
+    stream::{decompress, AdcState, Status},
+    AdcDecoder, AdcError,
+};
+use libfuzzer_sys::fuzz_target;
+use std::{cmp, io::Read};
+
+/// Failure of a decoder, or `None` if the stream ended cleanly.
+#[derive(Debug, PartialEq)]
+enum Failure {
+    InvalidOffset,
+    Truncated,
+}
+
+fn decode_stream(
+    input: &[u8],
+    in_sizes: &[usize],
+    out_sizes: &[usize],
+) -> (Vec<u8>, Option<Failure>) {
+    let mut state = AdcState::new();
+    let mut out = Vec::new();
+    let mut buf = [0; 256];
+    let mut pos = 0;
+    for (&in_size, &out_size) in in_sizes.iter().cycle().zip(out_sizes.iter().cycle()) {
+        // an empty slice ends the stream, so it is only passed once all input was taken
+        let end = cmp::min(pos + in_size, input.len());
+        let (status, read, written) =
+            decompress(&mut state, &input[pos..end], &mut buf[..out_size]);
+        pos += read;
+        out.extend_from_slice(&buf[..written]);
+        let failure = match status {
+            Status::NeedsMoreInput | Status::NeedsMoreOutput => continue,
+            Status::Done => None,
+            Status::InvalidOffset => Some(Failure::InvalidOffset),
+            Status::Truncated => Some(Failure::Truncated),
+            status => panic!("unexpected status {:?}", status),
+        };
+        assert_eq!(state.total_in(), pos as u64);
+        assert_eq!(state.total_out(), out.len() as u64);
+        return (out, failure);
+    }
+    unreachable!()
+}
+
+fn decode_reader(input: &[u8]) -> (Vec<u8>, Option<Failure>) {
+    let mut out = Vec::new();
+    let failure = match AdcDecoder::new(input).read_to_end(&mut out) {
+        Ok(_) => None,
+        Err(err) => match AdcError::from(err) {
+            AdcError::InvalidOffset(_) => Some(Failure::InvalidOffset),
+            AdcError::TruncatedChunk(_) => Some(Failure::Truncated),
+            err => panic!("unexpected error {}", err),
+        },
+    };
+    (out, failure)
+}
+
+fuzz_target!(|data: &[u8]| {
+    let (seeds, input) = match data.len() {
+        0 | 1 => return,
+        _ => data.split_at(2),
+    };
+    let (a, b) = (seeds[0] as usize, seeds[1] as usize);
+    let in_sizes = [1 + a % 5, 1 + a % 97, 4096, 1 + a % 2];
+    let out_sizes = [1 + b % 3, 1 + b % 67, 256, 1 + b % 129];
+
+    let pushed = decode_stream(input, &in_sizes, &out_sizes);
+    let pulled = decode_reader(input);
+    assert_eq!(pushed, pulled);
+});
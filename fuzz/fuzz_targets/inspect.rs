@@ -0,0 +1,34 @@
+//! Check that `inspect` and the decoder agree on arbitrary input.
+//!
+//! Both must accept and reject the same streams with the same error, and the chunk map must
+//! account for every compressed and decompressed byte.
+
+#![no_main]
+
+use adc::AdcDecoder;
+use libfuzzer_sys::fuzz_target;
+use std::io::Read;
+
+fuzz_target!(|data: &[u8]| {
+    let mut out = Vec::new();
+    let decoded = AdcDecoder::new(data).read_to_end(&mut out);
+
+    match (adc::inspect(data), decoded) {
+        (Ok(map), Ok(len)) => {
+            assert_eq!(map.decompressed_len(), len as u64);
+            assert_eq!(map.compressed_len(), data.len() as u64);
+
+            let mut compressed = 0;
+            let mut decompressed = 0;
+            for chunk in &map.chunks {
+                assert_eq!(chunk.compressed_offset, compressed);
+                assert_eq!(chunk.decompressed_offset, decompressed);
+                assert!(chunk.decompressed_len <= 128);
+                compressed += chunk.compressed_len;
+                decompressed += chunk.decompressed_len;
+            }
+        }
+        (Err(a), Err(b)) => assert_eq!(a.kind(), b.kind()),
+        (a, b) => panic!("inspect: {:?}, decoder: {:?}", a.map(|_| ()), b),
+    }
+});
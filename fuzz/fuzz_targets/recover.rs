@@ -0,0 +1,26 @@
+//! Decode arbitrary bytes in recovery mode.
+//!
+//! Recovery must never fail on in-memory input, must produce the same output as the strict
+//! decoder for valid streams, and reported corruptions must lie within the output.
+
+#![no_main]
+
+use adc::AdcDecoder;
+use libfuzzer_sys::fuzz_target;
+use std::io::Read;
+
+fuzz_target!(|data: &[u8]| {
+    let mut decoder = AdcDecoder::with_recovery(data);
+    let mut out = Vec::new();
+    decoder.read_to_end(&mut out).unwrap();
+
+    for c in decoder.corruptions() {
+        assert!(c.decompressed_offset + c.len <= out.len() as u64);
+    }
+
+    let mut strict = Vec::new();
+    if AdcDecoder::new(data).read_to_end(&mut strict).is_ok() {
+        assert!(decoder.corruptions().is_empty());
+        assert_eq!(out, strict);
+    }
+});
@@ -0,0 +1,135 @@
+//! Decode valid chunk sequences built from structured input, and mutations of them.
+//!
+//! Random bytes rarely get past the first few chunks before hitting an invalid offset. Here
+//! `arbitrary` builds streams that are valid by construction, so the decoder is driven deep
+//! into long streams with every chunk type, window distance and read size. A valid stream
+//! must decode to exactly the expected output; a mutated one must decode or fail consistently
+//! with `inspect`.
+
+#![no_main]
+
+use adc::AdcDecoder;
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use std::io::Read;
+
+#[derive(Arbitrary, Debug)]
+enum Chunk {
+    Plain(Vec<u8>),
+    Run { distance: u16, len: u8, short: bool },
+}
+
+#[derive(Arbitrary, Debug)]
+enum Mutation {
+    FlipBit { pos: usize, bit: u8 },
+    Truncate { len: usize },
+    Insert { pos: usize, byte: u8 },
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    chunks: Vec<Chunk>,
+    mutation: Option<Mutation>,
+    read_size: u8,
+}
+
+/// Encode `chunks`, returns the stream, its expected output and number of chunks.
+fn build(chunks: &[Chunk]) -> (Vec<u8>, Vec<u8>, usize) {
+    let mut stream = Vec::new();
+    let mut output: Vec<u8> = Vec::new();
+    let mut count = 0;
+
+    for chunk in chunks {
+        match *chunk {
+            Chunk::Plain(ref bytes) => {
+                let bytes = if bytes.is_empty() {
+                    &[0][..]
+                } else {
+                    &bytes[..]
+                };
+                let bytes = &bytes[..bytes.len().min(128)];
+                stream.push(0x80 | (bytes.len() - 1) as u8);
+                stream.extend_from_slice(bytes);
+                output.extend_from_slice(bytes);
+            }
+            Chunk::Run {
+                distance,
+                len,
+                short,
+            } => {
+                if output.is_empty() {
+                    continue;
+                }
+                let history = output.len().min(0x10000);
+                let offset = distance as usize % history;
+
+                let len = if short && offset <= 0x3ff {
+                    let len = 3 + len as usize % 16;
+                    stream.push((((len - 3) as u8) << 2) | (offset >> 8) as u8);
+                    stream.push(offset as u8);
+                    len
+                } else {
+                    let len = 4 + len as usize % 64;
+                    stream.push(0x40 | (len - 4) as u8);
+                    stream.extend_from_slice(&(offset as u16).to_be_bytes());
+                    len
+                };
+                for _ in 0..len {
+                    output.push(output[output.len() - 1 - offset]);
+                }
+            }
+        }
+        count += 1;
+    }
+    (stream, output, count)
+}
+
+fn decode(stream: &[u8], read_size: usize) -> Result<Vec<u8>, std::io::ErrorKind> {
+    let mut decoder = AdcDecoder::new(stream);
+    let mut out = Vec::new();
+    let mut buf = vec![0; read_size];
+    loop {
+        match decoder.read(&mut buf) {
+            Ok(0) => return Ok(out),
+            Ok(n) => {
+                assert!(n <= read_size);
+                out.extend_from_slice(&buf[..n]);
+            }
+            Err(err) => return Err(err.kind()),
+        }
+    }
+}
+
+fuzz_target!(|input: Input| {
+    let (mut stream, expected, count) = build(&input.chunks);
+    let read_size = 1 + input.read_size as usize;
+
+    let mutation = match input.mutation {
+        Some(mutation) if !stream.is_empty() => mutation,
+        _ => {
+            assert_eq!(decode(&stream, read_size), Ok(expected));
+            let map = adc::inspect(&stream[..]).unwrap();
+            assert_eq!(map.chunks.len(), count);
+            return;
+        }
+    };
+
+    let len = stream.len();
+    match mutation {
+        Mutation::FlipBit { pos, bit } => stream[pos % len] ^= 1 << (bit % 8),
+        Mutation::Truncate { len: to } => stream.truncate(to % len),
+        Mutation::Insert { pos, byte } => stream.insert(pos % (len + 1), byte),
+    }
+
+    let decoded = decode(&stream, read_size);
+    assert_eq!(decoded, decode(&stream, 4096));
+    match (adc::inspect(&stream[..]), decoded) {
+        (Ok(map), Ok(out)) => assert_eq!(map.decompressed_len(), out.len() as u64),
+        (Err(err), Err(kind)) => assert_eq!(err.kind(), kind),
+        (map, out) => panic!(
+            "inspect: {:?}, decoder: {:?}",
+            map.map(|_| ()),
+            out.map(|_| ())
+        ),
+    }
+});
@@ -0,0 +1,28 @@
+//! Compile the reference decoder from a libdmg-hfsplus checkout.
+//!
+//! The C source is not part of this repository. `ADC_REFERENCE_C` must point to its
+//! `dmg/adc.c`, and `ADC_REFERENCE_INCLUDE` may override the include directory, which
+//! defaults to the `includes` directory of the checkout.
+
+use std::{env, path::PathBuf};
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=ADC_REFERENCE_C");
+    println!("cargo:rerun-if-env-changed=ADC_REFERENCE_INCLUDE");
+
+    let source = match env::var_os("ADC_REFERENCE_C") {
+        Some(path) => PathBuf::from(path),
+        None => panic!("set ADC_REFERENCE_C to the path of libdmg-hfsplus' dmg/adc.c"),
+    };
+    let include = match env::var_os("ADC_REFERENCE_INCLUDE") {
+        Some(path) => PathBuf::from(path),
+        None => source.parent().unwrap().join("../includes"),
+    };
+    println!("cargo:rerun-if-changed={}", source.display());
+
+    cc::Build::new()
+        .file(&source)
+        .include(include)
+        .warnings(false)
+        .compile("adcreference");
+}
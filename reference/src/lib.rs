@@ -0,0 +1,233 @@
+//! Differential testing against the ADC decoder of [libdmg-hfsplus].
+//!
+//! libdmg-hfsplus is GPL licensed, so its source is not included here; see `build.rs` for how
+//! to point the build at a checkout. This crate is not published and nothing in `adc`
+//! depends on it.
+//!
+//! The C decoder does no validation at all. It reads past the end of truncated input and
+//! before the start of its output for offsets reaching back too far. [`compare`] wraps both
+//! buffers in guard regions so that this stays memory safe, and derives the reference
+//! verdict from what it observes: consuming more input than given means the stream was
+//! truncated.
+//!
+//! [libdmg-hfsplus]: https://github.com/planetbeing/libdmg-hfsplus
+
+use adc::{AdcDecoder, AdcError, ChunkContext};
+use std::{
+    io::{self, Read},
+    os::raw::c_int,
+};
+
+extern "C" {
+    fn adc_decompress(
+        in_size: c_int,
+        input: *mut u8,
+        avail_size: c_int,
+        output: *mut u8,
+        bytes_written: *mut usize,
+    ) -> c_int;
+}
+
+/// Largest amount of input a single chunk can read.
+const INPUT_GUARD: usize = 129;
+/// Largest distance a run can reach back.
+const OUTPUT_GUARD: usize = 0x10000;
+
+/// Result of running the reference decoder.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Reference {
+    /// Number of input bytes the decoder consumed, larger than the input if it was truncated
+    pub consumed: usize,
+    /// Decoded output
+    pub output: Vec<u8>,
+}
+
+/// Decode `input` with the reference decoder.
+pub fn reference(input: &[u8]) -> Reference {
+    let mut padded = input.to_vec();
+    padded.resize(input.len() + INPUT_GUARD, 0);
+
+    // best case expansion is 67 bytes from a 3 byte chunk, plus one chunk read past the end
+    let avail = input.len() / 3 * 67 + 2 * 128;
+    let mut output = vec![0; OUTPUT_GUARD + avail + 128];
+    let mut written = 0;
+    let consumed = unsafe {
+        adc_decompress(
+            input.len() as c_int,
+            padded.as_mut_ptr(),
+            avail as c_int,
+            output.as_mut_ptr().add(OUTPUT_GUARD),
+            &mut written,
+        )
+    };
+
+    Reference {
+        consumed: consumed as usize,
+        output: output[OUTPUT_GUARD..OUTPUT_GUARD + written].to_vec(),
+    }
+}
+
+/// Decode `input` with both decoders and describe how they differ, if they do.
+///
+/// - Streams `adc` accepts must be consumed exactly and decode to the same output.
+/// - Streams `adc` rejects as truncated must make the reference read past the end.
+/// - Streams `adc` rejects for an invalid offset must have a run at the reported position
+///   that reaches before the start of the output, checked on the input itself.
+/// - Any other error is a difference.
+/// - For all errors, the output `adc` produced before the error must be a prefix of the
+///   reference output.
+pub fn compare(input: &[u8]) -> Result<(), String> {
+    let reference = reference(input);
+    let mut output = Vec::new();
+    let res = AdcDecoder::new(input).read_to_end(&mut output);
+
+    if !reference.output.starts_with(&output) {
+        let at = output
+            .iter()
+            .zip(&reference.output)
+            .position(|(a, b)| a != b)
+            .unwrap_or(reference.output.len());
+        return Err(format!("output differs at byte {}", at));
+    }
+
+    match res {
+        Ok(_) if reference.consumed != input.len() => Err(format!(
+            "adc decoded the stream, reference consumed {} of {} bytes",
+            reference.consumed,
+            input.len()
+        )),
+        Ok(_) if reference.output.len() != output.len() => Err(format!(
+            "adc decoded {} bytes, reference {} bytes",
+            output.len(),
+            reference.output.len()
+        )),
+        Ok(_) => Ok(()),
+        Err(ref err)
+            if err.kind() == io::ErrorKind::UnexpectedEof && reference.consumed <= input.len() =>
+        {
+            Err(format!(
+                "adc reported truncation, reference consumed {} of {} bytes",
+                reference.consumed,
+                input.len()
+            ))
+        }
+        Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(()),
+        Err(err) => match AdcError::from(err) {
+            AdcError::InvalidOffset(context) => check_invalid_offset(input, &output, &context),
+            err => Err(format!("adc failed with {:?}", err)),
+        },
+    }
+}
+
+/// Check that the run `adc` rejected for an invalid offset really reaches before the start
+/// of the output, decoding its header without `adc`'s parser.
+fn check_invalid_offset(input: &[u8], output: &[u8], context: &ChunkContext) -> Result<(), String> {
+    let at = context.compressed_offset as usize;
+    if input.get(at..at + context.header.len()) != Some(&context.header[..]) {
+        return Err(format!(
+            "adc reported header {:02x?} at byte {}, which is not in the input",
+            context.header, at
+        ));
+    }
+    if context.decompressed_offset != output.len() as u64 {
+        return Err(format!(
+            "adc reported an invalid offset at output byte {} after decoding {} bytes",
+            context.decompressed_offset,
+            output.len()
+        ));
+    }
+
+    let offset = match context.header[..] {
+        [byte, low] if byte & 0xc0 == 0 => (u64::from(byte & 0x03) << 8) | u64::from(low),
+        [byte, high, low] if byte & 0xc0 == 0x40 => (u64::from(high) << 8) | u64::from(low),
+        _ => {
+            return Err(format!(
+                "adc reported an invalid offset for the chunk {:02x?}",
+                context.header
+            ))
+        }
+    };
+    // the run starts `offset + 1` bytes back, so an offset below the output length is valid
+    if offset < context.decompressed_offset {
+        return Err(format!(
+            "adc rejected a run {} bytes back at output byte {}",
+            offset + 1,
+            context.decompressed_offset
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use adc::generator::StreamGenerator;
+    use proptest::prelude::*;
+    use std::{fs, path::Path};
+
+    #[test]
+    fn generated_streams() {
+        for seed in 0..20 {
+            let stream = StreamGenerator::new(seed)
+                .zeros(5000)
+                .text(20000)
+                .random(70000)
+                .pathological(20000)
+                .finish();
+            compare(&stream.compressed).unwrap();
+            assert_eq!(reference(&stream.compressed).output, stream.decompressed);
+        }
+    }
+
+    #[test]
+    fn golden_corpus() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../testdata/golden");
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension() == Some("adc".as_ref()) {
+                let input = fs::read(&path).unwrap();
+                compare(&input).unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+            }
+        }
+    }
+
+    #[test]
+    fn truncated() {
+        compare(&[0x83, 0xfe, 0xed]).unwrap();
+        compare(&[0x81, 0xab, 0xcd, 0x40, 0x00]).unwrap();
+    }
+
+    #[test]
+    fn invalid_offset() {
+        compare(&[0x00, 0x00]).unwrap();
+        compare(&[0x81, 0xab, 0xcd, 0x40, 0x00, 0x02]).unwrap();
+
+        let context = |header: &[u8], decompressed_offset| {
+            let mut input = vec![0x81, 0xab, 0xcd];
+            input.extend_from_slice(header);
+            let err = AdcDecoder::new(&input[..])
+                .read_to_end(&mut Vec::new())
+                .unwrap_err();
+            match AdcError::from(err) {
+                AdcError::InvalidOffset(mut context) => {
+                    context.decompressed_offset = decompressed_offset;
+                    (input, context)
+                }
+                err => panic!("{:?}", err),
+            }
+        };
+        // a run 3 bytes back after 2 bytes of output
+        let (input, invalid) = context(&[0x00, 0x02], 2);
+        check_invalid_offset(&input, &[0xab, 0xcd], &invalid).unwrap();
+        // the same run would be valid after 3 bytes
+        let (input, valid) = context(&[0x00, 0x02], 3);
+        assert!(check_invalid_offset(&input, &[0xab, 0xcd, 0xab], &valid).is_err());
+    }
+
+    proptest! {
+        #[test]
+        fn arbitrary_bytes(input in prop::collection::vec(any::<u8>(), 0..512)) {
+            prop_assert_eq!(compare(&input), Ok(()));
+        }
+    }
+}
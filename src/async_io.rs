@@ -0,0 +1,604 @@
+//! Decompression of asynchronous readers and writers.
+//!
+//! [`AsyncAdcDecoder`] drives the state machine of the [`stream`](crate::stream) module from
+//! a buffer it fills from the input, so it never blocks and can pick up again anywhere in a
+//! chunk once the input has more data. [`AsyncAdcDecoderWriter`] does the same for
+//! compressed data written to it.
+//!
+//! Both implement the tokio traits with the `async` feature and the `futures-io` traits,
+//! used by async-std and smol, with the `futures` feature.
+
+use crate::stream::{decompress, AdcState, Status};
+use std::{
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+#[cfg(feature = "async")]
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Size of the buffers for compressed input and decompressed output.
+const BUF_SIZE: usize = 8 * 1024;
+
+/// Decoder for ADC data read from an asynchronous input.
+///
+/// Implements tokio's `AsyncRead` with the `async` feature and the one of `futures-io` with
+/// the `futures` feature.
+///
+#[cfg_attr(feature = "async", doc = "```")]
+#[cfg_attr(not(feature = "async"), doc = "```ignore")]
+/// use adc::AsyncAdcDecoder;
+/// use tokio::io::AsyncReadExt;
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let input: &[u8] = &[0x81, 0xab, 0xcd, 0x00, 0x01];
+/// let mut data = Vec::new();
+/// AsyncAdcDecoder::new(input).read_to_end(&mut data).await.unwrap();
+/// assert_eq!(data, [0xab, 0xcd, 0xab, 0xcd, 0xab]);
+/// # });
+/// ```
+pub struct AsyncAdcDecoder<R> {
+    input: R,
+    state: AdcState,
+    buf: Box<[u8]>,
+    pos: usize,
+    end: usize,
+    eof: bool,
+}
+
+impl<R> AsyncAdcDecoder<R> {
+    /// Create a decoder reading compressed data from `input`
+    pub fn new(input: R) -> AsyncAdcDecoder<R> {
+        AsyncAdcDecoder {
+            input,
+            state: AdcState::new(),
+            buf: vec![0; BUF_SIZE].into_boxed_slice(),
+            pos: 0,
+            end: 0,
+            eof: false,
+        }
+    }
+
+    /// Get the number of compressed bytes decoded so far
+    pub fn total_in(&self) -> u64 {
+        self.state.total_in()
+    }
+
+    /// Get the number of decompressed bytes produced so far
+    pub fn total_out(&self) -> u64 {
+        self.state.total_out()
+    }
+
+    /// Get a reference to the input
+    pub fn get_ref(&self) -> &R {
+        &self.input
+    }
+
+    /// Get a mutable reference to the input
+    ///
+    /// Reading from it makes the decoder continue with other data than the rest of the
+    /// stream.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.input
+    }
+
+    /// Return the input
+    ///
+    /// Compressed data already read into the buffer of the decoder but not decoded yet is
+    /// lost.
+    pub fn into_inner(self) -> R {
+        self.input
+    }
+}
+
+impl<R: Unpin> AsyncAdcDecoder<R> {
+    /// Decompress into `out`, refilling the buffer with `fill` whenever it runs empty.
+    ///
+    /// Holds everything but the runtime's read trait, returns the number of bytes written.
+    fn poll_decompress<F>(
+        &mut self,
+        cx: &mut Context<'_>,
+        out: &mut [u8],
+        mut fill: F,
+    ) -> Poll<io::Result<usize>>
+    where
+        F: FnMut(Pin<&mut R>, &mut Context<'_>, &mut [u8]) -> Poll<io::Result<usize>>,
+    {
+        loop {
+            if self.pos == self.end && !self.eof {
+                let len = ready!(fill(Pin::new(&mut self.input), cx, &mut self.buf))?;
+                self.pos = 0;
+                self.end = len;
+                // an empty input tells the state machine that the stream ended
+                self.eof = len == 0;
+            }
+
+            let input = &self.buf[self.pos..self.end];
+            let (status, read, written) = decompress(&mut self.state, input, out);
+            self.pos += read;
+
+            match status {
+                Status::NeedsMoreInput if written == 0 => {}
+                Status::Done | Status::NeedsMoreInput | Status::NeedsMoreOutput => {
+                    return Poll::Ready(Ok(written))
+                }
+                // errors stick, return the data before it first
+                _ if written > 0 => return Poll::Ready(Ok(written)),
+                status => return Poll::Ready(Err(self.state.error(status).into())),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncAdcDecoder<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        out: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let written = ready!(self.get_mut().poll_decompress(
+            cx,
+            out.initialize_unfilled(),
+            |input, cx, buf| {
+                let mut buf = ReadBuf::new(buf);
+                ready!(input.poll_read(cx, &mut buf))?;
+                Poll::Ready(Ok(buf.filled().len()))
+            }
+        ))?;
+        out.advance(written);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<R: futures_io::AsyncRead + Unpin> futures_io::AsyncRead for AsyncAdcDecoder<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        out: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut()
+            .poll_decompress(cx, out, |input, cx, buf| input.poll_read(cx, buf))
+    }
+}
+
+/// Decompressor writing the decompressed data to an asynchronous output.
+///
+/// The asynchronous counterpart of [`AdcDecoderWriter`](crate::AdcDecoderWriter):
+/// compressed data written to it is decoded as it arrives and the decompressed data is
+/// written out on the next write, flush or shutdown. Shutting it down checks that the
+/// stream ended on a chunk boundary, writes out the rest and shuts down the output.
+///
+/// Implements tokio's `AsyncWrite` with the `async` feature and the one of `futures-io` with
+/// the `futures` feature, where `poll_close` takes the place of `poll_shutdown`.
+///
+#[cfg_attr(feature = "async", doc = "```")]
+#[cfg_attr(not(feature = "async"), doc = "```ignore")]
+/// use adc::AsyncAdcDecoderWriter;
+/// use tokio::io::AsyncWriteExt;
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let mut decoder = AsyncAdcDecoderWriter::new(Vec::new());
+/// decoder.write_all(&[0x81, 0xab, 0xcd]).await.unwrap();
+/// decoder.write_all(&[0x00, 0x01]).await.unwrap();
+/// decoder.shutdown().await.unwrap();
+/// assert_eq!(decoder.get_ref()[..], [0xab, 0xcd, 0xab, 0xcd, 0xab]);
+/// # });
+/// ```
+pub struct AsyncAdcDecoderWriter<W> {
+    output: W,
+    state: AdcState,
+    buf: Box<[u8]>,
+    pos: usize,
+    end: usize,
+}
+
+impl<W> AsyncAdcDecoderWriter<W> {
+    /// Create a decoder writing the decompressed data to `output`
+    pub fn new(output: W) -> AsyncAdcDecoderWriter<W> {
+        AsyncAdcDecoderWriter {
+            output,
+            state: AdcState::new(),
+            buf: vec![0; BUF_SIZE].into_boxed_slice(),
+            pos: 0,
+            end: 0,
+        }
+    }
+
+    /// Get the number of compressed bytes decoded so far
+    pub fn total_in(&self) -> u64 {
+        self.state.total_in()
+    }
+
+    /// Get the number of decompressed bytes produced so far, including buffered ones
+    pub fn total_out(&self) -> u64 {
+        self.state.total_out()
+    }
+
+    /// Get a reference to the output
+    pub fn get_ref(&self) -> &W {
+        &self.output
+    }
+
+    /// Get a mutable reference to the output
+    ///
+    /// Writing to it directly mixes the data with decompressed data that is still buffered.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.output
+    }
+
+    /// Return the output
+    ///
+    /// Decompressed data not written out yet is lost.
+    pub fn into_inner(self) -> W {
+        self.output
+    }
+}
+
+impl<W: Unpin> AsyncAdcDecoderWriter<W> {
+    /// Write out the buffered output with `write`, keeping what is left if it fails.
+    fn poll_dump<F>(&mut self, cx: &mut Context<'_>, write: &mut F) -> Poll<io::Result<()>>
+    where
+        F: FnMut(Pin<&mut W>, &mut Context<'_>, &[u8]) -> Poll<io::Result<usize>>,
+    {
+        while self.pos < self.end {
+            let buf = &self.buf[self.pos..self.end];
+            match ready!(write(Pin::new(&mut self.output), cx, buf))? {
+                0 => return Poll::Ready(Err(io::ErrorKind::WriteZero.into())),
+                n => self.pos += n,
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    /// Decode from `input`, writing out the output of earlier calls first.
+    ///
+    /// Holds everything but the runtime's write trait, returns the number of bytes taken.
+    fn poll_decompress<F>(
+        &mut self,
+        cx: &mut Context<'_>,
+        input: &[u8],
+        mut write: F,
+    ) -> Poll<io::Result<usize>>
+    where
+        F: FnMut(Pin<&mut W>, &mut Context<'_>, &[u8]) -> Poll<io::Result<usize>>,
+    {
+        if input.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        loop {
+            ready!(self.poll_dump(cx, &mut write))?;
+            let (status, read, written) = decompress(&mut self.state, input, &mut self.buf);
+            self.pos = 0;
+            self.end = written;
+            match status {
+                // the output is written out on the next call, so an error writing it can't
+                // lose input that was already taken
+                _ if read > 0 => return Poll::Ready(Ok(read)),
+                // errors stick, write out the data before it first
+                status if status.is_error() && written == 0 => {
+                    return Poll::Ready(Err(self.state.error(status).into()))
+                }
+                // a run filled the buffer without taking any input
+                _ => {}
+            }
+        }
+    }
+
+    /// Check that the stream ended on a chunk boundary and write out all data.
+    fn poll_finish<F>(&mut self, cx: &mut Context<'_>, mut write: F) -> Poll<io::Result<()>>
+    where
+        F: FnMut(Pin<&mut W>, &mut Context<'_>, &[u8]) -> Poll<io::Result<usize>>,
+    {
+        loop {
+            ready!(self.poll_dump(cx, &mut write))?;
+            // no input marks the end of the stream
+            let (status, _, written) = decompress(&mut self.state, &[], &mut self.buf);
+            self.pos = 0;
+            self.end = written;
+            match status {
+                Status::NeedsMoreOutput => {}
+                Status::Done => break,
+                status if written == 0 => return Poll::Ready(Err(self.state.error(status).into())),
+                _ => {}
+            }
+        }
+        self.poll_dump(cx, &mut write)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<W: AsyncWrite + Unpin> AsyncWrite for AsyncAdcDecoderWriter<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        input: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut()
+            .poll_decompress(cx, input, |output, cx, buf| output.poll_write(cx, buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_dump(cx, &mut |output, cx, buf| output.poll_write(cx, buf)))?;
+        Pin::new(&mut this.output).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_finish(cx, |output, cx, buf| output.poll_write(cx, buf)))?;
+        Pin::new(&mut this.output).poll_shutdown(cx)
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<W: futures_io::AsyncWrite + Unpin> futures_io::AsyncWrite for AsyncAdcDecoderWriter<W> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        input: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.get_mut()
+            .poll_decompress(cx, input, |output, cx, buf| output.poll_write(cx, buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_dump(cx, &mut |output, cx, buf| output.poll_write(cx, buf)))?;
+        Pin::new(&mut this.output).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_finish(cx, |output, cx, buf| output.poll_write(cx, buf)))?;
+        Pin::new(&mut this.output).poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generator::StreamGenerator, AdcError};
+
+    /// Input handing out at most `step` bytes per read, pending every other time.
+    struct Trickle<'a> {
+        data: &'a [u8],
+        step: usize,
+        pending: bool,
+    }
+
+    impl<'a> Trickle<'a> {
+        fn new(data: &'a [u8], step: usize) -> Trickle<'a> {
+            Trickle {
+                data,
+                step,
+                pending: false,
+            }
+        }
+
+        /// Take the next piece of at most `max` bytes
+        fn poll_next(&mut self, cx: &mut Context<'_>, max: usize) -> Poll<&'a [u8]> {
+            self.pending = !self.pending;
+            if self.pending {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            let (piece, rest) = self.data.split_at(self.step.min(self.data.len()).min(max));
+            self.data = rest;
+            Poll::Ready(piece)
+        }
+    }
+
+    /// Output taking at most `step` bytes per write, pending every other time.
+    struct Choke {
+        data: Vec<u8>,
+        step: usize,
+        pending: bool,
+        closed: bool,
+    }
+
+    impl Choke {
+        fn new(step: usize) -> Choke {
+            Choke {
+                data: Vec::new(),
+                step,
+                pending: false,
+                closed: false,
+            }
+        }
+
+        fn poll_take(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+            self.pending = !self.pending;
+            if self.pending {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            let len = self.step.min(buf.len());
+            self.data.extend_from_slice(&buf[..len]);
+            Poll::Ready(Ok(len))
+        }
+    }
+
+    #[cfg(feature = "async")]
+    mod tokio_io {
+        use super::*;
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        impl AsyncRead for Trickle<'_> {
+            fn poll_read(
+                self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                buf: &mut ReadBuf<'_>,
+            ) -> Poll<io::Result<()>> {
+                let piece = ready!(self.get_mut().poll_next(cx, buf.remaining()));
+                buf.put_slice(piece);
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        impl AsyncWrite for Choke {
+            fn poll_write(
+                self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                buf: &[u8],
+            ) -> Poll<io::Result<usize>> {
+                self.get_mut().poll_take(cx, buf)
+            }
+
+            fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+
+            fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+                self.get_mut().closed = true;
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        #[tokio::test]
+        async fn trickled_input() {
+            let stream = StreamGenerator::new(13).text(20_000).zeros(3000).finish();
+            for &step in &[1, 2, 3, 1000] {
+                let mut decoder = AsyncAdcDecoder::new(Trickle::new(&stream.compressed, step));
+                let mut data = Vec::new();
+                decoder.read_to_end(&mut data).await.unwrap();
+
+                assert!(data == stream.decompressed, "step {}", step);
+                assert_eq!(decoder.total_in(), stream.compressed.len() as u64);
+            }
+        }
+
+        #[tokio::test]
+        async fn errors() {
+            let input: &[u8] = &[0x81, 0xab, 0xcd, 0x00, 0x05];
+            let err = AsyncAdcDecoder::new(input)
+                .read_to_end(&mut Vec::new())
+                .await
+                .unwrap_err();
+            assert!(matches!(AdcError::from(err), AdcError::InvalidOffset(c) if c.index == 1));
+
+            let input: &[u8] = &[0x83, 0xfe, 0xed];
+            let err = AsyncAdcDecoder::new(input)
+                .read_to_end(&mut Vec::new())
+                .await
+                .unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        }
+
+        #[tokio::test]
+        async fn choked_output() {
+            let stream = StreamGenerator::new(14).text(20_000).zeros(30_000).finish();
+            for &step in &[1, 7, 1000] {
+                let mut decoder = AsyncAdcDecoderWriter::new(Choke::new(step));
+                for piece in stream.compressed.chunks(3) {
+                    decoder.write_all(piece).await.unwrap();
+                }
+                decoder.shutdown().await.unwrap();
+
+                let output = decoder.into_inner();
+                assert!(output.data == stream.decompressed, "step {}", step);
+                assert!(output.closed);
+            }
+
+            let mut decoder = AsyncAdcDecoderWriter::new(Vec::new());
+            decoder.write_all(&[0x83, 0xfe, 0xed]).await.unwrap();
+            let err = decoder.shutdown().await.unwrap_err();
+            assert!(matches!(AdcError::from(err), AdcError::TruncatedChunk(_)));
+        }
+    }
+
+    #[cfg(feature = "futures")]
+    mod futures_io {
+        use super::*;
+        use ::futures_io::{AsyncRead, AsyncWrite};
+        use std::future::{poll_fn, Future};
+
+        /// Run `future` to completion, polling it again right away while it is pending.
+        fn block_on<F: Future>(future: F) -> F::Output {
+            let mut future = std::pin::pin!(future);
+            let mut cx = Context::from_waker(std::task::Waker::noop());
+            loop {
+                if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                    return output;
+                }
+            }
+        }
+
+        impl AsyncRead for Trickle<'_> {
+            fn poll_read(
+                self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                buf: &mut [u8],
+            ) -> Poll<io::Result<usize>> {
+                let piece = ready!(self.get_mut().poll_next(cx, buf.len()));
+                buf[..piece.len()].copy_from_slice(piece);
+                Poll::Ready(Ok(piece.len()))
+            }
+        }
+
+        impl AsyncWrite for Choke {
+            fn poll_write(
+                self: Pin<&mut Self>,
+                cx: &mut Context<'_>,
+                buf: &[u8],
+            ) -> Poll<io::Result<usize>> {
+                self.get_mut().poll_take(cx, buf)
+            }
+
+            fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+
+            fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+                self.get_mut().closed = true;
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        fn read_to_end<R: AsyncRead + Unpin>(mut input: R) -> io::Result<Vec<u8>> {
+            let mut data = Vec::new();
+            let mut buf = [0; 100];
+            block_on(poll_fn(|cx| loop {
+                match ready!(Pin::new(&mut input).poll_read(cx, &mut buf))? {
+                    0 => return Poll::Ready(Ok::<_, io::Error>(())),
+                    n => data.extend_from_slice(&buf[..n]),
+                }
+            }))?;
+            Ok(data)
+        }
+
+        #[test]
+        fn trickled_input() {
+            let stream = StreamGenerator::new(15).text(20_000).zeros(3000).finish();
+            for &step in &[1, 3, 1000] {
+                let decoder = AsyncAdcDecoder::new(Trickle::new(&stream.compressed, step));
+                let data = read_to_end(decoder).unwrap();
+                assert!(data == stream.decompressed, "step {}", step);
+            }
+
+            let input = Trickle::new(&[0x81, 0xab, 0xcd, 0x00, 0x05], 2);
+            let err = read_to_end(AsyncAdcDecoder::new(input)).unwrap_err();
+            assert!(matches!(AdcError::from(err), AdcError::InvalidOffset(_)));
+        }
+
+        #[test]
+        fn choked_output() {
+            let stream = StreamGenerator::new(16).text(20_000).zeros(30_000).finish();
+            let mut decoder = AsyncAdcDecoderWriter::new(Choke::new(5));
+            let mut input = &stream.compressed[..];
+            block_on(poll_fn(|cx| {
+                while !input.is_empty() {
+                    let n = ready!(Pin::new(&mut decoder).poll_write(cx, input))?;
+                    input = &input[n..];
+                }
+                Pin::new(&mut decoder).poll_close(cx)
+            }))
+            .unwrap();
+
+            let output = decoder.into_inner();
+            assert!(output.data == stream.decompressed);
+            assert!(output.closed);
+        }
+    }
+}
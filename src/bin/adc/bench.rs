@@ -0,0 +1,134 @@
+//! `adc bench`: measure decompression throughput.
+
+use crate::{Error, Result};
+use adc::AdcDecoder;
+use std::{
+    fs, io,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Compressed input file
+    input: PathBuf,
+    /// Number of measured runs
+    #[arg(short = 'n', long, default_value_t = 10)]
+    iterations: usize,
+    /// Number of unmeasured warm-up runs
+    #[arg(long, default_value_t = 1)]
+    warmup: usize,
+}
+
+pub fn run(args: Args, json: bool) -> Result<()> {
+    let input = fs::read(&args.input).map_err(|err| Error::with_path(&args.input, err))?;
+
+    let mut times = Vec::with_capacity(args.iterations);
+    let mut len = 0;
+    for i in 0..args.warmup + args.iterations {
+        let start = Instant::now();
+        len = io::copy(&mut AdcDecoder::new(&input[..]), &mut io::sink())
+            .map_err(|err| Error::with_path(&args.input, err))?;
+        if i >= args.warmup {
+            times.push(start.elapsed());
+        }
+    }
+
+    let stats = Stats::new(&times, len);
+    if json {
+        println!("{}", json_report(&args, input.len(), len, stats.as_ref()));
+        return Ok(());
+    }
+
+    println!(
+        "{}: {} bytes compressed, {} bytes decompressed",
+        args.input.display(),
+        input.len(),
+        len
+    );
+    if let Some(stats) = stats {
+        println!(
+            "decode: {} runs, min {:.1} MB/s, median {:.1} MB/s, mean {:.1} MB/s, max {:.1} MB/s",
+            times.len(),
+            stats.min,
+            stats.median,
+            stats.mean,
+            stats.max
+        );
+    }
+    Ok(())
+}
+
+/// `--json` output, rates are in MB/s and `null` without measured runs.
+fn json_report(args: &Args, compressed: usize, decompressed: u64, stats: Option<&Stats>) -> String {
+    let rate = |f: fn(&Stats) -> f64| stats.map_or_else(|| "null".to_owned(), |s| f(s).to_string());
+    format!(
+        "{{\"path\":{},\"compressed_len\":{},\"decompressed_len\":{},\"runs\":{},\
+         \"min_mb_s\":{},\"median_mb_s\":{},\"mean_mb_s\":{},\"max_mb_s\":{}}}",
+        crate::json::string(&args.input.to_string_lossy()),
+        compressed,
+        decompressed,
+        args.iterations,
+        rate(|s| s.min),
+        rate(|s| s.median),
+        rate(|s| s.mean),
+        rate(|s| s.max)
+    )
+}
+
+/// Throughput statistics in MB/s.
+#[derive(PartialEq, Debug)]
+struct Stats {
+    min: f64,
+    median: f64,
+    mean: f64,
+    max: f64,
+}
+
+impl Stats {
+    fn new(times: &[Duration], bytes: u64) -> Option<Stats> {
+        if times.is_empty() {
+            return None;
+        }
+
+        let mut rates: Vec<f64> = times
+            .iter()
+            .map(|t| bytes as f64 / 1e6 / t.as_secs_f64().max(1e-9))
+            .collect();
+        rates.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        let mid = rates.len() / 2;
+        let median = if rates.len().is_multiple_of(2) {
+            (rates[mid - 1] + rates[mid]) / 2.0
+        } else {
+            rates[mid]
+        };
+
+        Some(Stats {
+            min: rates[0],
+            median,
+            mean: rates.iter().sum::<f64>() / rates.len() as f64,
+            max: rates[rates.len() - 1],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats() {
+        let times = [1, 4, 2, 2].map(Duration::from_secs);
+        assert_eq!(
+            Stats::new(&times, 4_000_000),
+            Some(Stats {
+                min: 1.0,
+                median: 2.0,
+                mean: 2.25,
+                max: 4.0,
+            })
+        );
+        assert_eq!(Stats::new(&[], 1), None);
+    }
+}
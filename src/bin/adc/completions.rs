@@ -0,0 +1,17 @@
+//! `adc completions`: generate shell completion scripts.
+
+use crate::{Cli, Result};
+use clap::CommandFactory;
+use clap_complete::Shell;
+use std::io;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Shell to generate completions for
+    shell: Shell,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    clap_complete::generate(args.shell, &mut Cli::command(), "adc", &mut io::stdout());
+    Ok(())
+}
@@ -0,0 +1,244 @@
+//! `adc compress`: compress a file or stdin.
+
+use crate::{
+    copy, for_each_parallel, input_name, input_size, open_input, output_name, progress::Progress,
+    with_output, Error, Result,
+};
+use adc::{AdcEncoder, CompressionLevel};
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum Level {
+    Store,
+    Fastest,
+    Default,
+    Best,
+    Optimal,
+}
+
+impl From<Level> for CompressionLevel {
+    fn from(level: Level) -> CompressionLevel {
+        match level {
+            Level::Store => CompressionLevel::Store,
+            Level::Fastest => CompressionLevel::Fastest,
+            Level::Default => CompressionLevel::Default,
+            Level::Best => CompressionLevel::Best,
+            Level::Optimal => CompressionLevel::Optimal,
+        }
+    }
+}
+
+/// Compression level options, `--level` or gzip-style `-1` to `-9`.
+#[derive(clap::Args, Clone, Copy, Default)]
+#[group(multiple = false)]
+pub struct LevelArgs {
+    /// Trade-off between speed and compression ratio [default: default]
+    #[arg(short, long, value_enum)]
+    level: Option<Level>,
+    /// Compress fastest
+    #[arg(short = '1', long)]
+    fast: bool,
+    #[arg(short = '2', hide = true)]
+    two: bool,
+    #[arg(short = '3', hide = true)]
+    three: bool,
+    #[arg(short = '4', hide = true)]
+    four: bool,
+    #[arg(short = '5', hide = true)]
+    five: bool,
+    #[arg(short = '6', hide = true)]
+    six: bool,
+    #[arg(short = '7', hide = true)]
+    seven: bool,
+    #[arg(short = '8', hide = true)]
+    eight: bool,
+    /// Compress best, -2 to -8 select the levels in between
+    #[arg(short = '9', long)]
+    best: bool,
+}
+
+impl LevelArgs {
+    /// Check if any level option was given
+    pub fn is_used(&self) -> bool {
+        let numeric = [
+            self.two, self.three, self.four, self.five, self.six, self.seven, self.eight,
+        ];
+        self.level.is_some() || self.fast || self.best || numeric.contains(&true)
+    }
+
+    /// Get the selected level
+    ///
+    /// `-1` is the fastest level, `-2` to `-6` the default and `-7` to `-9` the best one.
+    /// `optimal` is only available through `--level`.
+    pub fn get(&self) -> CompressionLevel {
+        match self.level {
+            Some(level) => level.into(),
+            None if self.fast => CompressionLevel::Fastest,
+            None if self.best || self.seven || self.eight => CompressionLevel::Best,
+            None => CompressionLevel::Default,
+        }
+    }
+}
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Input files, stdin if omitted or `-`
+    inputs: Vec<PathBuf>,
+    /// Output file, stdout if omitted or `-`; the output directory for multiple inputs
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    #[command(flatten)]
+    level: LevelArgs,
+    /// Don't show progress
+    #[arg(short, long)]
+    quiet: bool,
+    /// Number of files to compress concurrently
+    #[arg(short, long, default_value_t = 1, value_name = "N")]
+    jobs: usize,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    if args.inputs.len() <= 1 {
+        let input = args.inputs.first().cloned();
+        return compress(&input, &args.output, &args);
+    }
+
+    let dir = match args.output {
+        Some(ref dir) if dir.is_dir() => dir,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "multiple inputs need an existing output directory (-o DIR)",
+            )
+            .into())
+        }
+    };
+
+    for_each_parallel(&args.inputs, args.jobs, |input| {
+        let output = compressed_in_dir(dir, input);
+        compress(&Some(input.clone()), &Some(output), &args)
+    })
+}
+
+/// Path of the compressed output for `input` in `dir`, the file name with `.adc` appended.
+fn compressed_in_dir(dir: &Path, input: &Path) -> PathBuf {
+    let mut name = input.file_name().unwrap_or(input.as_os_str()).to_owned();
+    name.push(".adc");
+    dir.join(name)
+}
+
+fn compress(input: &Option<PathBuf>, output: &Option<PathBuf>, args: &Args) -> Result<()> {
+    let mut reader = open_input(input)?;
+    let output_name = output_name(output);
+    let show_progress = !args.quiet && args.inputs.len() <= 1;
+    let mut progress = Progress::new(input_size(input), show_progress);
+
+    with_output(output, |out| {
+        let mut encoder = AdcEncoder::with_level(out, args.level.get());
+        let len = copy(
+            &mut reader,
+            &input_name(input),
+            &mut encoder,
+            &output_name,
+            &mut |done| progress.update(done, done),
+        )?;
+        encoder
+            .finish()
+            .map_err(|err| Error::with_path(&output_name, err))?;
+        progress.finish(len);
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::fs;
+
+    #[test]
+    fn compress_file() {
+        let dir = std::env::temp_dir();
+        let input = dir.join(format!("adc-cli-compress-in-{}", std::process::id()));
+        let output = dir.join(format!("adc-cli-compress-out-{}", std::process::id()));
+        let data = b"to be or not to be, that is the question".repeat(20);
+        fs::write(&input, &data).unwrap();
+
+        for &level in &[Level::Store, Level::Optimal] {
+            run(Args {
+                inputs: vec![input.clone()],
+                output: Some(output.clone()),
+                level: LevelArgs {
+                    level: Some(level),
+                    ..LevelArgs::default()
+                },
+                quiet: true,
+                jobs: 1,
+            })
+            .unwrap();
+
+            let compressed = fs::read(&output).unwrap();
+            assert_eq!(adc::decompress_to_vec(&compressed).unwrap(), data);
+        }
+        fs::remove_file(input).unwrap();
+        fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn multiple_files() {
+        let dir =
+            std::env::temp_dir().join(format!("adc-cli-compress-multi-{}", std::process::id()));
+        let out_dir = dir.join("out");
+        fs::create_dir_all(&out_dir).unwrap();
+        let first = dir.join("first.bin");
+        let second = dir.join("second");
+        let missing = dir.join("missing");
+        fs::write(&first, b"abcabcabcabc").unwrap();
+        fs::write(&second, [0; 100]).unwrap();
+
+        let err = run(Args {
+            inputs: vec![first, missing, second],
+            output: Some(out_dir.clone()),
+            level: LevelArgs::default(),
+            quiet: true,
+            jobs: 2,
+        })
+        .unwrap_err();
+
+        assert_eq!(err.to_string(), "1 of 3 files failed");
+        let decompress =
+            |name: &str| adc::decompress_to_vec(&fs::read(out_dir.join(name)).unwrap()).unwrap();
+        assert_eq!(decompress("first.bin.adc"), b"abcabcabcabc");
+        assert_eq!(decompress("second.adc"), [0; 100]);
+        assert!(!out_dir.join("missing.adc").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn levels() {
+        #[derive(clap::Parser)]
+        struct Cli {
+            #[command(flatten)]
+            level: LevelArgs,
+        }
+        let level = |args: &[&str]| {
+            Cli::try_parse_from([&["adc"], args].concat()).map(|cli| cli.level.get())
+        };
+
+        assert_eq!(level(&[]).unwrap(), CompressionLevel::Default);
+        assert_eq!(level(&["-1"]).unwrap(), CompressionLevel::Fastest);
+        assert_eq!(level(&["--fast"]).unwrap(), CompressionLevel::Fastest);
+        assert_eq!(level(&["-6"]).unwrap(), CompressionLevel::Default);
+        assert_eq!(level(&["-8"]).unwrap(), CompressionLevel::Best);
+        assert_eq!(level(&["--best"]).unwrap(), CompressionLevel::Best);
+        assert_eq!(
+            level(&["-l", "optimal"]).unwrap(),
+            CompressionLevel::Optimal
+        );
+        assert!(level(&["-1", "-9"]).is_err());
+        assert!(level(&["--best", "--level", "store"]).is_err());
+    }
+}
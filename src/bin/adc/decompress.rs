@@ -0,0 +1,255 @@
+//! `adc decompress`: decompress a file or stdin.
+
+use crate::{
+    copy, for_each_parallel, input_name, input_size, open_input, output_in_dir, output_name,
+    progress::Progress, with_output, Result,
+};
+use adc::{AdcDecoder, Corruption};
+use std::{
+    io,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+/// Number of decompressed bytes between updates of the consumed input size.
+const PROGRESS_INTERVAL: u64 = 64 * 1024;
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Compressed input files, stdin if omitted or `-`
+    inputs: Vec<PathBuf>,
+    /// Output file, stdout if omitted or `-`; the output directory for multiple inputs
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+    /// Don't show progress
+    #[arg(short, long)]
+    quiet: bool,
+    /// Fail if a stream decompresses to more than SIZE bytes (K, M, G suffixes allowed)
+    #[arg(long, value_name = "SIZE", value_parser = crate::parse_size)]
+    max_output: Option<u64>,
+    /// Fill damaged regions with zeros instead of failing, and report them
+    #[arg(long)]
+    recover: bool,
+    /// Number of files to decompress concurrently
+    #[arg(short, long, default_value_t = 1, value_name = "N")]
+    jobs: usize,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    if args.inputs.len() <= 1 {
+        let input = args.inputs.first().cloned();
+        return decompress(&input, &args.output, &args);
+    }
+
+    let dir = match args.output {
+        Some(ref dir) if dir.is_dir() => dir,
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "multiple inputs need an existing output directory (-o DIR)",
+            )
+            .into())
+        }
+    };
+
+    for_each_parallel(&args.inputs, args.jobs, |input| {
+        let output = output_in_dir(dir, input, "adc");
+        decompress(&Some(input.clone()), &Some(output), &args)
+    })
+}
+
+fn decompress(input: &Option<PathBuf>, output: &Option<PathBuf>, args: &Args) -> Result<()> {
+    let reader = open_input(input)?;
+    let mut decoder = if args.recover {
+        AdcDecoder::with_recovery(reader)
+    } else {
+        AdcDecoder::new(reader)
+    };
+    if let Some(max) = args.max_output {
+        decoder = decoder.max_output_size(max);
+    }
+    let consumed = Arc::new(AtomicU64::new(0));
+    let hook = consumed.clone();
+    let mut decoder = decoder.on_progress(PROGRESS_INTERVAL, move |input, _| {
+        hook.store(input, Ordering::Relaxed)
+    });
+    let show_progress = !args.quiet && args.inputs.len() <= 1;
+    let mut progress = Progress::new(input_size(input), show_progress);
+
+    with_output(output, |out| {
+        let len = copy(
+            &mut decoder,
+            &input_name(input),
+            out,
+            &output_name(output),
+            &mut |done| progress.update(consumed.load(Ordering::Relaxed), done),
+        )?;
+        progress.finish(len);
+        Ok(())
+    })?;
+
+    report_corruptions(input, decoder.corruptions());
+    Ok(())
+}
+
+fn report_corruptions(input: &Option<PathBuf>, corruptions: &[Corruption]) {
+    if corruptions.is_empty() {
+        return;
+    }
+
+    eprintln!(
+        "adc: {}: recovered from {} damaged regions",
+        input_name(input).display(),
+        corruptions.len()
+    );
+    for c in corruptions {
+        eprintln!(
+            "  output {}..{}: {}, zero-filled",
+            c.decompressed_offset,
+            c.decompressed_offset + c.len,
+            c.kind
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn decompress_file() {
+        let dir = std::env::temp_dir();
+        let input = dir.join(format!("adc-cli-in-{}", std::process::id()));
+        let output = dir.join(format!("adc-cli-out-{}", std::process::id()));
+        fs::write(&input, [0x83, 0xfe, 0xed, 0xfa, 0xce, 0x00, 0x00]).unwrap();
+
+        run(Args {
+            inputs: vec![input.clone()],
+            output: Some(output.clone()),
+            quiet: true,
+            max_output: None,
+            recover: false,
+            jobs: 1,
+        })
+        .unwrap();
+
+        assert_eq!(
+            fs::read(&output).unwrap(),
+            [0xfe, 0xed, 0xfa, 0xce, 0xce, 0xce, 0xce]
+        );
+        fs::remove_file(input).unwrap();
+        fs::remove_file(output).unwrap();
+    }
+
+    #[test]
+    fn corrupt_input_removes_output() {
+        let dir = std::env::temp_dir();
+        let input = dir.join(format!("adc-cli-bad-in-{}", std::process::id()));
+        let output = dir.join(format!("adc-cli-bad-out-{}", std::process::id()));
+        fs::write(&input, [0x00, 0x00]).unwrap();
+
+        let err = run(Args {
+            inputs: vec![input.clone()],
+            output: Some(output.clone()),
+            quiet: true,
+            max_output: None,
+            recover: false,
+            jobs: 1,
+        })
+        .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "{}: invalid chunk offset in chunk 0 at compressed offset 0, decompressed \
+                 offset 0 (header 00 00)",
+                input.display()
+            )
+        );
+        assert!(!output.exists());
+        fs::remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn max_output() {
+        let dir = std::env::temp_dir();
+        let input = dir.join(format!("adc-cli-max-in-{}", std::process::id()));
+        let output = dir.join(format!("adc-cli-max-out-{}", std::process::id()));
+        fs::write(&input, [0x83, 0xfe, 0xed, 0xfa, 0xce, 0x00, 0x00]).unwrap();
+
+        let err = run(Args {
+            inputs: vec![input.clone()],
+            output: Some(output.clone()),
+            quiet: true,
+            max_output: Some(6),
+            recover: false,
+            jobs: 1,
+        })
+        .unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "{}: decompressed data exceeds the limit of 6 bytes",
+                input.display()
+            )
+        );
+        assert!(!output.exists());
+        fs::remove_file(input).unwrap();
+    }
+
+    #[test]
+    fn multiple_files() {
+        let dir = std::env::temp_dir().join(format!("adc-cli-multi-{}", std::process::id()));
+        let out_dir = dir.join("out");
+        fs::create_dir_all(&out_dir).unwrap();
+        let good = dir.join("good.adc");
+        let bad = dir.join("bad.adc");
+        let other = dir.join("other.bin");
+        fs::write(&good, [0x81, 0xab, 0xcd]).unwrap();
+        fs::write(&bad, [0x00, 0x00]).unwrap();
+        fs::write(&other, [0x80, 0x01]).unwrap();
+
+        let err = run(Args {
+            inputs: vec![good, bad, other],
+            output: Some(out_dir.clone()),
+            quiet: true,
+            max_output: None,
+            recover: false,
+            jobs: 2,
+        })
+        .unwrap_err();
+
+        assert_eq!(err.to_string(), "1 of 3 files failed");
+        assert_eq!(fs::read(out_dir.join("good")).unwrap(), [0xab, 0xcd]);
+        assert_eq!(fs::read(out_dir.join("other.bin.out")).unwrap(), [0x01]);
+        assert!(!out_dir.join("bad").exists());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn recover() {
+        let dir = std::env::temp_dir();
+        let input = dir.join(format!("adc-cli-recover-in-{}", std::process::id()));
+        let output = dir.join(format!("adc-cli-recover-out-{}", std::process::id()));
+        fs::write(&input, [0x81, 0xab, 0xcd, 0x00, 0x05]).unwrap();
+
+        run(Args {
+            inputs: vec![input.clone()],
+            output: Some(output.clone()),
+            quiet: true,
+            max_output: None,
+            recover: true,
+            jobs: 1,
+        })
+        .unwrap();
+
+        assert_eq!(fs::read(&output).unwrap(), [0xab, 0xcd, 0, 0, 0]);
+        fs::remove_file(input).unwrap();
+        fs::remove_file(output).unwrap();
+    }
+}
@@ -0,0 +1,234 @@
+//! `adc diff`: compare two streams chunk by chunk.
+
+use crate::{json, Error, Result};
+use adc::{AdcDecoder, ChunkInfo, StreamMap};
+use std::{
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// First compressed stream
+    a: PathBuf,
+    /// Second compressed stream
+    b: PathBuf,
+    /// Also compare the decoded output, and only fail if it differs
+    #[arg(short, long)]
+    decoded: bool,
+}
+
+pub fn run(args: Args, json: bool) -> Result<()> {
+    let a = Stream::read(&args.a)?;
+    let b = Stream::read(&args.b)?;
+    let diff = Diff::new(&a, &b);
+    let output = if args.decoded {
+        Some(compare_output(&a, &b)?)
+    } else {
+        None
+    };
+
+    if json {
+        println!("{}", diff.to_json(&a, &b, output));
+    } else {
+        diff.print(&a, &b, output);
+    }
+
+    let differs = match output {
+        Some(output) => output.is_some(),
+        None => diff.first.is_some(),
+    };
+    if differs {
+        return Err(io::Error::other("streams differ").into());
+    }
+    Ok(())
+}
+
+struct Stream<'a> {
+    path: &'a Path,
+    data: Vec<u8>,
+    map: StreamMap,
+}
+
+impl<'a> Stream<'a> {
+    fn read(path: &'a Path) -> Result<Stream<'a>> {
+        let data = fs::read(path).map_err(|err| Error::with_path(path, err))?;
+        let map = adc::inspect(&data[..]).map_err(|err| Error::with_path(path, err))?;
+        Ok(Stream { path, data, map })
+    }
+
+    fn chunk(&self, index: usize) -> Option<(&ChunkInfo, &[u8])> {
+        let chunk = self.map.chunks.get(index)?;
+        let start = chunk.compressed_offset as usize;
+        Some((
+            chunk,
+            &self.data[start..start + chunk.compressed_len as usize],
+        ))
+    }
+
+    fn describe(&self, index: usize) -> String {
+        match self.chunk(index) {
+            Some((c, _)) => format!(
+                "{}, {} bytes{} -> output {}..{}",
+                c.kind,
+                c.compressed_len,
+                c.offset
+                    .map_or_else(String::new, |o| format!(" from offset {}", o)),
+                c.decompressed_offset,
+                c.decompressed_offset + c.decompressed_len
+            ),
+            None => "end of stream".to_owned(),
+        }
+    }
+}
+
+/// Chunk-level differences of two streams.
+#[derive(PartialEq, Debug)]
+struct Diff {
+    /// Index of the first differing chunk
+    first: Option<usize>,
+    /// Number of chunk indices at which the streams differ
+    count: usize,
+}
+
+impl Diff {
+    fn new(a: &Stream, b: &Stream) -> Diff {
+        let len = a.map.chunks.len().max(b.map.chunks.len());
+        let mut diff = Diff {
+            first: None,
+            count: 0,
+        };
+        for i in 0..len {
+            let same = match (a.chunk(i), b.chunk(i)) {
+                (Some((ca, da)), Some((cb, db))) => {
+                    ca.decompressed_offset == cb.decompressed_offset && da == db
+                }
+                _ => false,
+            };
+            if !same {
+                diff.first.get_or_insert(i);
+                diff.count += 1;
+            }
+        }
+        diff
+    }
+
+    fn print(&self, a: &Stream, b: &Stream, output: Option<Option<u64>>) {
+        for s in &[a, b] {
+            println!(
+                "{}: {} chunks, {} bytes compressed, {} bytes decompressed",
+                s.path.display(),
+                s.map.chunks.len(),
+                s.map.compressed_len(),
+                s.map.decompressed_len()
+            );
+        }
+
+        match self.first {
+            Some(first) => {
+                println!("first difference at chunk {}:", first);
+                println!("  {}: {}", a.path.display(), a.describe(first));
+                println!("  {}: {}", b.path.display(), b.describe(first));
+                println!("{} chunk positions differ", self.count);
+            }
+            None => println!("chunks are identical"),
+        }
+
+        match output {
+            Some(Some(offset)) => println!("decoded output differs at byte {}", offset),
+            Some(None) => println!("decoded output is identical"),
+            None => {}
+        }
+    }
+
+    fn to_json(&self, a: &Stream, b: &Stream, output: Option<Option<u64>>) -> String {
+        let stream = |s: &Stream| {
+            format!(
+                "{{\"path\":{},\"chunks\":{},\"compressed_len\":{},\"decompressed_len\":{}}}",
+                json::string(&s.path.to_string_lossy()),
+                s.map.chunks.len(),
+                s.map.compressed_len(),
+                s.map.decompressed_len()
+            )
+        };
+        let null = || "null".to_owned();
+        format!(
+            "{{\"a\":{},\"b\":{},\"first_chunk_difference\":{},\"differing_chunks\":{},\
+             \"first_output_difference\":{}}}",
+            stream(a),
+            stream(b),
+            self.first.map_or_else(null, |i| i.to_string()),
+            self.count,
+            output.flatten().map_or_else(null, |o| o.to_string())
+        )
+    }
+}
+
+/// Decode both streams and find the first differing output byte.
+///
+/// If one output is a prefix of the other, the difference is at the end of the shorter one.
+fn compare_output(a: &Stream, b: &Stream) -> Result<Option<u64>> {
+    let decode = |s: &Stream| {
+        let mut data = Vec::new();
+        AdcDecoder::new(&s.data[..])
+            .read_to_end(&mut data)
+            .map_err(|err| Error::with_path(s.path, err))?;
+        Ok::<_, Error>(data)
+    };
+    let (da, db) = (decode(a)?, decode(b)?);
+
+    let first = da.iter().zip(&db).position(|(x, y)| x != y);
+    Ok(match first {
+        Some(i) => Some(i as u64),
+        None if da.len() != db.len() => Some(da.len().min(db.len()) as u64),
+        None => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(data: &[u8]) -> Stream<'static> {
+        Stream {
+            path: Path::new("test"),
+            data: data.to_vec(),
+            map: adc::inspect(data).unwrap(),
+        }
+    }
+
+    #[test]
+    fn same_output_different_encoding() {
+        // "abababab" as a run and as plain bytes
+        let a = stream(&[0x81, b'a', b'b', 0x40, 0x00, 0x01, 0x81, b'a', b'b']);
+        let b = stream(&[0x87, b'a', b'b', b'a', b'b', b'a', b'b', b'a', b'b']);
+
+        assert_eq!(
+            Diff::new(&a, &b),
+            Diff {
+                first: Some(0),
+                count: 3
+            }
+        );
+        assert_eq!(compare_output(&a, &b).unwrap(), None);
+        assert_eq!(b.describe(0), "plain, 9 bytes -> output 0..8");
+        assert_eq!(b.describe(1), "end of stream");
+    }
+
+    #[test]
+    fn diverging_output() {
+        let a = stream(&[0x81, 0xab, 0xcd, 0x00, 0x01]);
+        let b = stream(&[0x81, 0xab, 0xcd, 0x00, 0x00]);
+
+        assert_eq!(
+            Diff::new(&a, &b),
+            Diff {
+                first: Some(1),
+                count: 1
+            }
+        );
+        assert_eq!(compare_output(&a, &b).unwrap(), Some(2));
+        assert_eq!(compare_output(&a, &a).unwrap(), None);
+    }
+}
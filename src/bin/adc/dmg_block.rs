@@ -0,0 +1,206 @@
+//! `adc dmg-block`: decode a single compressed run from a disk image.
+
+use crate::{copy, output_name, with_output, Error, Result};
+use std::{fs::File, path::PathBuf};
+
+#[cfg(feature = "dmg")]
+use std::io;
+
+#[cfg(feature = "dmg")]
+const SOURCE_ARGS: &[&str] = &["offset", "index", "list"];
+#[cfg(not(feature = "dmg"))]
+const SOURCE_ARGS: &[&str] = &["offset"];
+
+#[derive(clap::Args)]
+#[command(group(clap::ArgGroup::new("source").required(true).args(SOURCE_ARGS)))]
+pub struct Args {
+    /// Disk image file
+    image: PathBuf,
+    /// Offset of the ADC-compressed run in the image
+    #[arg(long, requires = "length")]
+    offset: Option<u64>,
+    /// Length of the ADC-compressed run
+    #[arg(long, requires = "offset")]
+    length: Option<u64>,
+    /// Index of the run in the image's block tables, see --list
+    #[cfg(feature = "dmg")]
+    #[arg(long)]
+    index: Option<usize>,
+    /// List the runs of the image's block tables
+    #[cfg(feature = "dmg")]
+    #[arg(long)]
+    list: bool,
+    /// Output file, stdout if omitted or `-`
+    #[arg(short, long = "out")]
+    out: Option<PathBuf>,
+}
+
+// `json` only affects --list
+#[cfg_attr(not(feature = "dmg"), allow(unused_variables))]
+pub fn run(args: Args, json: bool) -> Result<()> {
+    let image = File::open(&args.image).map_err(|err| Error::with_path(&args.image, err))?;
+
+    #[cfg(feature = "dmg")]
+    {
+        if args.list {
+            return list(&args, &image, json);
+        }
+        if let Some(index) = args.index {
+            return extract_run(&args, &image, index);
+        }
+    }
+
+    let (offset, length) = match (args.offset, args.length) {
+        (Some(offset), Some(length)) => (offset, length),
+        _ => unreachable!("enforced by argument parsing"),
+    };
+    let mut decoder = adc::decode_extent(&image, offset, length);
+    with_output(&args.out, |out| {
+        copy(
+            &mut decoder,
+            &args.image,
+            out,
+            &output_name(&args.out),
+            &mut |_| {},
+        )?;
+        Ok(())
+    })
+}
+
+#[cfg(feature = "dmg")]
+fn read_image(args: &Args, mut image: &File) -> Result<adc::dmg::DmgImage> {
+    adc::dmg::DmgImage::read_from(&mut image).map_err(|err| Error::with_path(&args.image, err))
+}
+
+#[cfg(feature = "dmg")]
+fn list(args: &Args, image: &File, json: bool) -> Result<()> {
+    let image = read_image(args, image)?;
+    if json {
+        let runs: Vec<String> = image
+            .runs()
+            .enumerate()
+            .map(|(i, run)| {
+                format!(
+                    "{{\"index\":{},\"type\":\"{}\",\"type_code\":{},\"sector\":{},\
+                     \"sectors\":{},\"offset\":{},\"length\":{}}}",
+                    i,
+                    type_name(run.kind),
+                    u32::from(run.kind),
+                    run.sector_number,
+                    run.sector_count,
+                    run.compressed_offset,
+                    run.compressed_length
+                )
+            })
+            .collect();
+        println!("{{\"runs\":[{}]}}", runs.join(","));
+        return Ok(());
+    }
+
+    println!(
+        "{:>6}  {:<12}  {:>12}  {:>8}  {:>12}  {:>10}",
+        "index", "type", "sector", "sectors", "offset", "length"
+    );
+    for (i, run) in image.runs().enumerate() {
+        println!(
+            "{:>6}  {:<12}  {:>12}  {:>8}  {:>12}  {:>10}",
+            i,
+            type_name(run.kind),
+            run.sector_number,
+            run.sector_count,
+            run.compressed_offset,
+            run.compressed_length
+        );
+    }
+    Ok(())
+}
+
+/// Stable name of a run type for listings, unknown types are shown as their number.
+#[cfg(feature = "dmg")]
+fn type_name(kind: adc::dmg::RunType) -> String {
+    use adc::dmg::RunType;
+
+    let name = match kind {
+        RunType::ZeroFill => "zero-fill",
+        RunType::Raw => "raw",
+        RunType::Ignore => "ignore",
+        RunType::Adc => "adc",
+        RunType::Zlib => "zlib",
+        RunType::Bzip2 => "bzip2",
+        RunType::Lzfse => "lzfse",
+        RunType::Lzma => "lzma",
+        RunType::Comment => "comment",
+        RunType::Terminator => "terminator",
+        _ => return format!("{:#010x}", u32::from(kind)),
+    };
+    name.to_owned()
+}
+
+#[cfg(feature = "dmg")]
+fn extract_run(args: &Args, file: &File, index: usize) -> Result<()> {
+    let image = read_image(args, file)?;
+    let run = image.runs().nth(index).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("image has no run {}", index),
+        )
+    })?;
+
+    let input = adc::FileExtent::new(file, run.compressed_offset, run.compressed_length);
+    let mut decoder = run
+        .decoder(io::BufReader::new(input))
+        .map_err(|err| Error::with_path(&args.image, err))?;
+    with_output(&args.out, |out| {
+        copy(
+            &mut decoder,
+            &args.image,
+            out,
+            &output_name(&args.out),
+            &mut |_| {},
+        )?;
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn extract_by_offset() {
+        let dir = std::env::temp_dir();
+        let image = dir.join(format!("adc-cli-dmg-{}", std::process::id()));
+        let out = dir.join(format!("adc-cli-dmg-out-{}", std::process::id()));
+        fs::write(&image, [0xee, 0xee, 0x81, 0xab, 0xcd, 0x00, 0x01, 0xee]).unwrap();
+
+        run(
+            Args {
+                image: image.clone(),
+                offset: Some(2),
+                length: Some(5),
+                #[cfg(feature = "dmg")]
+                index: None,
+                #[cfg(feature = "dmg")]
+                list: false,
+                out: Some(out.clone()),
+            },
+            false,
+        )
+        .unwrap();
+
+        assert_eq!(fs::read(&out).unwrap(), [0xab, 0xcd, 0xab, 0xcd, 0xab]);
+        fs::remove_file(image).unwrap();
+        fs::remove_file(out).unwrap();
+    }
+
+    #[cfg(feature = "dmg")]
+    #[test]
+    fn type_names() {
+        use adc::dmg::RunType;
+
+        assert_eq!(type_name(RunType::Adc), "adc");
+        assert_eq!(type_name(RunType::ZeroFill), "zero-fill");
+        assert_eq!(type_name(RunType::from(0x8000_000a)), "0x8000000a");
+    }
+}
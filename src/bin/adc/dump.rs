@@ -0,0 +1,29 @@
+//! `adc dump`: hex dump a compressed stream.
+
+use crate::{input_name, open_input, with_output, Error, Result};
+use std::{io::Read, path::PathBuf};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Compressed input file, stdin if omitted or `-`
+    input: Option<PathBuf>,
+    /// Interleave the dump with chunk boundaries and decoded headers
+    #[arg(short, long)]
+    annotate: bool,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    let mut input = Vec::new();
+    open_input(&args.input)?
+        .read_to_end(&mut input)
+        .map_err(|err| Error::with_path(&input_name(&args.input), err))?;
+
+    with_output(&None, |out| {
+        if args.annotate {
+            adc::annotated_hexdump(&input, out)?;
+        } else {
+            adc::hexdump(&input, 0, out)?;
+        }
+        Ok(())
+    })
+}
@@ -0,0 +1,260 @@
+//! gzip-style usage: `adc FILE...` compresses files in place, `adc -d FILE...`
+//! decompresses them.
+
+use crate::{
+    compress::LevelArgs, copy, input_name, open_input, output_name, with_output, Error, Result,
+};
+use adc::{AdcDecoder, AdcEncoder, CompressionLevel};
+use std::{
+    ffi::OsString,
+    fs,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Files to process, stdin if omitted or `-`
+    files: Vec<PathBuf>,
+    /// Decompress
+    #[arg(short, long)]
+    decompress: bool,
+    /// Write to stdout and keep the input files
+    #[arg(short = 'c', long)]
+    stdout: bool,
+    /// Keep the input files
+    #[arg(short, long)]
+    keep: bool,
+    /// Overwrite existing output files
+    #[arg(short, long)]
+    force: bool,
+    /// Suffix of compressed files
+    #[arg(short = 'S', long, default_value = ".adc")]
+    suffix: String,
+    /// Fail if a stream decompresses to more than SIZE bytes (K, M, G suffixes allowed)
+    #[arg(long, value_name = "SIZE", value_parser = crate::parse_size)]
+    max_output: Option<u64>,
+    #[command(flatten)]
+    level: LevelArgs,
+}
+
+impl Args {
+    /// Check if any gzip-style option or file was given
+    pub fn is_used(&self) -> bool {
+        !self.files.is_empty()
+            || self.decompress
+            || self.stdout
+            || self.keep
+            || self.force
+            || self.max_output.is_some()
+            || self.level.is_used()
+    }
+}
+
+pub fn run(args: Args) -> Result<()> {
+    let files = if args.files.is_empty() {
+        vec![PathBuf::from("-")]
+    } else {
+        args.files.clone()
+    };
+    if files.len() == 1 {
+        return process(&files[0], &args);
+    }
+
+    let mut failed = 0;
+    for file in &files {
+        if let Err(err) = process(file, &args) {
+            eprintln!("adc: {}", err);
+            failed += 1;
+        }
+    }
+    match failed {
+        0 => Ok(()),
+        n => Err(io::Error::other(format!("{} of {} files failed", n, files.len())).into()),
+    }
+}
+
+fn process(file: &Path, args: &Args) -> Result<()> {
+    let input = Some(file.to_owned());
+    if args.stdout || file.as_os_str() == "-" {
+        if !args.decompress && !args.force && io::stdout().is_terminal() {
+            let msg = "compressed data not written to a terminal, use -f to force";
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, msg).into());
+        }
+        return transcode(&input, &None, args);
+    }
+
+    let output = if args.decompress {
+        strip_suffix(file, &args.suffix).ok_or_else(|| {
+            let msg = format!("unknown suffix, expected {}", args.suffix);
+            Error::with_path(file, io::Error::new(io::ErrorKind::InvalidInput, msg))
+        })?
+    } else {
+        if strip_suffix(file, &args.suffix).is_some() {
+            let msg = format!("already has the {} suffix", args.suffix);
+            return Err(Error::with_path(
+                file,
+                io::Error::new(io::ErrorKind::InvalidInput, msg),
+            ));
+        }
+        let mut name = file.as_os_str().to_owned();
+        name.push(&args.suffix);
+        PathBuf::from(name)
+    };
+    if !args.force && fs::symlink_metadata(&output).is_ok() {
+        let err = io::Error::new(io::ErrorKind::AlreadyExists, "already exists, use -f");
+        return Err(Error::with_path(&output, err));
+    }
+    let metadata = fs::metadata(file).map_err(|err| Error::with_path(file, err))?;
+
+    transcode(&input, &Some(output.clone()), args)?;
+    fs::set_permissions(&output, metadata.permissions())
+        .map_err(|err| Error::with_path(&output, err))?;
+    if !args.keep {
+        fs::remove_file(file).map_err(|err| Error::with_path(file, err))?;
+    }
+    Ok(())
+}
+
+/// Compress or decompress `input` to `output`, depending on `-d`.
+fn transcode(input: &Option<PathBuf>, output: &Option<PathBuf>, args: &Args) -> Result<()> {
+    if args.decompress {
+        decompress(input, output, args.max_output)
+    } else {
+        compress(input, output, args.level.get())
+    }
+}
+
+fn decompress(input: &Option<PathBuf>, output: &Option<PathBuf>, max: Option<u64>) -> Result<()> {
+    let mut decoder = AdcDecoder::new(open_input(input)?);
+    if let Some(max) = max {
+        decoder = decoder.max_output_size(max);
+    }
+    with_output(output, |out| {
+        let name = output_name(output);
+        copy(&mut decoder, &input_name(input), out, &name, &mut |_| {})?;
+        Ok(())
+    })
+}
+
+fn compress(
+    input: &Option<PathBuf>,
+    output: &Option<PathBuf>,
+    level: CompressionLevel,
+) -> Result<()> {
+    let mut reader = open_input(input)?;
+    with_output(output, |out| {
+        let name = output_name(output);
+        let mut encoder = AdcEncoder::with_level(out, level);
+        copy(
+            &mut reader,
+            &input_name(input),
+            &mut encoder,
+            &name,
+            &mut |_| {},
+        )?;
+        encoder
+            .finish()
+            .map_err(|err| Error::with_path(&name, err))?;
+        Ok(())
+    })
+}
+
+/// Remove `suffix` from the file name of `path`, `None` if the name doesn't end with it.
+fn strip_suffix(path: &Path, suffix: &str) -> Option<PathBuf> {
+    let name = path.file_name()?.to_str()?;
+    let stem = name.strip_suffix(suffix).filter(|s| !s.is_empty())?;
+    Some(path.with_file_name(OsString::from(stem)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(files: Vec<PathBuf>) -> Args {
+        Args {
+            files,
+            decompress: true,
+            stdout: false,
+            keep: false,
+            force: false,
+            suffix: ".adc".to_owned(),
+            max_output: None,
+            level: LevelArgs::default(),
+        }
+    }
+
+    #[test]
+    fn suffixes() {
+        assert_eq!(
+            strip_suffix(Path::new("dir/file.bin.adc"), ".adc"),
+            Some(PathBuf::from("dir/file.bin"))
+        );
+        assert_eq!(strip_suffix(Path::new("file.bin"), ".adc"), None);
+        assert_eq!(strip_suffix(Path::new(".adc"), ".adc"), None);
+    }
+
+    #[test]
+    fn in_place() {
+        let dir = std::env::temp_dir().join(format!("adc-cli-gzip-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("data.adc");
+        let output = dir.join("data");
+        fs::write(&input, [0x81, 0xab, 0xcd]).unwrap();
+
+        run(args(vec![input.clone()])).unwrap();
+        assert_eq!(fs::read(&output).unwrap(), [0xab, 0xcd]);
+        assert!(!input.exists());
+
+        // existing output is only replaced with -f, -k keeps the input
+        fs::write(&input, [0x80, 0x01]).unwrap();
+        let err = run(args(vec![input.clone()])).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("{}: already exists, use -f", output.display())
+        );
+
+        let mut forced = args(vec![input.clone()]);
+        forced.force = true;
+        forced.keep = true;
+        run(forced).unwrap();
+        assert_eq!(fs::read(&output).unwrap(), [0x01]);
+        assert!(input.exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn compress_in_place() {
+        let dir = std::env::temp_dir().join(format!("adc-cli-gzip-c-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("data");
+        let output = dir.join("data.adc");
+        let data = b"abcdabcdabcdabcd".repeat(10);
+        fs::write(&input, &data).unwrap();
+
+        let mut compress = args(vec![input.clone()]);
+        compress.decompress = false;
+        run(compress).unwrap();
+        assert!(!input.exists());
+        assert_eq!(
+            adc::decompress_to_vec(&fs::read(&output).unwrap()).unwrap(),
+            data
+        );
+
+        // compressed files aren't compressed again
+        let mut again = args(vec![output.clone()]);
+        again.decompress = false;
+        let err = run(again).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("{}: already has the .adc suffix", output.display())
+        );
+
+        run(args(vec![output.clone()])).unwrap();
+        assert_eq!(fs::read(&input).unwrap(), data);
+        assert!(!output.exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,121 @@
+//! `adc inspect`: print the chunk structure of a stream.
+
+use crate::{input_name, open_input, with_output, Error, Result};
+use adc::StreamMap;
+use std::{io, path::PathBuf};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Compressed input file, stdin if omitted or `-`
+    input: Option<PathBuf>,
+}
+
+pub fn run(args: Args, json: bool) -> Result<()> {
+    let map = adc::inspect(open_input(&args.input)?)
+        .map_err(|err| Error::with_path(&input_name(&args.input), err))?;
+
+    with_output(&None, |out| {
+        if json {
+            write_json(&map, out)?;
+        } else {
+            write_table(&map, out)?;
+        }
+        Ok(())
+    })
+}
+
+fn write_table(map: &StreamMap, out: &mut dyn io::Write) -> io::Result<()> {
+    writeln!(
+        out,
+        "{:>8}  {:<10}  {:>10}  {:>6}  {:>10}  {:>7}  {:>6}",
+        "chunk", "type", "in_offset", "in_len", "out_offset", "out_len", "offset"
+    )?;
+    for (i, c) in map.chunks.iter().enumerate() {
+        let offset = c.offset.map_or_else(|| "-".to_owned(), |o| o.to_string());
+        writeln!(
+            out,
+            "{:>8}  {:<10}  {:>10}  {:>6}  {:>10}  {:>7}  {:>6}",
+            i,
+            c.kind,
+            c.compressed_offset,
+            c.compressed_len,
+            c.decompressed_offset,
+            c.decompressed_len,
+            offset
+        )?;
+    }
+    writeln!(
+        out,
+        "{} chunks, {} bytes compressed, {} bytes decompressed",
+        map.chunks.len(),
+        map.compressed_len(),
+        map.decompressed_len()
+    )
+}
+
+fn write_json(map: &StreamMap, out: &mut dyn io::Write) -> io::Result<()> {
+    write!(
+        out,
+        "{{\"compressed_len\":{},\"decompressed_len\":{},\"chunks\":[",
+        map.compressed_len(),
+        map.decompressed_len()
+    )?;
+    for (i, c) in map.chunks.iter().enumerate() {
+        let offset = c
+            .offset
+            .map_or_else(|| "null".to_owned(), |o| o.to_string());
+        write!(
+            out,
+            "{}{{\"index\":{},\"type\":\"{}\",\"compressed_offset\":{},\"compressed_len\":{},\
+             \"decompressed_offset\":{},\"decompressed_len\":{},\"offset\":{}}}",
+            if i == 0 { "" } else { "," },
+            i,
+            c.kind,
+            c.compressed_offset,
+            c.compressed_len,
+            c.decompressed_offset,
+            c.decompressed_len,
+            offset
+        )?;
+    }
+    writeln!(out, "]}}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &[u8] = &[0x83, 0xfe, 0xed, 0xfa, 0xce, 0x00, 0x00];
+
+    #[test]
+    fn table() {
+        let mut out = Vec::new();
+        write_table(&adc::inspect(INPUT).unwrap(), &mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            [
+                "   chunk  type         in_offset  in_len  out_offset  out_len  offset",
+                "       0  plain                0       5           0        4       -",
+                "       1  two_byte             5       2           4        3       0",
+                "2 chunks, 7 bytes compressed, 7 bytes decompressed\n",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn json() {
+        let mut out = Vec::new();
+        write_json(&adc::inspect(INPUT).unwrap(), &mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"compressed_len\":7,\"decompressed_len\":7,\"chunks\":[\
+             {\"index\":0,\"type\":\"plain\",\"compressed_offset\":0,\"compressed_len\":5,\
+             \"decompressed_offset\":0,\"decompressed_len\":4,\"offset\":null},\
+             {\"index\":1,\"type\":\"two_byte\",\"compressed_offset\":5,\"compressed_len\":2,\
+             \"decompressed_offset\":4,\"decompressed_len\":3,\"offset\":0}]}\n"
+        );
+    }
+}
@@ -0,0 +1,330 @@
+//! Command line tool for working with ADC-compressed data.
+//!
+//! Besides the subcommands, `adc` accepts gzip-style options, e.g. `adc FILE` replaces the
+//! file with `FILE.adc` and `adc -d FILE.adc` replaces it with its decompressed contents.
+//! Files named like a command have to be given with a path, e.g. `adc -d ./stats`.
+//!
+//! With `--json` (or `--porcelain`), commands that report results print them as JSON with
+//! stable field names, and errors are printed to stderr as `{"error": "..."}`.
+//!
+//! Exits with status 0 on success, 1 if a command fails and 2 on invalid usage.
+
+use clap::{error::ErrorKind, CommandFactory, Parser, Subcommand};
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, prelude::*, BufReader, BufWriter},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::atomic::{AtomicUsize, Ordering},
+    thread,
+};
+
+mod bench;
+mod completions;
+mod compress;
+mod decompress;
+mod diff;
+mod dmg_block;
+mod dump;
+mod gzip;
+mod inspect;
+mod json;
+mod progress;
+mod selftest;
+mod stats;
+mod verify;
+
+#[derive(Parser)]
+#[command(name = "adc", version, about = "Apple Data Compression tool")]
+struct Cli {
+    /// Print machine-readable JSON output
+    #[arg(long, global = true, visible_alias = "porcelain")]
+    json: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    gzip: gzip::Args,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Decompress ADC data
+    Decompress(decompress::Args),
+    /// Compress data to ADC
+    Compress(compress::Args),
+    /// Print the chunk structure of a compressed stream
+    Inspect(inspect::Args),
+    /// Check compressed streams for corruption
+    Verify(verify::Args),
+    /// Measure decompression throughput
+    Bench(bench::Args),
+    /// Hex dump a compressed stream
+    Dump(dump::Args),
+    /// Decode a single run from a disk image
+    DmgBlock(dmg_block::Args),
+    /// Print compression analytics of a stream as JSON or CSV
+    Stats(stats::Args),
+    /// Compare two compressed streams chunk by chunk
+    Diff(diff::Args),
+    /// Compress and decompress a file and compare the result to the original
+    Selftest(selftest::Args),
+    /// Generate shell completions
+    Completions(completions::Args),
+}
+
+/// Error of a command, with the file it happened on if any.
+#[derive(Debug)]
+pub struct Error {
+    path: Option<PathBuf>,
+    err: io::Error,
+}
+
+impl Error {
+    pub fn with_path(path: &Path, err: io::Error) -> Error {
+        Error {
+            path: Some(path.to_owned()),
+            err,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error { path: None, err }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.path {
+            Some(ref path) => write!(f, "{}: {}", path.display(), self.err),
+            None => write!(f, "{}", self.err),
+        }
+    }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// Parse a byte size with an optional `K`, `M`, `G` or `T` suffix (powers of 1024).
+pub fn parse_size(s: &str) -> std::result::Result<u64, String> {
+    let s = s.trim();
+    let (digits, shift) = match s.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => {
+            let shift = match c.to_ascii_uppercase() {
+                'K' => 10,
+                'M' => 20,
+                'G' => 30,
+                'T' => 40,
+                _ => return Err(format!("unknown size suffix '{}'", c)),
+            };
+            (&s[..i], shift)
+        }
+        _ => (s, 0),
+    };
+
+    let value: u64 = digits
+        .parse()
+        .map_err(|_| format!("invalid size '{}'", s))?;
+    value
+        .checked_mul(1 << shift)
+        .ok_or_else(|| format!("size '{}' is too large", s))
+}
+
+/// Get the path of a file argument, or `None` if it refers to stdin/stdout.
+fn file_path(path: &Option<PathBuf>) -> Option<&Path> {
+    path.as_deref().filter(|p| p.as_os_str() != "-")
+}
+
+/// Name of an input argument to use in messages.
+pub fn input_name(path: &Option<PathBuf>) -> PathBuf {
+    file_path(path)
+        .unwrap_or_else(|| Path::new("<stdin>"))
+        .to_owned()
+}
+
+/// Name of an output argument to use in messages.
+pub fn output_name(path: &Option<PathBuf>) -> PathBuf {
+    file_path(path)
+        .unwrap_or_else(|| Path::new("<stdout>"))
+        .to_owned()
+}
+
+/// Open a file for reading, or stdin if no path or `-` is given.
+pub fn open_input(path: &Option<PathBuf>) -> Result<Box<dyn Read>> {
+    match file_path(path) {
+        Some(path) => {
+            let file = File::open(path).map_err(|err| Error::with_path(path, err))?;
+            Ok(Box::new(BufReader::new(file)))
+        }
+        None => Ok(Box::new(BufReader::new(io::stdin()))),
+    }
+}
+
+/// Run `f` with a writer for `path`, or stdout if no path or `-` is given.
+///
+/// A partially written output file is removed if `f` fails.
+pub fn with_output<F>(path: &Option<PathBuf>, f: F) -> Result<()>
+where
+    F: FnOnce(&mut dyn Write) -> Result<()>,
+{
+    let name = output_name(path);
+    match file_path(path) {
+        Some(path) => {
+            let file = File::create(path).map_err(|err| Error::with_path(path, err))?;
+            let mut out = BufWriter::new(file);
+            let res =
+                f(&mut out).and_then(|_| out.flush().map_err(|err| Error::with_path(&name, err)));
+            if res.is_err() {
+                drop(out);
+                fs::remove_file(path).ok();
+            }
+            res
+        }
+        None => {
+            let stdout = io::stdout();
+            let mut out = BufWriter::new(stdout.lock());
+            f(&mut out)?;
+            out.flush().map_err(|err| Error::with_path(&name, err))
+        }
+    }
+}
+
+/// Size of an input file, if known.
+pub fn input_size(path: &Option<PathBuf>) -> Option<u64> {
+    file_path(path)
+        .and_then(|p| fs::metadata(p).ok())
+        .filter(|m| m.is_file())
+        .map(|m| m.len())
+}
+
+/// Path of the output for `input` in `dir`.
+///
+/// The `suffix` extension is removed from the file name, other names get `.out` appended.
+pub fn output_in_dir(dir: &Path, input: &Path, suffix: &str) -> PathBuf {
+    let name = input.file_name().unwrap_or(input.as_os_str());
+    let path = Path::new(name);
+    match path.extension() {
+        Some(ext) if ext == suffix => dir.join(path.file_stem().unwrap()),
+        _ => {
+            let mut name = name.to_owned();
+            name.push(".out");
+            dir.join(name)
+        }
+    }
+}
+
+/// Run `f` for every item on up to `jobs` threads.
+///
+/// Failures are printed as they happen and summarized in the returned error.
+pub fn for_each_parallel<T, F>(items: &[T], jobs: usize, f: F) -> Result<()>
+where
+    T: Sync,
+    F: Fn(&T) -> Result<()> + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let failed = AtomicUsize::new(0);
+
+    thread::scope(|s| {
+        for _ in 0..jobs.clamp(1, items.len().max(1)) {
+            s.spawn(|| {
+                while let Some(item) = items.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    if let Err(err) = f(item) {
+                        eprintln!("adc: {}", err);
+                        failed.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            });
+        }
+    });
+
+    match failed.into_inner() {
+        0 => Ok(()),
+        n => Err(io::Error::other(format!("{} of {} files failed", n, items.len())).into()),
+    }
+}
+
+/// Copy all data from `input` to `output`, attributing errors to the given names.
+///
+/// `progress` is called with the number of bytes copied so far.
+pub fn copy(
+    input: &mut dyn Read,
+    input_name: &Path,
+    output: &mut dyn Write,
+    output_name: &Path,
+    progress: &mut dyn FnMut(u64),
+) -> Result<u64> {
+    let mut buf = vec![0; 64 * 1024];
+    let mut total = 0;
+    loop {
+        let n = match input.read(&mut buf) {
+            Ok(0) => return Ok(total),
+            Ok(n) => n,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(Error::with_path(input_name, err)),
+        };
+        output
+            .write_all(&buf[..n])
+            .map_err(|err| Error::with_path(output_name, err))?;
+        total += n as u64;
+        progress(total);
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    let command = match cli.command {
+        Some(_) if cli.gzip.is_used() => Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "gzip-style options can't be combined with a command",
+            )
+            .exit(),
+        Some(command) => command,
+        None => return exit(gzip::run(cli.gzip), cli.json),
+    };
+    let res = match command {
+        Command::Decompress(args) => decompress::run(args),
+        Command::Compress(args) => compress::run(args),
+        Command::Inspect(args) => inspect::run(args, cli.json),
+        Command::Verify(args) => verify::run(args, cli.json),
+        Command::Bench(args) => bench::run(args, cli.json),
+        Command::Dump(args) => dump::run(args),
+        Command::DmgBlock(args) => dmg_block::run(args, cli.json),
+        Command::Stats(args) => stats::run(args, cli.json),
+        Command::Diff(args) => diff::run(args, cli.json),
+        Command::Selftest(args) => selftest::run(args, cli.json),
+        Command::Completions(args) => completions::run(args),
+    };
+    exit(res, cli.json)
+}
+
+fn exit(res: Result<()>, json: bool) -> ExitCode {
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) if json => {
+            eprintln!("{{\"error\":{}}}", json::string(&err.to_string()));
+            ExitCode::FAILURE
+        }
+        Err(err) => {
+            eprintln!("adc: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("100"), Ok(100));
+        assert_eq!(parse_size("4k"), Ok(4096));
+        assert_eq!(parse_size("2G"), Ok(2 << 30));
+        assert!(parse_size("1X").is_err());
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("99999999999T").is_err());
+    }
+}
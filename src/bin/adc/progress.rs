@@ -0,0 +1,108 @@
+//! Progress and throughput display on stderr.
+
+use std::{
+    io::{self, IsTerminal, Write},
+    time::{Duration, Instant},
+};
+
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+const BAR_WIDTH: u64 = 30;
+
+/// Progress display for a long running operation.
+///
+/// Nothing is printed if the display is disabled or stderr is not a terminal.
+pub struct Progress {
+    total: Option<u64>,
+    enabled: bool,
+    start: Instant,
+    last_draw: Option<Instant>,
+}
+
+impl Progress {
+    /// Create a display for processing `total` input bytes, if known
+    pub fn new(total: Option<u64>, enabled: bool) -> Progress {
+        Progress {
+            total,
+            enabled: enabled && io::stderr().is_terminal(),
+            start: Instant::now(),
+            last_draw: None,
+        }
+    }
+
+    /// Update the display with the number of input bytes consumed and output bytes produced
+    pub fn update(&mut self, input: u64, output: u64) {
+        if !self.enabled {
+            return;
+        }
+        let now = Instant::now();
+        if self.last_draw.is_some_and(|t| now - t < REDRAW_INTERVAL) {
+            return;
+        }
+        self.last_draw = Some(now);
+
+        let line = render(self.total, input, output, now - self.start);
+        eprint!("\r{}", line);
+        io::stderr().flush().ok();
+    }
+
+    /// Clear the progress line and print a summary
+    pub fn finish(&mut self, output: u64) {
+        if !self.enabled {
+            return;
+        }
+        let elapsed = self.start.elapsed();
+        eprintln!(
+            "\r\x1b[K{} in {:.2}s ({})",
+            format_bytes(output),
+            elapsed.as_secs_f64(),
+            format_rate(output, elapsed)
+        );
+    }
+}
+
+fn render(total: Option<u64>, input: u64, output: u64, elapsed: Duration) -> String {
+    let rate = format_rate(output, elapsed);
+    match total {
+        Some(total) if total > 0 => {
+            let done = std::cmp::min(input, total);
+            let filled = done * BAR_WIDTH / total;
+            format!(
+                "[{}{}] {:>3}%  {}",
+                "#".repeat(filled as usize),
+                " ".repeat((BAR_WIDTH - filled) as usize),
+                done * 100 / total,
+                rate
+            )
+        }
+        _ => format!("{}  {}", format_bytes(output), rate),
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / 1e6)
+}
+
+fn format_rate(bytes: u64, elapsed: Duration) -> String {
+    let secs = elapsed.as_secs_f64();
+    if secs == 0.0 {
+        return "- MB/s".to_owned();
+    }
+    format!("{:.1} MB/s", bytes as f64 / 1e6 / secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_line() {
+        assert_eq!(
+            render(Some(200), 100, 4_000_000, Duration::from_secs(2)),
+            "[###############               ]  50%  2.0 MB/s"
+        );
+        assert_eq!(
+            render(None, 100, 1_500_000, Duration::from_secs(1)),
+            "1.5 MB  1.5 MB/s"
+        );
+    }
+}
@@ -0,0 +1,149 @@
+//! `adc selftest`: compress and decompress a file and compare the result to the original.
+
+use crate::{compress::LevelArgs, json, Error, Result};
+use adc::{AdcDecoder, CompressionLevel};
+use std::{
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Uncompressed input file
+    input: PathBuf,
+    #[command(flatten)]
+    level: LevelArgs,
+}
+
+/// Outcome of a round trip.
+#[derive(PartialEq, Debug)]
+struct Report {
+    original_len: usize,
+    compressed_len: usize,
+    /// First offset at which the decompressed data differs from the original
+    mismatch: Option<usize>,
+    /// Error decoding the compressed data
+    error: Option<String>,
+}
+
+impl Report {
+    fn is_ok(&self) -> bool {
+        self.mismatch.is_none() && self.error.is_none()
+    }
+}
+
+pub fn run(args: Args, json: bool) -> Result<()> {
+    let data = fs::read(&args.input).map_err(|err| Error::with_path(&args.input, err))?;
+    let report = round_trip(&data, args.level.get());
+
+    if json {
+        println!("{}", json_report(&args.input, &report));
+    } else if report.is_ok() {
+        println!(
+            "{}: ok, {} bytes compressed to {} ({})",
+            args.input.display(),
+            report.original_len,
+            report.compressed_len,
+            percent(&report)
+        );
+    }
+    if report.is_ok() {
+        return Ok(());
+    }
+
+    let mut msg = match report.mismatch {
+        Some(offset) => format!("round trip differs from the original at offset {}", offset),
+        None => "round trip failed".to_owned(),
+    };
+    if let Some(ref error) = report.error {
+        msg = format!("{}, decoding failed: {}", msg, error);
+    }
+    Err(Error::with_path(
+        &args.input,
+        io::Error::new(io::ErrorKind::InvalidData, msg),
+    ))
+}
+
+/// Compress `data` and decode it again.
+fn round_trip(data: &[u8], level: CompressionLevel) -> Report {
+    let compressed = adc::compress_to_vec(data, level);
+    let mut decoded = Vec::new();
+    let error = AdcDecoder::new(&compressed[..])
+        .read_to_end(&mut decoded)
+        .err()
+        .map(|err| err.to_string());
+    Report {
+        original_len: data.len(),
+        compressed_len: compressed.len(),
+        mismatch: first_difference(data, &decoded),
+        error,
+    }
+}
+
+/// Offset of the first byte that differs, or of the end of the shorter slice.
+fn first_difference(a: &[u8], b: &[u8]) -> Option<usize> {
+    match a.iter().zip(b).position(|(x, y)| x != y) {
+        Some(offset) => Some(offset),
+        None if a.len() != b.len() => Some(a.len().min(b.len())),
+        None => None,
+    }
+}
+
+/// Compressed size relative to the original size.
+fn percent(report: &Report) -> String {
+    match report.original_len {
+        0 => "-".to_owned(),
+        len => format!("{:.1}%", report.compressed_len as f64 * 100.0 / len as f64),
+    }
+}
+
+/// `--json` output, `ratio` is the compressed size divided by the original size.
+fn json_report(path: &Path, report: &Report) -> String {
+    let ratio = match report.original_len {
+        0 => "null".to_owned(),
+        len => (report.compressed_len as f64 / len as f64).to_string(),
+    };
+    format!(
+        "{{\"path\":{},\"ok\":{},\"original_len\":{},\"compressed_len\":{},\"ratio\":{},\
+         \"mismatch_offset\":{},\"error\":{}}}",
+        json::string(&path.to_string_lossy()),
+        report.is_ok(),
+        report.original_len,
+        report.compressed_len,
+        ratio,
+        report
+            .mismatch
+            .map_or_else(|| "null".to_owned(), |offset| offset.to_string()),
+        report
+            .error
+            .as_ref()
+            .map_or_else(|| "null".to_owned(), |error| json::string(error))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report() {
+        let data = b"abcabcabcabcabcabcabcabc";
+        let report = round_trip(data, CompressionLevel::Default);
+        assert!(report.is_ok());
+        assert_eq!(report.original_len, 24);
+        assert_eq!(
+            json_report(Path::new("a.bin"), &report),
+            format!(
+                "{{\"path\":\"a.bin\",\"ok\":true,\"original_len\":24,\"compressed_len\":{},\
+                 \"ratio\":{},\"mismatch_offset\":null,\"error\":null}}",
+                report.compressed_len,
+                report.compressed_len as f64 / 24.0
+            )
+        );
+
+        assert_eq!(first_difference(b"abc", b"abc"), None);
+        assert_eq!(first_difference(b"abc", b"abd"), Some(2));
+        assert_eq!(first_difference(b"abc", b"ab"), Some(2));
+    }
+}
@@ -0,0 +1,193 @@
+//! `adc stats`: compression analytics of a stream.
+
+use crate::{input_name, open_input, parse_size, with_output, Error, Result};
+use adc::{AdcChunkType, StreamMap};
+use std::{io, path::PathBuf};
+
+const TYPES: [AdcChunkType; 3] = [
+    AdcChunkType::Plain,
+    AdcChunkType::TwoByte,
+    AdcChunkType::ThreeByte,
+];
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum Format {
+    Json,
+    Csv,
+}
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Compressed input file, stdin if omitted or `-`
+    input: Option<PathBuf>,
+    /// Output format, `--json` implies `json`
+    #[arg(long, value_enum, default_value_t = Format::Json)]
+    format: Format,
+    /// Size of the output regions for the per-region ratio (K, M, G suffixes allowed)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, default_value = "1M")]
+    region_size: u64,
+}
+
+pub fn run(args: Args, json: bool) -> Result<()> {
+    let map = adc::inspect(open_input(&args.input)?)
+        .map_err(|err| Error::with_path(&input_name(&args.input), err))?;
+    let stats = Stats::new(&map, args.region_size.max(1));
+
+    with_output(&None, |out| {
+        match if json { Format::Json } else { args.format } {
+            Format::Json => stats.write_json(out)?,
+            Format::Csv => stats.write_csv(out)?,
+        }
+        Ok(())
+    })
+}
+
+#[derive(Default)]
+struct TypeStats {
+    chunks: u64,
+    compressed: u64,
+    decompressed: u64,
+}
+
+struct Stats {
+    compressed: u64,
+    decompressed: u64,
+    types: [TypeStats; 3],
+    /// Compressed and decompressed bytes of each output region
+    regions: Vec<(u64, u64)>,
+    /// Run counts by distance, bucket `i` holds distances in `2^i..2^(i+1)`
+    distances: [u64; 17],
+}
+
+impl Stats {
+    fn new(map: &StreamMap, region_size: u64) -> Stats {
+        let mut stats = Stats {
+            compressed: map.compressed_len(),
+            decompressed: map.decompressed_len(),
+            types: Default::default(),
+            regions: Vec::new(),
+            distances: [0; 17],
+        };
+
+        for chunk in &map.chunks {
+            let t = &mut stats.types[TYPES.iter().position(|&t| t == chunk.kind).unwrap()];
+            t.chunks += 1;
+            t.compressed += chunk.compressed_len;
+            t.decompressed += chunk.decompressed_len;
+
+            let region = (chunk.decompressed_offset / region_size) as usize;
+            if stats.regions.len() <= region {
+                stats.regions.resize(region + 1, (0, 0));
+            }
+            stats.regions[region].0 += chunk.compressed_len;
+            stats.regions[region].1 += chunk.decompressed_len;
+
+            if let Some(offset) = chunk.offset {
+                let distance = u32::from(offset) + 1;
+                stats.distances[(31 - distance.leading_zeros()) as usize] += 1;
+            }
+        }
+        stats
+    }
+
+    fn write_json(&self, out: &mut dyn io::Write) -> io::Result<()> {
+        write!(
+            out,
+            "{{\"compressed_len\":{},\"decompressed_len\":{},\"ratio\":{},\"chunk_types\":{{",
+            self.compressed,
+            self.decompressed,
+            ratio(self.compressed, self.decompressed)
+        )?;
+        for (i, (kind, t)) in TYPES.iter().zip(&self.types).enumerate() {
+            write!(
+                out,
+                "{}\"{}\":{{\"chunks\":{},\"compressed_bytes\":{},\"decompressed_bytes\":{}}}",
+                if i == 0 { "" } else { "," },
+                kind,
+                t.chunks,
+                t.compressed,
+                t.decompressed
+            )?;
+        }
+        write!(out, "}},\"region_ratios\":[")?;
+        for (i, &(c, d)) in self.regions.iter().enumerate() {
+            write!(out, "{}{}", if i == 0 { "" } else { "," }, ratio(c, d))?;
+        }
+        write!(out, "],\"distance_histogram\":{{")?;
+        for (i, count) in self.distances.iter().enumerate() {
+            write!(
+                out,
+                "{}\"{}\":{}",
+                if i == 0 { "" } else { "," },
+                1 << i,
+                count
+            )?;
+        }
+        writeln!(out, "}}}}")
+    }
+
+    fn write_csv(&self, out: &mut dyn io::Write) -> io::Result<()> {
+        writeln!(out, "metric,key,value")?;
+        writeln!(out, "total,compressed_len,{}", self.compressed)?;
+        writeln!(out, "total,decompressed_len,{}", self.decompressed)?;
+        writeln!(
+            out,
+            "total,ratio,{}",
+            ratio(self.compressed, self.decompressed)
+        )?;
+        for (kind, t) in TYPES.iter().zip(&self.types) {
+            writeln!(out, "chunks,{},{}", kind, t.chunks)?;
+            writeln!(out, "compressed_bytes,{},{}", kind, t.compressed)?;
+            writeln!(out, "decompressed_bytes,{},{}", kind, t.decompressed)?;
+        }
+        for (i, &(c, d)) in self.regions.iter().enumerate() {
+            writeln!(out, "region_ratio,{},{}", i, ratio(c, d))?;
+        }
+        for (i, count) in self.distances.iter().enumerate() {
+            writeln!(out, "distance_histogram,{},{}", 1 << i, count)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compressed size relative to the decompressed size, with four decimals.
+fn ratio(compressed: u64, decompressed: u64) -> String {
+    if decompressed == 0 {
+        return "0".to_owned();
+    }
+    format!("{:.4}", compressed as f64 / decompressed as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // plain (5 -> 4), two byte distance 1 (2 -> 3), three byte distance 7 (3 -> 4)
+    const INPUT: &[u8] = &[0x83, 0xfe, 0xed, 0xfa, 0xce, 0x00, 0x00, 0x40, 0x00, 0x06];
+
+    #[test]
+    fn collect() {
+        let stats = Stats::new(&adc::inspect(INPUT).unwrap(), 4);
+
+        assert_eq!(stats.types[0].chunks, 1);
+        assert_eq!(stats.types[1].decompressed, 3);
+        assert_eq!(stats.types[2].compressed, 3);
+        assert_eq!(stats.regions, [(5, 4), (5, 7)]);
+        assert_eq!(stats.distances[0], 1);
+        assert_eq!(stats.distances[2], 1);
+    }
+
+    #[test]
+    fn csv() {
+        let mut out = Vec::new();
+        Stats::new(&adc::inspect(INPUT).unwrap(), 1 << 20)
+            .write_csv(&mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.starts_with("metric,key,value\ntotal,compressed_len,10\n"));
+        assert!(out.contains("\nchunks,three_byte,1\n"));
+        assert!(out.contains("\nregion_ratio,0,0.9091\n"));
+        assert!(out.contains("\ndistance_histogram,4,1\n"));
+    }
+}
@@ -0,0 +1,130 @@
+//! `adc verify`: check streams for corruption without writing any output.
+
+use crate::{input_name, json, open_input, Error, Result};
+use adc::{AdcDecoder, AdcError};
+use std::{io, path::PathBuf};
+
+#[derive(clap::Args)]
+pub struct Args {
+    /// Compressed input files, stdin if omitted or `-`
+    inputs: Vec<PathBuf>,
+    /// Only report failures
+    #[arg(short, long)]
+    quiet: bool,
+    /// Fail if a stream decompresses to more than SIZE bytes (K, M, G suffixes allowed)
+    #[arg(long, value_name = "SIZE", value_parser = crate::parse_size)]
+    max_output: Option<u64>,
+}
+
+pub fn run(args: Args, json: bool) -> Result<()> {
+    let inputs = if args.inputs.is_empty() {
+        vec![None]
+    } else {
+        args.inputs.into_iter().map(Some).collect()
+    };
+
+    let mut failed = 0;
+    for input in &inputs {
+        let res = verify(input, args.max_output);
+        if json {
+            println!("{}", json_result(input, &res));
+            failed += res.is_err() as usize;
+            continue;
+        }
+        match res {
+            Ok(len) if !args.quiet => {
+                println!("{}: ok, {} bytes", input_name(input).display(), len)
+            }
+            Ok(_) => {}
+            Err(err) => {
+                eprintln!("adc: {}", err);
+                failed += 1;
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} of {} streams are corrupt", failed, inputs.len()),
+        )
+        .into());
+    }
+    Ok(())
+}
+
+/// One line of `--json` output, e.g. `{"path":"a.adc","ok":true,"decompressed_len":5}`.
+fn json_result(input: &Option<PathBuf>, res: &Result<u64>) -> String {
+    let path = json::string(&input_name(input).to_string_lossy());
+    match res {
+        Ok(len) => format!(
+            "{{\"path\":{},\"ok\":true,\"decompressed_len\":{}}}",
+            path, len
+        ),
+        Err(err) => format!(
+            "{{\"path\":{},\"ok\":false,\"error\":{}}}",
+            path,
+            json::string(&err.err.to_string())
+        ),
+    }
+}
+
+/// Decode `input` and discard the output, returns the decompressed length.
+fn verify(input: &Option<PathBuf>, max_output: Option<u64>) -> Result<u64> {
+    let mut decoder = AdcDecoder::new(open_input(input)?);
+    if let Some(max) = max_output {
+        decoder = decoder.max_output_size(max);
+    }
+
+    io::copy(&mut decoder, &mut io::sink()).map_err(|err| {
+        // decoding errors already locate the corrupt chunk
+        if err.get_ref().is_some_and(|inner| inner.is::<AdcError>()) {
+            return Error::with_path(&input_name(input), err);
+        }
+        let err = io::Error::new(
+            err.kind(),
+            format!(
+                "{} at compressed offset {}, decompressed offset {}",
+                err,
+                decoder.total_in(),
+                decoder.total_out()
+            ),
+        );
+        Error::with_path(&input_name(input), err)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn corrupt_stream() {
+        let path = std::env::temp_dir().join(format!("adc-cli-verify-{}", std::process::id()));
+        fs::write(&path, [0x81, 0xab, 0xcd, 0x00, 0x05]).unwrap();
+
+        let err = verify(&Some(path.clone()), None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "{}: invalid chunk offset in chunk 1 at compressed offset 3, decompressed offset 2 \
+                 (header 00 05)",
+                path.display()
+            )
+        );
+
+        assert_eq!(
+            json_result(&Some(path.clone()), &Err(err)),
+            format!(
+                "{{\"path\":{},\"ok\":false,\"error\":\"invalid chunk offset in chunk 1 at \
+                 compressed offset 3, decompressed offset 2 (header 00 05)\"}}",
+                json::string(&path.to_string_lossy())
+            )
+        );
+
+        fs::write(&path, [0x81, 0xab, 0xcd, 0x00, 0x01]).unwrap();
+        assert_eq!(verify(&Some(path.clone()), None).unwrap(), 5);
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,207 @@
+//! Uniform decoding of the block types found in ADC-compressed disk images.
+//!
+//! Besides ADC-compressed blocks, images store uncompressed blocks and blocks that are all
+//! zeros. [`AnyBlockDecoder`] handles all three behind a single `Read` implementation, and
+//! [`detect`] picks between ADC and raw data for input of unknown kind.
+
+#[cfg(feature = "fallible-alloc")]
+use crate::out_of_memory;
+use crate::{read_full, AdcChunk, AdcDecoder};
+use std::io::{self, prelude::*};
+
+/// Number of bytes [`detect`] examines.
+pub const DETECT_LEN: usize = 4096;
+
+/// Input of a decoder created by [`detect`], the examined bytes followed by the rest.
+pub type Sniffed<R> = io::Chain<io::Cursor<Vec<u8>>, R>;
+
+/// Storage type of a block.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum BlockKind {
+    /// The block is ADC-compressed
+    Adc,
+    /// The block is stored uncompressed
+    Raw,
+    /// The block is all zeros and has no stored data
+    ZeroFill,
+}
+
+/// Decoder for a block of any supported [`BlockKind`].
+#[non_exhaustive]
+// most blocks are ADC blocks, boxing the decoder would only add an allocation to them
+#[allow(clippy::large_enum_variant)]
+pub enum AnyBlockDecoder<R> {
+    /// Decoder for an ADC-compressed block
+    Adc(AdcDecoder<R>),
+    /// Passthrough for an uncompressed block
+    Raw(R),
+    /// Zero source for a zero-filled block
+    ZeroFill(io::Take<io::Repeat>),
+}
+
+impl<R: Read> AnyBlockDecoder<R> {
+    /// Create a decoder for a block of the given kind
+    ///
+    /// `input` is the stored data of the block, `len` the length of the decoded block. `len`
+    /// is only used for zero-filled blocks, which do not read from `input` at all.
+    pub fn new(kind: BlockKind, input: R, len: u64) -> AnyBlockDecoder<R> {
+        match kind {
+            BlockKind::Adc => AnyBlockDecoder::Adc(AdcDecoder::new(input)),
+            BlockKind::Raw => AnyBlockDecoder::Raw(input),
+            BlockKind::ZeroFill => AnyBlockDecoder::ZeroFill(io::repeat(0).take(len)),
+        }
+    }
+
+    /// Get the kind of block being decoded
+    pub fn kind(&self) -> BlockKind {
+        match self {
+            AnyBlockDecoder::Adc(_) => BlockKind::Adc,
+            AnyBlockDecoder::Raw(_) => BlockKind::Raw,
+            AnyBlockDecoder::ZeroFill(_) => BlockKind::ZeroFill,
+        }
+    }
+}
+
+impl<R: Read> Read for AnyBlockDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            AnyBlockDecoder::Adc(d) => d.read(buf),
+            AnyBlockDecoder::Raw(r) => r.read(buf),
+            AnyBlockDecoder::ZeroFill(z) => z.read(buf),
+        }
+    }
+}
+
+/// Create a decoder for input that may or may not be ADC-compressed
+///
+/// Reads up to [`DETECT_LEN`] bytes and checks if they form a valid beginning of an ADC
+/// stream. If they do, the input is decoded, otherwise it is passed through unchanged.
+/// [`kind`](AnyBlockDecoder::kind) tells which one was chosen.
+///
+/// This is a heuristic. Short raw inputs can happen to be valid ADC streams, and an ADC
+/// stream that is corrupt within the examined bytes is passed through.
+pub fn detect<R: Read>(mut input: R) -> io::Result<AnyBlockDecoder<Sniffed<R>>> {
+    #[cfg(feature = "fallible-alloc")]
+    let mut prefix = {
+        let mut prefix = Vec::new();
+        prefix
+            .try_reserve_exact(DETECT_LEN)
+            .map_err(out_of_memory)?;
+        prefix.resize(DETECT_LEN, 0);
+        prefix
+    };
+    #[cfg(not(feature = "fallible-alloc"))]
+    let mut prefix = vec![0; DETECT_LEN];
+    let len = read_full(&mut input, &mut prefix)?;
+    prefix.truncate(len);
+
+    let kind = if looks_like_adc(&prefix, len < DETECT_LEN) {
+        BlockKind::Adc
+    } else {
+        BlockKind::Raw
+    };
+    Ok(AnyBlockDecoder::new(
+        kind,
+        io::Cursor::new(prefix).chain(input),
+        0,
+    ))
+}
+
+/// Check if `prefix` is the beginning of a valid ADC stream, `complete` if it is all of it.
+fn looks_like_adc(prefix: &[u8], complete: bool) -> bool {
+    if prefix.is_empty() {
+        return false;
+    }
+
+    let mut pos = 0;
+    let mut out = 0;
+    while pos < prefix.len() {
+        let (chunk, header_len) = match AdcChunk::parse(&prefix[pos..]) {
+            Some(parsed) => parsed,
+            None => return !complete,
+        };
+        pos += header_len;
+
+        match chunk.offset() {
+            None => {
+                pos += chunk.decompressed_len();
+                if pos > prefix.len() {
+                    return !complete;
+                }
+            }
+            Some(offset) if usize::from(offset) >= out => return false,
+            Some(_) => {}
+        }
+        out += chunk.decompressed_len();
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(kind: BlockKind, input: &[u8], len: u64) -> Vec<u8> {
+        let mut data = Vec::new();
+        AnyBlockDecoder::new(kind, input, len)
+            .read_to_end(&mut data)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn all_kinds() {
+        let input: &[u8] = &[0x83, 0xfe, 0xed, 0xfa, 0xce, 0x00, 0x00];
+
+        assert_eq!(
+            decode(BlockKind::Adc, input, 0),
+            [0xfe, 0xed, 0xfa, 0xce, 0xce, 0xce, 0xce]
+        );
+        assert_eq!(decode(BlockKind::Raw, input, 0), input);
+        assert_eq!(decode(BlockKind::ZeroFill, input, 5), [0; 5]);
+    }
+
+    fn detected(input: &[u8]) -> (BlockKind, Vec<u8>) {
+        let mut d = detect(input).unwrap();
+        let mut data = Vec::new();
+        d.read_to_end(&mut data).unwrap();
+        (d.kind(), data)
+    }
+
+    #[test]
+    fn detect_kind() {
+        let input: &[u8] = &[0x83, 0xfe, 0xed, 0xfa, 0xce, 0x00, 0x00];
+        assert_eq!(
+            detected(input),
+            (
+                BlockKind::Adc,
+                vec![0xfe, 0xed, 0xfa, 0xce, 0xce, 0xce, 0xce]
+            )
+        );
+
+        let text = b"Hello, this is not compressed".to_vec();
+        assert_eq!(detected(&text), (BlockKind::Raw, text));
+
+        // a truncated chunk at the very end is not valid ADC
+        let input: &[u8] = &[0x83, 0xfe, 0xed];
+        assert_eq!(detected(input), (BlockKind::Raw, input.to_vec()));
+        assert_eq!(detected(&[]), (BlockKind::Raw, vec![]));
+    }
+
+    #[test]
+    fn detect_long_input() {
+        let stream = crate::generator::StreamGenerator::new(3)
+            .text(20000)
+            .finish();
+        assert_eq!(
+            detected(&stream.compressed),
+            (BlockKind::Adc, stream.decompressed.clone())
+        );
+        // the examined bytes are passed through along with the rest
+        assert_eq!(
+            detected(&stream.decompressed),
+            (BlockKind::Raw, stream.decompressed)
+        );
+    }
+}
@@ -0,0 +1,129 @@
+//! Types reading compressed data from a `BufRead`.
+//!
+//! [`Decoder`] parses chunk headers and plain data straight out of the buffer of the input,
+//! instead of reading them piece by piece like [`read::Decoder`](crate::read::Decoder). Use
+//! it for inputs that are buffered anyway, like a `BufReader<File>` or an in-memory cursor.
+//!
+//! ```
+//! use adc::bufread::Decoder;
+//! use std::io::{BufReader, Read};
+//!
+//! let input: &[u8] = &[0x81, 0xab, 0xcd, 0x00, 0x01];
+//! let mut data = Vec::new();
+//! Decoder::new(BufReader::new(input)).read_to_end(&mut data).unwrap();
+//! assert_eq!(data, [0xab, 0xcd, 0xab, 0xcd, 0xab]);
+//! ```
+
+use crate::stream::{decompress, AdcState, Status};
+use std::io::{self, BufRead, Read};
+
+/// Decoder for ADC data read from a buffered input.
+pub struct Decoder<R> {
+    input: R,
+    state: AdcState,
+}
+
+impl<R: BufRead> Decoder<R> {
+    /// Create a decoder reading compressed data from `input`
+    pub fn new(input: R) -> Decoder<R> {
+        Decoder {
+            input,
+            state: AdcState::new(),
+        }
+    }
+
+    /// Get the number of compressed bytes consumed so far
+    pub fn total_in(&self) -> u64 {
+        self.state.total_in()
+    }
+
+    /// Get the number of decompressed bytes produced so far
+    pub fn total_out(&self) -> u64 {
+        self.state.total_out()
+    }
+
+    /// Get a reference to the input
+    pub fn get_ref(&self) -> &R {
+        &self.input
+    }
+
+    /// Get a mutable reference to the input
+    ///
+    /// Consuming data from it makes the decoder continue with other data than the rest of
+    /// the stream.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.input
+    }
+
+    /// Return the input, positioned right after the last chunk read
+    pub fn into_inner(self) -> R {
+        self.input
+    }
+}
+
+impl<R: BufRead> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            // an empty buffer at the end of the input tells the state machine that the
+            // stream ended
+            let input = match self.input.fill_buf() {
+                Ok(input) => input,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            let (status, read, written) = decompress(&mut self.state, input, buf);
+            self.input.consume(read);
+
+            match status {
+                Status::NeedsMoreInput if written == 0 => {}
+                Status::Done | Status::NeedsMoreInput | Status::NeedsMoreOutput => {
+                    return Ok(written)
+                }
+                // errors stick, return the data before it first
+                _ if written > 0 => return Ok(written),
+                status => return Err(self.state.error(status).into()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generator::StreamGenerator, AdcError};
+    use std::io::BufReader;
+
+    #[test]
+    fn small_buffers() {
+        let stream = StreamGenerator::new(16)
+            .text(20_000)
+            .zeros(5000)
+            .random(3000)
+            .finish();
+        for &capacity in &[1, 2, 3, 100, 1 << 16] {
+            let input = BufReader::with_capacity(capacity, &stream.compressed[..]);
+            let mut decoder = Decoder::new(input);
+            let mut data = Vec::new();
+            decoder.read_to_end(&mut data).unwrap();
+
+            assert!(data == stream.decompressed, "capacity {}", capacity);
+            assert_eq!(decoder.total_in(), stream.compressed.len() as u64);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let input: &[u8] = &[0x81, 0xab, 0xcd, 0x00, 0x05];
+        let mut data = Vec::new();
+        let err = Decoder::new(input).read_to_end(&mut data).unwrap_err();
+        assert!(matches!(AdcError::from(err), AdcError::InvalidOffset(c) if c.index == 1));
+        assert_eq!(data, [0xab, 0xcd]);
+
+        let input: &[u8] = &[0x83, 0xfe, 0xed];
+        let err = Decoder::new(input).read_to_end(&mut data).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,91 @@
+//! Big endian integer reads and writes, so the crate doesn't need a dependency for them.
+
+use std::{
+    convert::TryFrom,
+    io::{self, Read, Write},
+};
+
+/// Extension of `Read` for reading big endian integers.
+pub(crate) trait ReadBe: Read {
+    fn read_u32_be(&mut self) -> io::Result<u32> {
+        let mut buf = [0; 4];
+        self.read_exact(&mut buf)?;
+        Ok(u32::from_be_bytes(buf))
+    }
+
+    fn read_u64_be(&mut self) -> io::Result<u64> {
+        let mut buf = [0; 8];
+        self.read_exact(&mut buf)?;
+        Ok(u64::from_be_bytes(buf))
+    }
+
+    /// Read a `u32` length and that many bytes, failing with `InvalidData` above `max`
+    fn read_bytes_be(&mut self, max: usize) -> io::Result<Vec<u8>> {
+        let len = self.read_u32_be()? as usize;
+        if len > max {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "byte string too long",
+            ));
+        }
+        let mut buf = vec![0; len];
+        self.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+}
+
+impl<R: Read + ?Sized> ReadBe for R {}
+
+/// Extension of `Write` for writing big endian integers.
+pub(crate) trait WriteBe: Write {
+    fn write_u32_be(&mut self, value: u32) -> io::Result<()> {
+        self.write_all(&value.to_be_bytes())
+    }
+
+    fn write_u64_be(&mut self, value: u64) -> io::Result<()> {
+        self.write_all(&value.to_be_bytes())
+    }
+
+    /// Write the length of `bytes` as a `u32` followed by the bytes
+    fn write_bytes_be(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let len = u32::try_from(bytes.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "byte string too long"))?;
+        self.write_u32_be(len)?;
+        self.write_all(bytes)
+    }
+}
+
+impl<W: Write + ?Sized> WriteBe for W {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn big_endian() {
+        let mut input: &[u8] = &[4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
+        assert_eq!(input.read_u32_be().unwrap(), 0x0405_0607);
+        assert_eq!(input.read_u64_be().unwrap(), 0x0809_0a0b_0c0d_0e0f);
+        assert_eq!(
+            input.read_u32_be().unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn byte_strings() {
+        let mut output = Vec::new();
+        output.write_u64_be(0x0102).unwrap();
+        output.write_bytes_be(b"abc").unwrap();
+        assert_eq!(
+            output,
+            [0, 0, 0, 0, 0, 0, 1, 2, 0, 0, 0, 3, b'a', b'b', b'c']
+        );
+
+        let mut input = &output[8..];
+        assert_eq!(input.read_bytes_be(3).unwrap(), b"abc");
+        let mut input = &output[8..];
+        let err = input.read_bytes_be(2).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,235 @@
+//! C interface, available with the `capi` feature.
+//!
+//! Build a static or dynamic library with
+//! `cargo rustc --release --lib --features capi --crate-type staticlib` (or `cdylib`) and
+//! generate the header with `cbindgen --config cbindgen.toml --output adc.h`.
+//!
+//! [`adc_decompress`] decodes a whole stream held in memory, the `adc_decoder_*` functions
+//! decode a stream piece by piece like the [`stream`](crate::stream) module:
+//!
+//! ```c
+//! adc_decoder *d = adc_decoder_new();
+//! size_t read, written;
+//! int status = adc_decode(d, in, in_len, &read, out, out_len, &written);
+//! /* ... */
+//! adc_decoder_free(d);
+//! ```
+
+use crate::stream::{decompress, AdcState, Status};
+use std::{os::raw::c_int, ptr, slice};
+
+/// The stream ended at a chunk boundary, all output was written
+pub const ADC_OK: c_int = 0;
+/// All of the input was consumed, pass more to continue
+pub const ADC_NEEDS_MORE_INPUT: c_int = 1;
+/// The output is full and more decompressed data is pending
+pub const ADC_NEEDS_MORE_OUTPUT: c_int = 2;
+/// A run referenced data before the start of the output
+pub const ADC_INVALID_OFFSET: c_int = -1;
+/// The stream ended in the middle of a chunk
+pub const ADC_TRUNCATED: c_int = -2;
+/// A required pointer was null
+pub const ADC_INVALID_ARGUMENT: c_int = -3;
+
+/// Streaming decoder state, opaque to C.
+#[allow(non_camel_case_types)]
+pub struct adc_decoder(AdcState);
+
+fn status_code(status: Status) -> c_int {
+    match status {
+        Status::Done => ADC_OK,
+        Status::NeedsMoreInput => ADC_NEEDS_MORE_INPUT,
+        Status::NeedsMoreOutput => ADC_NEEDS_MORE_OUTPUT,
+        Status::Truncated => ADC_TRUNCATED,
+        // the state has the full window, anything else is an invalid offset
+        _ => ADC_INVALID_OFFSET,
+    }
+}
+
+/// Turn a pointer and length from C into a slice, `None` for a null pointer.
+///
+/// # Safety
+///
+/// A non-null `data` must be valid for `len` bytes.
+unsafe fn input<'a>(data: *const u8, len: usize) -> Option<&'a [u8]> {
+    match len {
+        0 => Some(&[]),
+        _ if data.is_null() => None,
+        _ => Some(slice::from_raw_parts(data, len)),
+    }
+}
+
+/// Mutable counterpart of [`input`].
+///
+/// # Safety
+///
+/// A non-null `data` must be valid for writes of `len` bytes.
+unsafe fn output<'a>(data: *mut u8, len: usize) -> Option<&'a mut [u8]> {
+    match len {
+        0 => Some(&mut []),
+        _ if data.is_null() => None,
+        _ => Some(slice::from_raw_parts_mut(data, len)),
+    }
+}
+
+/// Decompress the complete stream of `in_len` bytes at `in_ptr` into the `out_len` bytes
+/// at `out_ptr`
+///
+/// Stores the number of bytes written in `written` unless it is null. Returns `ADC_OK`,
+/// `ADC_NEEDS_MORE_OUTPUT` if the output is too small, or a negative error code.
+///
+/// # Safety
+///
+/// `in_ptr` must be valid for reads of `in_len` bytes and `out_ptr` for writes of `out_len`
+/// bytes, `written` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn adc_decompress(
+    in_ptr: *const u8,
+    in_len: usize,
+    out_ptr: *mut u8,
+    out_len: usize,
+    written: *mut usize,
+) -> c_int {
+    let (input, output) = match (input(in_ptr, in_len), output(out_ptr, out_len)) {
+        (Some(input), Some(output)) => (input, output),
+        _ => return ADC_INVALID_ARGUMENT,
+    };
+
+    let mut state = AdcState::new();
+    let (mut status, _, mut len) = decompress(&mut state, input, output);
+    if status == Status::NeedsMoreInput {
+        // all input was consumed, mark the end of the stream
+        let (end, _, n) = decompress(&mut state, &[], &mut output[len..]);
+        status = end;
+        len += n;
+    }
+    if !written.is_null() {
+        *written = len;
+    }
+    status_code(status)
+}
+
+/// Create a streaming decoder, free it with [`adc_decoder_free`]
+#[no_mangle]
+pub extern "C" fn adc_decoder_new() -> *mut adc_decoder {
+    Box::into_raw(Box::new(adc_decoder(AdcState::new())))
+}
+
+/// Free a decoder created by [`adc_decoder_new`], null is ignored
+///
+/// # Safety
+///
+/// `decoder` must be null or a decoder from [`adc_decoder_new`] not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn adc_decoder_free(decoder: *mut adc_decoder) {
+    if !decoder.is_null() {
+        drop(Box::from_raw(decoder));
+    }
+}
+
+/// Decompress from `in_len` bytes at `in_ptr` into the `out_len` bytes at `out_ptr`
+///
+/// Stores the number of bytes consumed in `read` and the number of bytes written in
+/// `written`, unconsumed input has to be passed again. An empty input marks the end of the
+/// stream. Returns one of the `ADC_*` status codes, errors are negative and stick.
+///
+/// # Safety
+///
+/// `decoder` must come from [`adc_decoder_new`], `in_ptr` must be valid for reads of
+/// `in_len` bytes, `out_ptr` for writes of `out_len` bytes, and `read` and `written` for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn adc_decode(
+    decoder: *mut adc_decoder,
+    in_ptr: *const u8,
+    in_len: usize,
+    read: *mut usize,
+    out_ptr: *mut u8,
+    out_len: usize,
+    written: *mut usize,
+) -> c_int {
+    let decoder = match decoder.as_mut() {
+        Some(decoder) => decoder,
+        None => return ADC_INVALID_ARGUMENT,
+    };
+    let (input, output) = match (input(in_ptr, in_len), output(out_ptr, out_len)) {
+        (Some(input), Some(output)) if !read.is_null() && !written.is_null() => (input, output),
+        _ => return ADC_INVALID_ARGUMENT,
+    };
+
+    let (status, consumed, len) = decompress(&mut decoder.0, input, output);
+    ptr::write(read, consumed);
+    ptr::write(written, len);
+    status_code(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::StreamGenerator;
+    use std::cmp;
+
+    #[test]
+    fn one_shot() {
+        let input = [0x81, 0xab, 0xcd, 0x00, 0x01];
+        let mut output = [0; 8];
+        let mut written = 0;
+        let status = unsafe {
+            adc_decompress(
+                input.as_ptr(),
+                input.len(),
+                output.as_mut_ptr(),
+                output.len(),
+                &mut written,
+            )
+        };
+        assert_eq!((status, written), (ADC_OK, 5));
+        assert_eq!(output[..5], [0xab, 0xcd, 0xab, 0xcd, 0xab]);
+
+        let status =
+            unsafe { adc_decompress(input.as_ptr(), 4, output.as_mut_ptr(), 8, ptr::null_mut()) };
+        assert_eq!(status, ADC_TRUNCATED);
+        let status =
+            unsafe { adc_decompress(input.as_ptr(), 5, output.as_mut_ptr(), 4, &mut written) };
+        assert_eq!((status, written), (ADC_NEEDS_MORE_OUTPUT, 4));
+        let status =
+            unsafe { adc_decompress(ptr::null(), 5, output.as_mut_ptr(), 4, &mut written) };
+        assert_eq!(status, ADC_INVALID_ARGUMENT);
+    }
+
+    #[test]
+    fn streaming() {
+        let stream = StreamGenerator::new(21).text(5000).zeros(1000).finish();
+        let decoder = adc_decoder_new();
+        let mut data = Vec::new();
+        let mut buf = [0; 100];
+        let mut pos = 0;
+        loop {
+            let end = cmp::min(pos + 7, stream.compressed.len());
+            let input = &stream.compressed[pos..end];
+            let (mut read, mut written) = (0, 0);
+            let status = unsafe {
+                adc_decode(
+                    decoder,
+                    input.as_ptr(),
+                    input.len(),
+                    &mut read,
+                    buf.as_mut_ptr(),
+                    buf.len(),
+                    &mut written,
+                )
+            };
+            pos += read;
+            data.extend_from_slice(&buf[..written]);
+            match status {
+                ADC_NEEDS_MORE_INPUT | ADC_NEEDS_MORE_OUTPUT => {}
+                status => {
+                    assert_eq!(status, ADC_OK);
+                    break;
+                }
+            }
+        }
+        unsafe { adc_decoder_free(decoder) };
+        assert!(data == stream.decompressed);
+    }
+}
@@ -0,0 +1,188 @@
+//! UDIF checksum verification of decoded block data.
+//!
+//! Disk images store a CRC-32 of the decompressed data of every block. [`ChecksumDecoder`]
+//! computes it while decoding and compares it to the stored value once the stream ends.
+
+use crate::AdcDecoder;
+use std::{
+    error::Error,
+    fmt,
+    io::{self, prelude::*},
+};
+
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// Incremental CRC-32 as used by UDIF checksums.
+#[derive(Clone, Debug)]
+pub struct Crc32(u32);
+
+impl Crc32 {
+    /// Create a new hasher
+    pub fn new() -> Crc32 {
+        Crc32(!0)
+    }
+
+    /// Feed more data into the checksum
+    pub fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = CRC32_TABLE[((self.0 ^ u32::from(byte)) & 0xff) as usize] ^ (self.0 >> 8);
+        }
+    }
+
+    /// Get the checksum of all data fed so far
+    pub fn sum(&self) -> u32 {
+        !self.0
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Crc32 {
+        Crc32::new()
+    }
+}
+
+/// Compute the CRC-32 of `bytes`.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    crc.update(bytes);
+    crc.sum()
+}
+
+/// Checksum of decoded block data did not match the block table.
+///
+/// Returned as the payload of an [`io::Error`] of kind `InvalidData`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub struct ChecksumMismatch {
+    /// Index of the block in the block table
+    pub block: usize,
+    /// Checksum recorded in the block table
+    pub expected: u32,
+    /// Checksum of the decoded data
+    pub actual: u32,
+}
+
+impl fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "block {}: checksum mismatch, expected {:08x}, got {:08x}",
+            self.block, self.expected, self.actual
+        )
+    }
+}
+
+impl Error for ChecksumMismatch {}
+
+/// Decoder verifying the CRC-32 of the decompressed data at the end of the stream.
+pub struct ChecksumDecoder<R> {
+    inner: AdcDecoder<R>,
+    crc: Crc32,
+    block: usize,
+    expected: u32,
+}
+
+impl<R: Read> ChecksumDecoder<R> {
+    /// Create a decoder for block number `block` whose data should have the CRC-32 `expected`
+    pub fn new(input: R, block: usize, expected: u32) -> ChecksumDecoder<R> {
+        ChecksumDecoder {
+            inner: AdcDecoder::new(input),
+            crc: Crc32::new(),
+            block,
+            expected,
+        }
+    }
+
+    /// Get the checksum of the data decoded so far
+    pub fn checksum(&self) -> u32 {
+        self.crc.sum()
+    }
+}
+
+impl<R: Read> Read for ChecksumDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let n = self.inner.read(buf)?;
+        self.crc.update(&buf[..n]);
+
+        if n == 0 && self.checksum() != self.expected {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                ChecksumMismatch {
+                    block: self.block,
+                    expected: self.expected,
+                    actual: self.checksum(),
+                },
+            ));
+        }
+
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &[u8] = &[0x83, 0xfe, 0xed, 0xfa, 0xce, 0x00, 0x00];
+    const OUTPUT: &[u8] = &[0xfe, 0xed, 0xfa, 0xce, 0xce, 0xce, 0xce];
+
+    #[test]
+    fn check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn matching_checksum() {
+        let mut d = ChecksumDecoder::new(INPUT, 0, crc32(OUTPUT));
+        let mut data = Vec::new();
+        d.read_to_end(&mut data).unwrap();
+
+        assert_eq!(data, OUTPUT);
+    }
+
+    #[test]
+    fn mismatching_checksum() {
+        let mut d = ChecksumDecoder::new(INPUT, 5, 0x1234_5678);
+        let err = d.read_to_end(&mut Vec::new()).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let mismatch = err
+            .into_inner()
+            .unwrap()
+            .downcast::<ChecksumMismatch>()
+            .unwrap();
+        assert_eq!(
+            *mismatch,
+            ChecksumMismatch {
+                block: 5,
+                expected: 0x1234_5678,
+                actual: crc32(OUTPUT),
+            }
+        );
+    }
+}
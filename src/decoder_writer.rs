@@ -0,0 +1,192 @@
+//! Decompression of data pushed through the `Write` trait.
+
+use crate::stream::{decompress, AdcState, Status};
+use std::io::{self, Write};
+
+/// Size of the buffer for decompressed output.
+const BUF_SIZE: usize = 32 * 1024;
+
+/// Decompressor writing the decompressed data to an underlying writer.
+///
+/// Compressed data written to it is decoded as it arrives, in pieces of any size, which
+/// suits producers pushing data like network callbacks. Decompressed data is buffered and
+/// written out on the next write, [`flush`](Write::flush) or
+/// [`finish`](AdcDecoderWriter::finish). Dropping the decoder also finishes the stream,
+/// but ignores any errors.
+///
+/// ```
+/// use adc::AdcDecoderWriter;
+/// use std::io::Write;
+///
+/// let mut decoder = AdcDecoderWriter::new(Vec::new());
+/// decoder.write_all(&[0x81, 0xab, 0xcd]).unwrap();
+/// decoder.write_all(&[0x00, 0x01]).unwrap();
+/// let data = decoder.finish().unwrap();
+/// assert_eq!(data, [0xab, 0xcd, 0xab, 0xcd, 0xab]);
+/// ```
+pub struct AdcDecoderWriter<W: Write> {
+    output: Option<W>,
+    state: AdcState,
+    buf: Box<[u8]>,
+    pos: usize,
+    end: usize,
+}
+
+impl<W: Write> AdcDecoderWriter<W> {
+    /// Create a decoder writing the decompressed data to `output`
+    pub fn new(output: W) -> AdcDecoderWriter<W> {
+        AdcDecoderWriter {
+            output: Some(output),
+            state: AdcState::new(),
+            buf: vec![0; BUF_SIZE].into_boxed_slice(),
+            pos: 0,
+            end: 0,
+        }
+    }
+
+    /// Get the number of compressed bytes decoded so far
+    pub fn total_in(&self) -> u64 {
+        self.state.total_in()
+    }
+
+    /// Get the number of decompressed bytes produced so far, including buffered ones
+    pub fn total_out(&self) -> u64 {
+        self.state.total_out()
+    }
+
+    /// Check that the stream ended on a chunk boundary, write out all data and flush the
+    /// writer
+    ///
+    /// Fails with [`AdcError::TruncatedChunk`](crate::AdcError::TruncatedChunk) if the data
+    /// written so far ends within a chunk, and with the error of a corrupt chunk whose
+    /// header was already taken by a write.
+    pub fn try_finish(&mut self) -> io::Result<()> {
+        loop {
+            self.dump()?;
+            // no input marks the end of the stream
+            let (status, _, written) = decompress(&mut self.state, &[], &mut self.buf);
+            self.pos = 0;
+            self.end = written;
+            match status {
+                Status::NeedsMoreOutput => {}
+                Status::Done => break,
+                status if written == 0 => return Err(self.state.error(status).into()),
+                _ => {}
+            }
+        }
+        self.dump()?;
+        self.get_mut().flush()
+    }
+
+    /// Check the end of the stream like [`try_finish`](AdcDecoderWriter::try_finish) and
+    /// return the writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.try_finish()?;
+        Ok(self.output.take().expect("output taken before finish"))
+    }
+
+    /// Get a reference to the writer
+    pub fn get_ref(&self) -> &W {
+        self.output.as_ref().expect("output taken before finish")
+    }
+
+    /// Get a mutable reference to the writer
+    ///
+    /// Writing to it directly mixes the data with decompressed data that is still buffered.
+    pub fn get_mut(&mut self) -> &mut W {
+        self.output.as_mut().expect("output taken before finish")
+    }
+
+    /// Write out the buffered output, keeping what is left if the writer fails.
+    fn dump(&mut self) -> io::Result<()> {
+        while self.pos < self.end {
+            let output = self.output.as_mut().expect("output taken before finish");
+            match output.write(&self.buf[self.pos..self.end]) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => self.pos += n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for AdcDecoderWriter<W> {
+    fn write(&mut self, input: &[u8]) -> io::Result<usize> {
+        if input.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            self.dump()?;
+            let (status, read, written) = decompress(&mut self.state, input, &mut self.buf);
+            self.pos = 0;
+            self.end = written;
+            match status {
+                // the output is written out on the next call, so an error writing it can't
+                // lose input that was already taken
+                _ if read > 0 => return Ok(read),
+                // errors stick, write out the data before it first
+                status if status.is_error() && written == 0 => {
+                    return Err(self.state.error(status).into())
+                }
+                // a run filled the buffer without taking any input
+                _ => {}
+            }
+        }
+    }
+
+    /// Write out the data decompressed so far and flush the writer
+    fn flush(&mut self) -> io::Result<()> {
+        self.dump()?;
+        self.get_mut().flush()
+    }
+}
+
+impl<W: Write> Drop for AdcDecoderWriter<W> {
+    fn drop(&mut self) {
+        if self.output.is_some() {
+            let _ = self.try_finish();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generator::StreamGenerator, AdcError};
+
+    #[test]
+    fn split_writes() {
+        let stream = StreamGenerator::new(15)
+            .text(50_000)
+            .zeros(80_000)
+            .random(2000)
+            .finish();
+        for &step in &[1, 2, 3, 1000, 1 << 20] {
+            let mut decoder = AdcDecoderWriter::new(Vec::new());
+            for piece in stream.compressed.chunks(step) {
+                decoder.write_all(piece).unwrap();
+            }
+            assert_eq!(decoder.total_in(), stream.compressed.len() as u64);
+            let data = decoder.finish().unwrap();
+            assert!(data == stream.decompressed, "step {}", step);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        // the error shows up once the run is decoded, after its header was taken
+        let mut decoder = AdcDecoderWriter::new(Vec::new());
+        decoder.write_all(&[0x81, 0xab, 0xcd, 0x00, 0x05]).unwrap();
+        let err = decoder.write(&[0x00]).unwrap_err();
+        assert!(matches!(AdcError::from(err), AdcError::InvalidOffset(c) if c.index == 1));
+
+        let mut decoder = AdcDecoderWriter::new(Vec::new());
+        decoder.write_all(&[0x83, 0xfe, 0xed]).unwrap();
+        let err = decoder.try_finish().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        // the data before the truncated chunk was written out
+        assert_eq!(decoder.output.as_deref(), Some(&[0xfe, 0xed][..]));
+    }
+}
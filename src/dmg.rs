@@ -0,0 +1,939 @@
+//! Minimal parsing of UDIF disk images (`.dmg`).
+//!
+//! Only the parts needed to locate the compressed runs of an image are parsed: the `koly`
+//! trailer at the end of the file, and the `blkx` block tables in the XML property list it
+//! points to. All offsets in the parsed structures are absolute offsets into the image file.
+//!
+//! ```no_run
+//! use adc::dmg::{DmgImage, RunType};
+//! use std::fs::File;
+//!
+//! let mut file = File::open("image.dmg").unwrap();
+//! let image = DmgImage::read_from(&mut file).unwrap();
+//! for partition in &image.partitions {
+//!     let adc_runs = partition.table.runs.iter().filter(|r| r.kind == RunType::Adc);
+//!     println!("{}: {} ADC runs", partition.name, adc_runs.count());
+//! }
+//!
+//! // decompressed contents of the first partition
+//! let mut reader = image.partitions[0].table.reader(&mut file).unwrap();
+//! std::io::copy(&mut reader, &mut std::io::sink()).unwrap();
+//! ```
+
+use crate::{bytes::ReadBe, AdcDecoder, AnyBlockDecoder, BlockKind, SECTOR_SIZE};
+use std::io::{self, prelude::*, SeekFrom};
+
+const KOLY_SIZE: usize = 512;
+
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Checksum record as stored in UDIF structures.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct UdifChecksum {
+    /// Checksum algorithm, 2 is CRC-32
+    pub kind: u32,
+    /// Size of the checksum in bits
+    pub bits: u32,
+    /// Checksum data, only the first `bits` bits are used
+    pub data: [u32; 32],
+}
+
+impl UdifChecksum {
+    /// Checksum type for CRC-32
+    pub const CRC32: u32 = 2;
+
+    fn parse(mut input: &[u8]) -> io::Result<UdifChecksum> {
+        let kind = input.read_u32_be()?;
+        let bits = input.read_u32_be()?;
+        let mut data = [0; 32];
+        for value in &mut data {
+            *value = input.read_u32_be()?;
+        }
+        Ok(UdifChecksum { kind, bits, data })
+    }
+
+    /// Get the checksum value if it is a CRC-32
+    pub fn crc32(&self) -> Option<u32> {
+        if self.kind == Self::CRC32 {
+            Some(self.data[0])
+        } else {
+            None
+        }
+    }
+
+    /// Compare the record with the CRC-32 `actual` of the data it covers
+    pub fn check(&self, actual: u32) -> ChecksumStatus {
+        match self.crc32() {
+            Some(expected) if expected == actual => ChecksumStatus::Match,
+            Some(expected) => ChecksumStatus::Mismatch { expected, actual },
+            None => ChecksumStatus::Unchecked,
+        }
+    }
+}
+
+/// Outcome of comparing data with a [`UdifChecksum`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum ChecksumStatus {
+    /// The CRC-32 of the data matches the record
+    Match,
+    /// The CRC-32 of the data differs from the record
+    Mismatch {
+        /// Checksum in the record
+        expected: u32,
+        /// Checksum of the data
+        actual: u32,
+    },
+    /// The record is no CRC-32 and was not checked
+    Unchecked,
+}
+
+/// Checksum verification of the decompressed data of one partition.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub struct PartitionCheck {
+    /// Name of the partition
+    pub name: String,
+    /// Number of decompressed bytes checked
+    pub decompressed_len: u64,
+    /// Outcome of the check against the checksum of the block table
+    pub status: ChecksumStatus,
+}
+
+/// Checksums of an image compared with its data, returned by [`DmgImage::verify`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub struct VerificationReport {
+    /// Outcome of the check of the stored data fork against the `koly` trailer
+    pub data_fork: ChecksumStatus,
+    /// Outcome of the checks of the partitions, in image order
+    pub partitions: Vec<PartitionCheck>,
+}
+
+impl VerificationReport {
+    /// Check that no checksum mismatched
+    pub fn is_ok(&self) -> bool {
+        std::iter::once(self.data_fork)
+            .chain(self.partitions.iter().map(|p| p.status))
+            .all(|status| !matches!(status, ChecksumStatus::Mismatch { .. }))
+    }
+}
+
+/// Compute the CRC-32 and length of all data read from `input`.
+fn checksum<R: Read>(mut input: R) -> io::Result<(u32, u64)> {
+    let mut crc = crate::Crc32::new();
+    let mut len = 0;
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        match input.read(&mut buf) {
+            Ok(0) => return Ok((crc.sum(), len)),
+            Ok(n) => {
+                crc.update(&buf[..n]);
+                len += n as u64;
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// The `koly` trailer found in the last 512 bytes of an image.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Koly {
+    /// Format version
+    pub version: u32,
+    /// Image flags
+    pub flags: u32,
+    /// Offset of the data fork
+    pub data_fork_offset: u64,
+    /// Length of the data fork
+    pub data_fork_length: u64,
+    /// Offset of the resource fork
+    pub rsrc_fork_offset: u64,
+    /// Length of the resource fork
+    pub rsrc_fork_length: u64,
+    /// Checksum of the data fork
+    pub data_checksum: UdifChecksum,
+    /// Offset of the XML property list
+    pub xml_offset: u64,
+    /// Length of the XML property list
+    pub xml_length: u64,
+    /// Checksum over the checksums of all block tables
+    pub master_checksum: UdifChecksum,
+    /// Image variant
+    pub image_variant: u32,
+    /// Number of sectors of the decompressed image
+    pub sector_count: u64,
+}
+
+impl Koly {
+    /// Parse a `koly` trailer from the 512 bytes it occupies
+    pub fn parse(input: &[u8]) -> io::Result<Koly> {
+        if input.len() < KOLY_SIZE || &input[..4] != b"koly" {
+            return Err(invalid_data("missing koly trailer"));
+        }
+
+        let mut r = &input[4..];
+        let version = r.read_u32_be()?;
+        let _header_size = r.read_u32_be()?;
+        let flags = r.read_u32_be()?;
+        let _running_data_fork_offset = r.read_u64_be()?;
+        let data_fork_offset = r.read_u64_be()?;
+        let data_fork_length = r.read_u64_be()?;
+        let rsrc_fork_offset = r.read_u64_be()?;
+        let rsrc_fork_length = r.read_u64_be()?;
+        // segment number, count and id
+        let r = &r[4 + 4 + 16..];
+        let data_checksum = UdifChecksum::parse(&r[..136])?;
+        let mut r = &r[136..];
+        let xml_offset = r.read_u64_be()?;
+        let xml_length = r.read_u64_be()?;
+        let r = &r[120..];
+        let master_checksum = UdifChecksum::parse(&r[..136])?;
+        let mut r = &r[136..];
+        let image_variant = r.read_u32_be()?;
+        let sector_count = r.read_u64_be()?;
+
+        Ok(Koly {
+            version,
+            flags,
+            data_fork_offset,
+            data_fork_length,
+            rsrc_fork_offset,
+            rsrc_fork_length,
+            data_checksum,
+            xml_offset,
+            xml_length,
+            master_checksum,
+            image_variant,
+            sector_count,
+        })
+    }
+
+    /// Read the `koly` trailer from the end of an image
+    pub fn read_from<R: Read + Seek>(input: &mut R) -> io::Result<Koly> {
+        input.seek(SeekFrom::End(-(KOLY_SIZE as i64)))?;
+        let mut buf = [0; KOLY_SIZE];
+        input.read_exact(&mut buf)?;
+        Koly::parse(&buf)
+    }
+}
+
+/// Storage type of a run in a block table.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum RunType {
+    /// Sectors are all zero and not stored
+    ZeroFill,
+    /// Sectors are stored uncompressed
+    Raw,
+    /// Sectors are unused and not stored
+    Ignore,
+    /// Sectors are ADC-compressed
+    Adc,
+    /// Sectors are zlib-compressed
+    Zlib,
+    /// Sectors are bzip2-compressed
+    Bzip2,
+    /// Sectors are LZFSE-compressed
+    Lzfse,
+    /// Sectors are LZMA-compressed
+    Lzma,
+    /// Comment, does not describe any sectors
+    Comment,
+    /// Marks the end of the block table
+    Terminator,
+    /// Any other run type
+    Unknown(u32),
+}
+
+impl RunType {
+    /// Get the block kind for runs that can be decoded by [`AnyBlockDecoder`]
+    ///
+    /// Ignored runs decode to zeros like zero-filled ones.
+    pub fn block_kind(self) -> Option<BlockKind> {
+        match self {
+            RunType::Adc => Some(BlockKind::Adc),
+            RunType::Raw => Some(BlockKind::Raw),
+            RunType::ZeroFill | RunType::Ignore => Some(BlockKind::ZeroFill),
+            _ => None,
+        }
+    }
+}
+
+impl From<u32> for RunType {
+    fn from(kind: u32) -> RunType {
+        match kind {
+            0x0000_0000 => RunType::ZeroFill,
+            0x0000_0001 => RunType::Raw,
+            0x0000_0002 => RunType::Ignore,
+            0x8000_0004 => RunType::Adc,
+            0x8000_0005 => RunType::Zlib,
+            0x8000_0006 => RunType::Bzip2,
+            0x8000_0007 => RunType::Lzfse,
+            0x8000_0008 => RunType::Lzma,
+            0x7fff_fffe => RunType::Comment,
+            0xffff_ffff => RunType::Terminator,
+            other => RunType::Unknown(other),
+        }
+    }
+}
+
+impl From<RunType> for u32 {
+    fn from(kind: RunType) -> u32 {
+        match kind {
+            RunType::ZeroFill => 0x0000_0000,
+            RunType::Raw => 0x0000_0001,
+            RunType::Ignore => 0x0000_0002,
+            RunType::Adc => 0x8000_0004,
+            RunType::Zlib => 0x8000_0005,
+            RunType::Bzip2 => 0x8000_0006,
+            RunType::Lzfse => 0x8000_0007,
+            RunType::Lzma => 0x8000_0008,
+            RunType::Comment => 0x7fff_fffe,
+            RunType::Terminator => 0xffff_ffff,
+            RunType::Unknown(other) => other,
+        }
+    }
+}
+
+/// A run of sectors in a block table.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Run {
+    /// How the sectors are stored
+    pub kind: RunType,
+    /// First sector of the run in the decompressed image
+    pub sector_number: u64,
+    /// Number of sectors in the run
+    pub sector_count: u64,
+    /// Offset of the stored data in the image file
+    pub compressed_offset: u64,
+    /// Length of the stored data
+    pub compressed_length: u64,
+}
+
+impl Run {
+    /// Get the location of the run for use with [`ParallelExtractor`](crate::ParallelExtractor)
+    ///
+    /// The offsets of runs from [`BlockTable::parse`] are known to fit into a `u64`.
+    #[cfg(any(unix, windows))]
+    pub fn block(&self) -> crate::Block {
+        crate::Block {
+            input_offset: self.compressed_offset,
+            input_len: self.compressed_length,
+            output_offset: self.sector_number * SECTOR_SIZE,
+        }
+    }
+
+    /// Size of the run in the decompressed image
+    pub fn decompressed_len(&self) -> u64 {
+        self.sector_count * SECTOR_SIZE
+    }
+
+    /// Create a decoder for the run, reading the stored data of the run from `input`
+    ///
+    /// Fails with `Unsupported` for compression methods other than ADC.
+    pub fn decoder<R: Read>(&self, input: R) -> io::Result<AnyBlockDecoder<R>> {
+        match self.kind.block_kind() {
+            Some(kind) => Ok(AnyBlockDecoder::new(kind, input, self.decompressed_len())),
+            None => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "unsupported run type",
+            )),
+        }
+    }
+}
+
+/// A `mish` block table describing the runs of one partition.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BlockTable {
+    /// First sector of the partition in the decompressed image
+    pub first_sector: u64,
+    /// Number of sectors in the partition
+    pub sector_count: u64,
+    /// Checksum of the decompressed partition data
+    pub checksum: UdifChecksum,
+    /// Runs of the partition, with absolute sector numbers and file offsets
+    pub runs: Vec<Run>,
+}
+
+impl BlockTable {
+    /// Parse a `mish` block table, resolving offsets relative to `data_fork_offset`
+    ///
+    /// Fails with `InvalidData` if the sectors or stored data of a run lie beyond the range
+    /// of byte offsets.
+    pub fn parse(input: &[u8], data_fork_offset: u64) -> io::Result<BlockTable> {
+        if input.len() < 204 || &input[..4] != b"mish" {
+            return Err(invalid_data("invalid block table"));
+        }
+
+        let mut r = &input[4..];
+        let _version = r.read_u32_be()?;
+        let first_sector = r.read_u64_be()?;
+        let sector_count = r.read_u64_be()?;
+        let data_offset = r.read_u64_be()?;
+        // buffers needed, block descriptors, reserved
+        let r = &r[4 + 4 + 24..];
+        let checksum = UdifChecksum::parse(&r[..136])?;
+        let mut r = &r[136..];
+        let run_count = r.read_u32_be()? as usize;
+
+        let out_of_range = || invalid_data("block table offsets out of range");
+        first_sector
+            .checked_add(sector_count)
+            .and_then(|end| end.checked_mul(SECTOR_SIZE))
+            .ok_or_else(out_of_range)?;
+        let base = data_fork_offset
+            .checked_add(data_offset)
+            .ok_or_else(out_of_range)?;
+        let mut runs = Vec::with_capacity(std::cmp::min(run_count, r.len() / 40));
+        for _ in 0..run_count {
+            let kind = RunType::from(r.read_u32_be()?);
+            let _comment = r.read_u32_be()?;
+            let sector_number = r.read_u64_be()?;
+            let sector_count = r.read_u64_be()?;
+            let compressed_offset = r.read_u64_be()?;
+            let compressed_length = r.read_u64_be()?;
+
+            if kind == RunType::Terminator {
+                break;
+            }
+            // checked once here, so the byte offsets derived from a run can't overflow
+            let sector_number = first_sector
+                .checked_add(sector_number)
+                .ok_or_else(out_of_range)?;
+            sector_number
+                .checked_add(sector_count)
+                .and_then(|end| end.checked_mul(SECTOR_SIZE))
+                .ok_or_else(out_of_range)?;
+            let compressed_offset = base
+                .checked_add(compressed_offset)
+                .filter(|offset| offset.checked_add(compressed_length).is_some())
+                .ok_or_else(out_of_range)?;
+            runs.push(Run {
+                kind,
+                sector_number,
+                sector_count,
+                compressed_offset,
+                compressed_length,
+            });
+        }
+
+        Ok(BlockTable {
+            first_sector,
+            sector_count,
+            checksum,
+            runs,
+        })
+    }
+
+    /// Create a reader for the decompressed partition data, reading the runs from `input`
+    ///
+    /// The runs are decoded in the order of their sectors, sectors not covered by any run
+    /// read as zeros. `input` has to be the image file as the offsets of the runs are
+    /// absolute.
+    ///
+    /// Fails with `InvalidData` if runs overlap or lie outside the partition, or if the
+    /// stored length of a raw run differs from its size.
+    pub fn reader<R: Read + Seek>(&self, input: R) -> io::Result<PartitionReader<'_, R>> {
+        let mut runs: Vec<_> = self
+            .runs
+            .iter()
+            .filter(|run| run.kind != RunType::Comment)
+            .collect();
+        runs.sort_by_key(|run| run.sector_number);
+
+        // parse made sure that these sums don't overflow
+        let end = self.first_sector + self.sector_count;
+        let mut sector = self.first_sector;
+        for run in &runs {
+            if run.sector_number < sector || run.sector_number + run.sector_count > end {
+                return Err(invalid_data(
+                    "overlapping run or run outside of the partition",
+                ));
+            }
+            if run.kind == RunType::Raw && run.compressed_length != run.decompressed_len() {
+                return Err(invalid_data(
+                    "stored length of raw run differs from its size",
+                ));
+            }
+            sector = run.sector_number + run.sector_count;
+        }
+
+        Ok(PartitionReader {
+            runs: runs.into_iter().peekable(),
+            sector: self.first_sector,
+            end,
+            input: Some(input),
+            current: Current::Done,
+        })
+    }
+}
+
+/// Reader for the decompressed data of a partition, created by [`BlockTable::reader`].
+///
+/// ADC runs are decoded, raw runs passed through and zero-filled or ignored runs read as
+/// zeros, as do gaps between runs. Fails with `Unsupported` at runs of other compression
+/// methods and with the error of the decoder if an ADC run doesn't decompress to the size
+/// of the run.
+pub struct PartitionReader<'a, R> {
+    /// Runs not started yet, sorted by sector
+    runs: std::iter::Peekable<std::vec::IntoIter<&'a Run>>,
+    /// First sector not read yet
+    sector: u64,
+    /// Sector after the end of the partition
+    end: u64,
+    /// The image, `None` while a run reads from it
+    input: Option<R>,
+    current: Current<R>,
+}
+
+/// Run being read by a [`PartitionReader`].
+enum Current<R> {
+    Adc(Box<AdcDecoder<io::Take<R>>>),
+    Raw(io::Take<R>),
+    Zeros(u64),
+    Done,
+}
+
+impl<R: Read + Seek> PartitionReader<'_, R> {
+    /// Start reading the next run with sectors, returns `false` after the last one.
+    fn next_run(&mut self) -> io::Result<bool> {
+        match std::mem::replace(&mut self.current, Current::Done) {
+            Current::Adc(d) => self.input = Some(d.into_inner().into_inner()),
+            Current::Raw(r) => self.input = Some(r.into_inner()),
+            Current::Zeros(_) | Current::Done => {}
+        }
+
+        // sectors before the next run or the end of the partition read as zeros
+        let next = self.runs.peek().map_or(self.end, |run| run.sector_number);
+        if next > self.sector {
+            self.current = Current::Zeros((next - self.sector) * SECTOR_SIZE);
+            self.sector = next;
+            return Ok(true);
+        }
+        let run = match self.runs.next() {
+            Some(run) => run,
+            None => return Ok(false),
+        };
+        self.sector = run.sector_number + run.sector_count;
+        let len = run.decompressed_len();
+        self.current = match run.kind.block_kind() {
+            Some(BlockKind::ZeroFill) => Current::Zeros(len),
+            Some(kind) => {
+                let mut input = self.input.take().expect("input taken by a finished run");
+                let stored = match input.seek(SeekFrom::Start(run.compressed_offset)) {
+                    Ok(_) => input.take(run.compressed_length),
+                    Err(err) => {
+                        self.input = Some(input);
+                        return Err(err);
+                    }
+                };
+                match kind {
+                    BlockKind::Adc => {
+                        Current::Adc(Box::new(AdcDecoder::with_expected_size(stored, len)))
+                    }
+                    _ => Current::Raw(stored),
+                }
+            }
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "unsupported run type",
+                ))
+            }
+        };
+        Ok(true)
+    }
+}
+
+impl<R: Read + Seek> Read for PartitionReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let n = match self.current {
+                Current::Adc(ref mut d) => d.read(buf)?,
+                Current::Raw(ref mut r) => match r.read(buf)? {
+                    0 if r.limit() > 0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                    n => n,
+                },
+                Current::Zeros(ref mut left) => {
+                    let n = std::cmp::min(*left, buf.len() as u64) as usize;
+                    buf[..n].fill(0);
+                    *left -= n as u64;
+                    n
+                }
+                Current::Done => 0,
+            };
+            if n > 0 || !self.next_run()? {
+                return Ok(n);
+            }
+        }
+    }
+}
+
+/// A partition of an image together with its block table.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub struct Partition {
+    /// Name of the partition
+    pub name: String,
+    /// Block table of the partition
+    pub table: BlockTable,
+}
+
+/// The parsed structure of a UDIF image.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub struct DmgImage {
+    /// The `koly` trailer
+    pub koly: Koly,
+    /// Partitions listed in the `blkx` resource
+    pub partitions: Vec<Partition>,
+}
+
+impl DmgImage {
+    /// Read the trailer and block tables of an image
+    pub fn read_from<R: Read + Seek>(input: &mut R) -> io::Result<DmgImage> {
+        let koly = Koly::read_from(input)?;
+        if koly.xml_length == 0 {
+            return Err(invalid_data("image has no XML property list"));
+        }
+
+        input.seek(SeekFrom::Start(koly.xml_offset))?;
+        let plist = plist::Value::from_reader_xml(input.take(koly.xml_length))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        let blkx = plist
+            .as_dictionary()
+            .and_then(|d| d.get("resource-fork"))
+            .and_then(|v| v.as_dictionary())
+            .and_then(|d| d.get("blkx"))
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| invalid_data("missing blkx resource"))?;
+
+        let mut partitions = Vec::with_capacity(blkx.len());
+        for entry in blkx {
+            let entry = entry
+                .as_dictionary()
+                .ok_or_else(|| invalid_data("invalid blkx entry"))?;
+            let data = entry
+                .get("Data")
+                .and_then(|v| v.as_data())
+                .ok_or_else(|| invalid_data("blkx entry without data"))?;
+            let name = entry
+                .get("Name")
+                .or_else(|| entry.get("CFName"))
+                .and_then(|v| v.as_string())
+                .unwrap_or_default();
+
+            partitions.push(Partition {
+                name: name.to_owned(),
+                table: BlockTable::parse(data, koly.data_fork_offset)?,
+            });
+        }
+
+        Ok(DmgImage { koly, partitions })
+    }
+
+    /// Iterate over the runs of all partitions
+    pub fn runs(&self) -> impl Iterator<Item = &Run> {
+        self.partitions.iter().flat_map(|p| p.table.runs.iter())
+    }
+
+    /// Decompress every partition and compare the checksums of the image with the data
+    ///
+    /// The data fork checksum of the trailer covers the stored data, the checksum of each
+    /// block table the decompressed data of its partition. Records other than CRC-32 are
+    /// reported as [`ChecksumStatus::Unchecked`]. Fails if reading the image or decoding a
+    /// run fails.
+    ///
+    /// ```no_run
+    /// use adc::dmg::DmgImage;
+    /// use std::fs::File;
+    ///
+    /// let mut file = File::open("image.dmg").unwrap();
+    /// let image = DmgImage::read_from(&mut file).unwrap();
+    /// let report = image.verify(&mut file).unwrap();
+    /// for partition in &report.partitions {
+    ///     println!("{}: {:?}", partition.name, partition.status);
+    /// }
+    /// assert!(report.is_ok());
+    /// ```
+    pub fn verify<R: Read + Seek>(&self, mut input: R) -> io::Result<VerificationReport> {
+        let data_fork = match self.koly.data_checksum.crc32() {
+            Some(_) => {
+                input.seek(SeekFrom::Start(self.koly.data_fork_offset))?;
+                let (crc, _) = checksum((&mut input).take(self.koly.data_fork_length))?;
+                self.koly.data_checksum.check(crc)
+            }
+            None => ChecksumStatus::Unchecked,
+        };
+
+        let mut partitions = Vec::with_capacity(self.partitions.len());
+        for partition in &self.partitions {
+            let (crc, decompressed_len) = checksum(partition.table.reader(&mut input)?)?;
+            partitions.push(PartitionCheck {
+                name: partition.name.clone(),
+                decompressed_len,
+                status: partition.table.checksum.check(crc),
+            });
+        }
+
+        Ok(VerificationReport {
+            data_fork,
+            partitions,
+        })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn base64(data: &[u8]) -> String {
+        const CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut out = String::new();
+        for chunk in data.chunks(3) {
+            let b = [
+                chunk[0],
+                *chunk.get(1).unwrap_or(&0),
+                *chunk.get(2).unwrap_or(&0),
+            ];
+            let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    out.push(CHARS[(n >> (18 - 6 * i)) as usize & 0x3f] as char);
+                } else {
+                    out.push('=');
+                }
+            }
+        }
+        out
+    }
+
+    fn mish(runs: &[(u32, u64, u64, u64, u64)], sector_count: u64, crc: u32) -> Vec<u8> {
+        let mut out = b"mish".to_vec();
+        out.extend_from_slice(&1u32.to_be_bytes());
+        out.extend_from_slice(&0u64.to_be_bytes());
+        out.extend_from_slice(&sector_count.to_be_bytes());
+        out.extend_from_slice(&0u64.to_be_bytes());
+        out.extend_from_slice(&[0; 32]);
+        out.extend_from_slice(&UdifChecksum::CRC32.to_be_bytes());
+        out.extend_from_slice(&32u32.to_be_bytes());
+        out.extend_from_slice(&crc.to_be_bytes());
+        out.extend_from_slice(&[0; 124]);
+        out.extend_from_slice(&(runs.len() as u32 + 1).to_be_bytes());
+        for &(kind, sector, count, offset, len) in runs.iter().chain(&[(!0, 0, 0, 0, 0)]) {
+            out.extend_from_slice(&kind.to_be_bytes());
+            out.extend_from_slice(&0u32.to_be_bytes());
+            for value in &[sector, count, offset, len] {
+                out.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+        out
+    }
+
+    /// Build an image with one partition of two ADC runs, a zero run and a raw run.
+    ///
+    /// Returns the image and its expected decompressed contents.
+    pub(crate) fn build_image() -> (Vec<u8>, Vec<u8>) {
+        let adc = crate::generator::StreamGenerator::new(1)
+            .text(SECTOR_SIZE as usize * 2)
+            .finish();
+        let raw = vec![0x55; SECTOR_SIZE as usize];
+
+        let mut image = vec![0xee; 16];
+        image.extend_from_slice(&adc.compressed);
+        image.extend_from_slice(&raw);
+
+        let adc_len = adc.compressed.len() as u64;
+        let runs = [
+            (0x8000_0004, 0, 2, 16, adc_len),
+            (0x0000_0000, 2, 1, 0, 0),
+            (0x0000_0001, 3, 1, 16 + adc_len, SECTOR_SIZE),
+        ];
+        let mut expected = adc.decompressed.clone();
+        expected.extend_from_slice(&[0; SECTOR_SIZE as usize]);
+        expected.extend_from_slice(&raw);
+
+        let table = mish(&runs, 4, crate::crc32(&expected));
+        let xml = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <plist version=\"1.0\"><dict><key>resource-fork</key><dict>\
+             <key>blkx</key><array><dict>\
+             <key>Attributes</key><string>0x0050</string>\
+             <key>Data</key><data>{}</data>\
+             <key>ID</key><string>0</string>\
+             <key>Name</key><string>disk image (Apple_HFS : 1)</string>\
+             </dict></array></dict></dict></plist>",
+            base64(&table)
+        );
+
+        let xml_offset = image.len() as u64;
+        image.extend_from_slice(xml.as_bytes());
+
+        let mut koly = b"koly".to_vec();
+        koly.extend_from_slice(&4u32.to_be_bytes());
+        koly.extend_from_slice(&512u32.to_be_bytes());
+        koly.extend_from_slice(&1u32.to_be_bytes());
+        koly.extend_from_slice(&0u64.to_be_bytes());
+        koly.extend_from_slice(&0u64.to_be_bytes());
+        koly.extend_from_slice(&xml_offset.to_be_bytes());
+        koly.extend_from_slice(&[0; 16 + 24 + 136]);
+        koly.extend_from_slice(&xml_offset.to_be_bytes());
+        koly.extend_from_slice(&(xml.len() as u64).to_be_bytes());
+        koly.extend_from_slice(&[0; 120 + 136]);
+        koly.extend_from_slice(&1u32.to_be_bytes());
+        koly.extend_from_slice(&4u64.to_be_bytes());
+        koly.resize(KOLY_SIZE, 0);
+        image.extend_from_slice(&koly);
+
+        (image, expected)
+    }
+
+    #[test]
+    fn parse_image() {
+        let (image, expected) = build_image();
+        let parsed = DmgImage::read_from(&mut Cursor::new(&image)).unwrap();
+
+        assert_eq!(parsed.koly.version, 4);
+        assert_eq!(parsed.koly.sector_count, 4);
+        assert_eq!(parsed.partitions.len(), 1);
+
+        let partition = &parsed.partitions[0];
+        assert_eq!(partition.name, "disk image (Apple_HFS : 1)");
+        assert_eq!(
+            partition.table.checksum.crc32(),
+            Some(crate::crc32(&expected))
+        );
+
+        let kinds: Vec<_> = parsed.runs().map(|r| r.kind).collect();
+        assert_eq!(kinds, [RunType::Adc, RunType::ZeroFill, RunType::Raw]);
+        let codes: Vec<u32> = kinds.into_iter().map(u32::from).collect();
+        assert_eq!(codes, [0x8000_0004, 0, 1]);
+        assert_eq!(partition.table.runs[0].compressed_offset, 16);
+        assert_eq!(partition.table.runs[2].block().output_offset, 3 * 512);
+    }
+
+    #[test]
+    fn decode_adc_run() {
+        let (image, expected) = build_image();
+        let parsed = DmgImage::read_from(&mut Cursor::new(&image)).unwrap();
+        let run = &parsed.partitions[0].table.runs[0];
+
+        let start = run.compressed_offset as usize;
+        let input = &image[start..start + run.compressed_length as usize];
+        let mut data = Vec::new();
+        crate::AdcDecoder::new(input)
+            .read_to_end(&mut data)
+            .unwrap();
+
+        assert_eq!(data, &expected[..run.decompressed_len() as usize]);
+    }
+
+    #[test]
+    fn read_partition() {
+        let (image, expected) = build_image();
+        let mut file = Cursor::new(&image);
+        let parsed = DmgImage::read_from(&mut file).unwrap();
+
+        let mut data = Vec::new();
+        let mut reader = parsed.partitions[0].table.reader(&mut file).unwrap();
+        reader.read_to_end(&mut data).unwrap();
+        assert!(data == expected);
+
+        // runs in any order, the zero run left out, sectors past the last run
+        let mut table = parsed.partitions[0].table.clone();
+        table.runs.remove(1);
+        table.runs.reverse();
+        table.sector_count += 2;
+        let mut data = Vec::new();
+        table
+            .reader(&mut file)
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap();
+        assert!(data[..expected.len()] == expected[..]);
+        assert!(data[expected.len()..] == [0; 2 * SECTOR_SIZE as usize][..]);
+
+        let mut table = parsed.partitions[0].table.clone();
+        table.runs[1].kind = RunType::Zlib;
+        let err = table
+            .reader(&mut file)
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    }
+
+    #[test]
+    fn invalid_partition_layout() {
+        let (image, _) = build_image();
+        let parsed = DmgImage::read_from(&mut Cursor::new(&image)).unwrap();
+        let table = &parsed.partitions[0].table;
+
+        let mut overlapping = table.clone();
+        overlapping.runs[1].sector_number = 1;
+        let mut outside = table.clone();
+        outside.runs[2].sector_count = 2;
+        let mut raw_length = table.clone();
+        raw_length.runs[2].compressed_length -= 1;
+
+        for table in &[overlapping, outside, raw_length] {
+            let err = table.reader(Cursor::new(&image)).err().unwrap();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn verify_checksums() {
+        let (mut image, expected) = build_image();
+        let parsed = DmgImage::read_from(&mut Cursor::new(&image)).unwrap();
+        let report = parsed.verify(Cursor::new(&image)).unwrap();
+        assert!(report.is_ok());
+        assert_eq!(report.data_fork, ChecksumStatus::Unchecked);
+        assert_eq!(report.partitions[0].status, ChecksumStatus::Match);
+        assert_eq!(report.partitions[0].decompressed_len, expected.len() as u64);
+
+        // damage the first byte of the raw run
+        let raw = &parsed.partitions[0].table.runs[2];
+        image[raw.compressed_offset as usize] ^= 1;
+        let mut damaged = expected.clone();
+        damaged[expected.len() - SECTOR_SIZE as usize] ^= 1;
+        let report = parsed.verify(Cursor::new(&image)).unwrap();
+        assert!(!report.is_ok());
+        assert_eq!(
+            report.partitions[0].status,
+            ChecksumStatus::Mismatch {
+                expected: crate::crc32(&expected),
+                actual: crate::crc32(&damaged),
+            }
+        );
+    }
+
+    #[test]
+    fn offsets_out_of_range() {
+        let tables = [
+            (mish(&[(1, u64::MAX, 1, 0, 0)], 1, 0), 0),
+            (mish(&[(1, 1, u64::MAX / 512, 0, 0)], 1, 0), 0),
+            (mish(&[(1, 0, 1, 1, 0)], 1, 0), u64::MAX),
+            (mish(&[(1, 0, 1, 0, u64::MAX)], 1, 0), 1),
+        ];
+        for (table, data_fork_offset) in &tables {
+            let err = BlockTable::parse(table, *data_fork_offset).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn missing_trailer() {
+        let err = DmgImage::read_from(&mut Cursor::new(vec![0; 1024])).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,1021 @@
+//! ADC compression.
+//!
+//! [`AdcEncoder`] finds repeated data within the last [`WINDOW_SIZE`] bytes of input and
+//! encodes it as runs, everything else as plain chunks. [`AdcEncoderReader`] does the same
+//! for data pulled from a reader.
+
+use crate::{
+    hash_chain::HashChain, MAX_PLAIN_CHUNK_LEN, MAX_RUN_CHUNK_LEN, MAX_TWO_BYTE_OFFSET,
+    MAX_TWO_BYTE_RUN_LEN, MIN_RUN_CHUNK_LEN, MIN_THREE_BYTE_RUN_LEN, WINDOW_SIZE,
+};
+use std::{
+    cmp,
+    collections::VecDeque,
+    convert::TryInto,
+    io::{self, prelude::*},
+};
+
+/// Number of bytes buffered before they are compressed.
+const BLOCK_SIZE: usize = 64 * 1024;
+
+/// Write `bytes` as plain chunks.
+pub(crate) fn write_plain<W: Write>(output: &mut W, bytes: &[u8]) -> io::Result<()> {
+    for part in bytes.chunks(MAX_PLAIN_CHUNK_LEN) {
+        output.write_all(&[0x80 | (part.len() - 1) as u8])?;
+        output.write_all(part)?;
+    }
+    Ok(())
+}
+
+/// Shortest run that can be encoded with the given offset.
+pub(crate) fn min_run_len(offset: u16) -> usize {
+    if offset <= MAX_TWO_BYTE_OFFSET {
+        MIN_RUN_CHUNK_LEN
+    } else {
+        MIN_THREE_BYTE_RUN_LEN
+    }
+}
+
+/// Write a run of at least [`min_run_len`] bytes as run chunks.
+pub(crate) fn write_run<W: Write>(output: &mut W, offset: u16, len: usize) -> io::Result<()> {
+    let min_len = min_run_len(offset);
+    debug_assert!(len >= min_len);
+
+    let mut left = len;
+    while left > 0 {
+        if offset <= MAX_TWO_BYTE_OFFSET && left <= MAX_TWO_BYTE_RUN_LEN {
+            output.write_all(&[
+                (((left - 3) as u8) << 2) | (offset >> 8) as u8,
+                offset as u8,
+            ])?;
+            break;
+        }
+        let mut part = cmp::min(left, MAX_RUN_CHUNK_LEN);
+        // leave enough for the last chunk
+        if left - part > 0 && left - part < min_len {
+            part = left - min_len;
+        }
+        let [hi, lo] = offset.to_be_bytes();
+        output.write_all(&[0x40 | (part - 4) as u8, hi, lo])?;
+        left -= part;
+    }
+    Ok(())
+}
+
+/// Trade-off between compression speed and ratio.
+///
+/// Besides the strategy, the level sets the default
+/// [`chain_length`](AdcEncoder::chain_length).
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[non_exhaustive]
+pub enum CompressionLevel {
+    /// Only emit plain chunks, for data that doesn't compress anyway
+    Store,
+    /// Take the most recent run found and only index the start of runs
+    Fastest,
+    /// Take the best run found
+    #[default]
+    Default,
+    /// Search further, and check if a better run starts at the next byte before taking one
+    Best,
+    /// Pick the sequence of chunks with the smallest encoded size among the runs found, which
+    /// is several times slower than `Best`
+    Optimal,
+}
+
+impl CompressionLevel {
+    fn chain_length(self) -> usize {
+        match self {
+            CompressionLevel::Store | CompressionLevel::Fastest => 1,
+            CompressionLevel::Default => 16,
+            CompressionLevel::Best | CompressionLevel::Optimal => 64,
+        }
+    }
+}
+
+/// Size of a single run chunk encoding `len` bytes.
+fn run_header_len(offset: u16, len: usize) -> usize {
+    if offset <= MAX_TWO_BYTE_OFFSET && len <= MAX_TWO_BYTE_RUN_LEN {
+        2
+    } else {
+        3
+    }
+}
+
+/// Number of bytes saved by encoding `len` bytes with a single run chunk.
+fn run_gain(offset: u16, len: usize) -> isize {
+    len as isize - run_header_len(offset, len) as isize
+}
+
+/// Chunk chosen by optimal parsing.
+#[derive(Clone, Copy)]
+enum Step {
+    Literal(usize),
+    Run(u16, usize),
+}
+
+/// Compressor writing ADC data to an underlying writer.
+///
+/// Input is buffered and compressed in blocks. [`finish`](AdcEncoder::finish) writes out
+/// the rest and returns the writer. Dropping the encoder also finishes the stream, but
+/// ignores any errors.
+///
+/// Compressed data the writer doesn't take, through short writes or errors like
+/// `WouldBlock`, stays buffered and is written first by the next call, so the encoder can
+/// sit on non-blocking outputs. A `write` that fails this way accepted no input and can be
+/// retried, as can [`try_finish`](AdcEncoder::try_finish) and `flush`.
+///
+/// ```
+/// use adc::{AdcDecoder, AdcEncoder};
+/// use std::io::{Read, Write};
+///
+/// let mut encoder = AdcEncoder::new(Vec::new());
+/// encoder.write_all(b"to be or not to be, that is the question").unwrap();
+/// let compressed = encoder.finish().unwrap();
+///
+/// let mut data = Vec::new();
+/// AdcDecoder::new(&compressed[..]).read_to_end(&mut data).unwrap();
+/// assert_eq!(data, b"to be or not to be, that is the question");
+/// ```
+pub struct AdcEncoder<W: Write> {
+    output: Option<W>,
+    level: CompressionLevel,
+    /// History of up to `WINDOW_SIZE` bytes, followed by the input not encoded yet
+    data: Vec<u8>,
+    /// Stream position of `data[0]`
+    base: u64,
+    /// Next byte to encode
+    pos: usize,
+    /// Number of bytes before `pos` still to be emitted as literal data
+    literal: usize,
+    chain: HashChain,
+    chain_length: usize,
+    /// Stream position of the next byte to add to the chain
+    indexed: u64,
+    /// Compressed data not written to `output` yet
+    out: Vec<u8>,
+    /// Number of bytes at the start of `out` already written
+    flushed: usize,
+    total_in: u64,
+    total_out: u64,
+    restart_interval: Option<u64>,
+    /// Number of bytes written since the last restart
+    segment_len: u64,
+}
+
+impl<W: Write> AdcEncoder<W> {
+    /// Create an encoder writing the compressed stream to `output`
+    pub fn new(output: W) -> AdcEncoder<W> {
+        AdcEncoder::with_level(output, CompressionLevel::Default)
+    }
+
+    /// Create an encoder compressing with the given level
+    pub fn with_level(output: W, level: CompressionLevel) -> AdcEncoder<W> {
+        AdcEncoder {
+            output: Some(output),
+            level,
+            data: Vec::new(),
+            base: 0,
+            pos: 0,
+            literal: 0,
+            chain: HashChain::new(),
+            chain_length: level.chain_length(),
+            indexed: 0,
+            out: Vec::new(),
+            flushed: 0,
+            total_in: 0,
+            total_out: 0,
+            restart_interval: None,
+            segment_len: 0,
+        }
+    }
+
+    /// Set how many earlier positions are checked for a run at each byte
+    ///
+    /// Longer chains find better runs but take longer, the default depends on the level.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chain_length` is zero.
+    pub fn chain_length(mut self, chain_length: usize) -> AdcEncoder<W> {
+        assert!(chain_length > 0, "chain length must not be zero");
+        self.chain_length = chain_length;
+        self
+    }
+
+    /// Prime the encoder with `dictionary`, so runs can refer back into it as if it came
+    /// before the data
+    ///
+    /// Only the last [`WINDOW_SIZE`] bytes are used. The stream has to be decoded with the
+    /// same dictionary, see [`AdcDecoder::set_dictionary`](crate::AdcDecoder::set_dictionary).
+    ///
+    /// ```
+    /// use adc::{AdcDecoder, AdcEncoder};
+    /// use std::io::{Read, Write};
+    ///
+    /// let previous_block = b"to be or not to be, that is the question";
+    /// let mut encoder = AdcEncoder::new(Vec::new()).with_dictionary(previous_block);
+    /// encoder.write_all(b"that is the question").unwrap();
+    /// let compressed = encoder.finish().unwrap();
+    /// assert!(compressed.len() < 5);
+    ///
+    /// let mut d = AdcDecoder::new(&compressed[..]);
+    /// d.set_dictionary(previous_block);
+    /// let mut data = Vec::new();
+    /// d.read_to_end(&mut data).unwrap();
+    /// assert_eq!(data, b"that is the question");
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if data was written already.
+    pub fn with_dictionary(mut self, dictionary: &[u8]) -> AdcEncoder<W> {
+        assert!(
+            self.base == 0 && self.data.is_empty(),
+            "the dictionary has to be set before writing"
+        );
+        let dictionary = &dictionary[dictionary.len().saturating_sub(WINDOW_SIZE)..];
+        self.data.extend_from_slice(dictionary);
+        for pos in 0..dictionary.len() {
+            self.insert(pos);
+        }
+        self.pos = dictionary.len();
+        self
+    }
+
+    /// [`restart`](AdcEncoder::restart) every `interval` bytes of input
+    ///
+    /// The stream then consists of segments that decode independently, each of them
+    /// `interval` bytes of data except for the last one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    pub fn restart_interval(mut self, interval: u64) -> AdcEncoder<W> {
+        assert!(interval > 0, "restart interval must not be zero");
+        self.restart_interval = Some(interval);
+        self
+    }
+
+    /// Encode all data written so far and start over with an empty history
+    ///
+    /// Runs after the restart point never refer back before it, so the compressed data from
+    /// [`total_out`](AdcEncoder::total_out) on decodes with a fresh decoder. Like flushing,
+    /// each restart costs a little compression. A dictionary is dropped as well.
+    ///
+    /// ```
+    /// use adc::AdcEncoder;
+    /// use std::io::Write;
+    ///
+    /// let mut encoder = AdcEncoder::new(Vec::new());
+    /// encoder.write_all(b"abcabcabcabc").unwrap();
+    /// encoder.restart().unwrap();
+    /// let start = encoder.total_out() as usize;
+    /// encoder.write_all(b"abcabcabcabc").unwrap();
+    /// let compressed = encoder.finish().unwrap();
+    ///
+    /// let second = adc::decompress_to_vec(&compressed[start..]).unwrap();
+    /// assert_eq!(second, b"abcabcabcabc");
+    /// ```
+    pub fn restart(&mut self) -> io::Result<()> {
+        self.start_segment();
+        self.write_out()
+    }
+
+    /// Get the number of bytes written to the encoder
+    pub fn total_in(&self) -> u64 {
+        self.total_in
+    }
+
+    /// Get the number of compressed bytes the output took so far
+    ///
+    /// Data written to the encoder is only reflected after it was compressed, e.g. by
+    /// [`restart`](AdcEncoder::restart) or [`try_finish`](AdcEncoder::try_finish).
+    pub fn total_out(&self) -> u64 {
+        self.total_out
+    }
+
+    /// Write out all remaining data and flush the writer
+    ///
+    /// More data can be written afterwards, [`finish`](AdcEncoder::finish) just returns the
+    /// writer then.
+    pub fn try_finish(&mut self) -> io::Result<()> {
+        self.compress(true);
+        self.write_out()?;
+        self.output_mut().flush()
+    }
+
+    /// Write out all remaining data and return the writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.try_finish()?;
+        Ok(self.output.take().expect("output taken before finish"))
+    }
+
+    fn output_mut(&mut self) -> &mut W {
+        self.output.as_mut().expect("output taken before finish")
+    }
+
+    /// Write the compressed data to the output, keeping what it doesn't take.
+    fn write_out(&mut self) -> io::Result<()> {
+        let output = self.output.as_mut().expect("output taken before finish");
+        while self.flushed < self.out.len() {
+            match output.write(&self.out[self.flushed..]) {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "failed to write compressed data",
+                    ))
+                }
+                Ok(n) => {
+                    self.flushed += n;
+                    self.total_out += n as u64;
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.out.clear();
+        self.flushed = 0;
+        Ok(())
+    }
+
+    /// Encode all buffered input and drop the history.
+    fn start_segment(&mut self) {
+        self.compress(true);
+        // runs are never searched before the base
+        self.base += self.data.len() as u64;
+        self.data.clear();
+        self.pos = 0;
+        self.segment_len = 0;
+    }
+
+    /// Encode the buffered input into `out`, leaving enough to find full length runs unless
+    /// `all`.
+    fn compress(&mut self, all: bool) {
+        let end = self.data.len();
+        let limit = if all {
+            end
+        } else {
+            end.saturating_sub(MAX_RUN_CHUNK_LEN)
+        };
+
+        if self.level == CompressionLevel::Store {
+            while self.pos < limit {
+                let n = cmp::min(limit - self.pos, MAX_PLAIN_CHUNK_LEN - self.literal);
+                self.pos += n;
+                self.literal += n;
+                if self.literal == MAX_PLAIN_CHUNK_LEN {
+                    self.flush_literal();
+                }
+            }
+        }
+        if self.level == CompressionLevel::Optimal {
+            while self.pos < limit {
+                let block_end = cmp::min(limit, self.pos + BLOCK_SIZE);
+                self.compress_optimal(block_end);
+            }
+        }
+        while self.pos < limit {
+            let mut run = self.find_match(self.pos, end);
+            if let (Some((offset, len)), CompressionLevel::Best) = (run, self.level) {
+                // a literal byte followed by a better run beats taking this one
+                self.insert(self.pos);
+                if let Some((next_offset, next_len)) = self.find_match(self.pos + 1, end) {
+                    if run_gain(next_offset, next_len) > run_gain(offset, len) + 1 {
+                        run = None;
+                    }
+                }
+            }
+
+            match run {
+                Some((offset, len)) => {
+                    self.flush_literal();
+                    // writing to a Vec can't fail
+                    let _ = write_run(&mut self.out, offset, len);
+                    if self.level == CompressionLevel::Fastest {
+                        self.insert(self.pos);
+                    } else {
+                        for pos in self.pos..self.pos + len {
+                            self.insert(pos);
+                        }
+                    }
+                    self.pos += len;
+                }
+                None => {
+                    self.insert(self.pos);
+                    self.pos += 1;
+                    self.literal += 1;
+                    if self.literal == MAX_PLAIN_CHUNK_LEN {
+                        self.flush_literal();
+                    }
+                }
+            }
+        }
+        if all {
+            self.flush_literal();
+        }
+
+        // keep only the history runs can reach
+        if self.pos > 2 * WINDOW_SIZE {
+            let drop = self.pos - WINDOW_SIZE;
+            self.data.drain(..drop);
+            self.base += drop as u64;
+            self.pos -= drop;
+        }
+    }
+
+    /// Encode the input up to `end` with the fewest bytes possible.
+    ///
+    /// Computes the cheapest encoding of every suffix of the block, trying all literal chunk
+    /// lengths and all lengths of the runs found at each position.
+    fn compress_optimal(&mut self, end: usize) {
+        let start = self.pos;
+        let len = end - start;
+        // runs found at start + i are runs[bounds[i]..bounds[i + 1]]
+        let mut runs = Vec::new();
+        let mut bounds = Vec::with_capacity(len + 1);
+        bounds.push(0);
+        for pos in start..end {
+            self.runs(pos, end, |offset, len| runs.push((offset, len)));
+            bounds.push(runs.len());
+            self.insert(pos);
+        }
+
+        // cost[i] is the size of the cheapest encoding of the block from start + i on
+        let mut cost = vec![0; len + 1];
+        let mut steps = vec![Step::Literal(1); len];
+        // ends j of literal chunks starting at i, with j + cost[j] increasing towards the front
+        let mut literal_ends = VecDeque::new();
+        for i in (0..len).rev() {
+            let key = |j: usize| j + cost[j];
+            while literal_ends.front().is_some_and(|&j| key(j) >= key(i + 1)) {
+                literal_ends.pop_front();
+            }
+            literal_ends.push_front(i + 1);
+            if literal_ends
+                .back()
+                .is_some_and(|&j| j > i + MAX_PLAIN_CHUNK_LEN)
+            {
+                literal_ends.pop_back();
+            }
+
+            let j = *literal_ends.back().expect("just pushed");
+            let mut best = j - i + 1 + cost[j];
+            steps[i] = Step::Literal(j - i);
+            // shorter runs are cheaper with the closer runs found before
+            let mut min_len = 0;
+            for &(offset, max_len) in &runs[bounds[i]..bounds[i + 1]] {
+                for n in cmp::max(min_len, min_run_len(offset))..=max_len {
+                    if run_header_len(offset, n) + cost[i + n] < best {
+                        best = run_header_len(offset, n) + cost[i + n];
+                        steps[i] = Step::Run(offset, n);
+                    }
+                }
+                min_len = max_len + 1;
+            }
+            cost[i] = best;
+        }
+
+        let mut i = 0;
+        while i < len {
+            // writing to a Vec can't fail
+            i += match steps[i] {
+                Step::Literal(n) => {
+                    let _ = write_plain(&mut self.out, &self.data[start + i..start + i + n]);
+                    n
+                }
+                Step::Run(offset, n) => {
+                    let _ = write_run(&mut self.out, offset, n);
+                    n
+                }
+            };
+        }
+        self.pos = end;
+    }
+
+    fn flush_literal(&mut self) {
+        if self.literal > 0 {
+            let start = self.pos - self.literal;
+            // writing to a Vec can't fail
+            let _ = write_plain(&mut self.out, &self.data[start..self.pos]);
+            self.literal = 0;
+        }
+    }
+
+    fn insert(&mut self, pos: usize) {
+        let stream_pos = self.base + pos as u64;
+        if stream_pos >= self.indexed && pos + 3 <= self.data.len() {
+            self.chain.insert(&self.data[pos..], stream_pos);
+            self.indexed = stream_pos + 1;
+        }
+    }
+
+    /// Find the run at `pos` saving the most bytes, as its offset and length.
+    fn find_match(&self, pos: usize, end: usize) -> Option<(u16, usize)> {
+        let mut best = None;
+        self.runs(pos, end, |offset, len| {
+            if best.is_none_or(|(o, l)| run_gain(offset, len) > run_gain(o, l)) {
+                best = Some((offset, len));
+            }
+        });
+        best
+    }
+
+    /// Call `f` with the offset and length of runs at `pos`, closest first
+    ///
+    /// Each run is longer than the ones before, as that is the only way a run further back
+    /// can be better.
+    fn runs<F: FnMut(u16, usize)>(&self, pos: usize, end: usize, mut f: F) {
+        if pos + MIN_RUN_CHUNK_LEN > end {
+            return;
+        }
+
+        let data = &self.data[..end];
+        let stream_pos = self.base + pos as u64;
+        let limit = cmp::max(self.base, stream_pos.saturating_sub(WINDOW_SIZE as u64));
+        let max_len = cmp::min(MAX_RUN_CHUNK_LEN, end - pos);
+        let mut best_len = 0;
+
+        for candidate in self
+            .chain
+            .candidates(&data[pos..], limit)
+            .take(self.chain_length)
+        {
+            let start = (candidate - self.base) as usize;
+            // can't be longer if the byte after the best length differs
+            if data[start + best_len] != data[pos + best_len] {
+                continue;
+            }
+            let len = common_prefix_len(&data[start..], &data[pos..], max_len);
+            let offset = (stream_pos - candidate - 1) as u16;
+            if len > best_len && len >= min_run_len(offset) {
+                f(offset, len);
+                best_len = len;
+                if len == max_len {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Number of equal bytes at the start of `a` and `b`, at most `max`.
+fn common_prefix_len(a: &[u8], b: &[u8], max: usize) -> usize {
+    let mut len = 0;
+    while len + 8 <= max {
+        let x = u64::from_le_bytes(a[len..len + 8].try_into().unwrap());
+        let y = u64::from_le_bytes(b[len..len + 8].try_into().unwrap());
+        if x != y {
+            return len + ((x ^ y).trailing_zeros() / 8) as usize;
+        }
+        len += 8;
+    }
+    while len < max && a[len] == b[len] {
+        len += 1;
+    }
+    len
+}
+
+impl<W: Write> Write for AdcEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // no more input until the output caught up, this bounds the buffered data
+        self.write_out()?;
+        // segments end exactly at the restart interval
+        let buf = match self.restart_interval {
+            Some(interval) => {
+                let left = interval - self.segment_len;
+                &buf[..cmp::min(buf.len() as u64, left) as usize]
+            }
+            None => buf,
+        };
+        self.data.extend_from_slice(buf);
+        self.total_in += buf.len() as u64;
+        self.segment_len += buf.len() as u64;
+        if Some(self.segment_len) == self.restart_interval {
+            self.start_segment();
+            let _ = self.write_out();
+        } else if self.data.len() - self.pos >= BLOCK_SIZE {
+            self.compress(false);
+            // buf is taken now, whatever the output doesn't accept goes out with the next call
+            let _ = self.write_out();
+        }
+        Ok(buf.len())
+    }
+
+    /// Encode all data written so far and flush the writer
+    ///
+    /// The data written so far can be decoded from the output afterwards. Flushing often
+    /// hurts compression a little, as runs can't cross the flushed position.
+    fn flush(&mut self) -> io::Result<()> {
+        self.try_finish()
+    }
+}
+
+impl<W: Write> Drop for AdcEncoder<W> {
+    fn drop(&mut self) {
+        if self.output.is_some() {
+            let _ = self.try_finish();
+        }
+    }
+}
+
+/// Compressor reading uncompressed data from an underlying reader.
+///
+/// Reading from it yields the compressed stream.
+///
+/// ```
+/// use adc::{AdcDecoder, AdcEncoderReader};
+/// use std::io::Read;
+///
+/// let input: &[u8] = b"to be or not to be, that is the question";
+/// let mut compressed = Vec::new();
+/// AdcEncoderReader::new(input).read_to_end(&mut compressed).unwrap();
+///
+/// let mut data = Vec::new();
+/// AdcDecoder::new(&compressed[..]).read_to_end(&mut data).unwrap();
+/// assert_eq!(data, input);
+/// ```
+pub struct AdcEncoderReader<R> {
+    input: R,
+    encoder: AdcEncoder<Vec<u8>>,
+    /// Bytes of the encoder output already returned
+    pos: usize,
+    done: bool,
+}
+
+impl<R: Read> AdcEncoderReader<R> {
+    /// Create an encoder compressing the data read from `input`
+    pub fn new(input: R) -> AdcEncoderReader<R> {
+        AdcEncoderReader::with_level(input, CompressionLevel::Default)
+    }
+
+    /// Create an encoder compressing with the given level
+    pub fn with_level(input: R, level: CompressionLevel) -> AdcEncoderReader<R> {
+        AdcEncoderReader {
+            input,
+            encoder: AdcEncoder::with_level(Vec::new(), level),
+            pos: 0,
+            done: false,
+        }
+    }
+
+    /// Set the chain length, see [`AdcEncoder::chain_length`]
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chain_length` is zero.
+    pub fn chain_length(mut self, chain_length: usize) -> AdcEncoderReader<R> {
+        self.encoder = self.encoder.chain_length(chain_length);
+        self
+    }
+
+    /// Restart every `interval` bytes, see [`AdcEncoder::restart_interval`]
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    pub fn restart_interval(mut self, interval: u64) -> AdcEncoderReader<R> {
+        self.encoder = self.encoder.restart_interval(interval);
+        self
+    }
+
+    /// Prime the encoder with a dictionary, see [`AdcEncoder::with_dictionary`]
+    ///
+    /// # Panics
+    ///
+    /// Panics if data was read already.
+    pub fn with_dictionary(mut self, dictionary: &[u8]) -> AdcEncoderReader<R> {
+        self.encoder = self.encoder.with_dictionary(dictionary);
+        self
+    }
+}
+
+impl<R: Read> Read for AdcEncoderReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let mut chunk = [0; 8 * 1024];
+        loop {
+            let out = self.encoder.output_mut();
+            if self.pos < out.len() {
+                let n = cmp::min(buf.len(), out.len() - self.pos);
+                buf[..n].copy_from_slice(&out[self.pos..self.pos + n]);
+                self.pos += n;
+                if self.pos == out.len() {
+                    out.clear();
+                    self.pos = 0;
+                }
+                return Ok(n);
+            }
+            if self.done {
+                return Ok(0);
+            }
+
+            let n = match self.input.read(&mut chunk) {
+                Ok(n) => n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if n == 0 {
+                self.encoder.try_finish()?;
+                self.done = true;
+            } else {
+                self.encoder.write_all(&chunk[..n])?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generator::StreamGenerator, inspect, AdcChunkType, AdcDecoder};
+    use proptest::prelude::*;
+
+    const LEVELS: &[CompressionLevel] = &[
+        CompressionLevel::Store,
+        CompressionLevel::Fastest,
+        CompressionLevel::Default,
+        CompressionLevel::Best,
+        CompressionLevel::Optimal,
+    ];
+
+    fn compress(data: &[u8]) -> Vec<u8> {
+        compress_with(data, CompressionLevel::Default)
+    }
+
+    fn compress_with(data: &[u8], level: CompressionLevel) -> Vec<u8> {
+        let mut encoder = AdcEncoder::with_level(Vec::new(), level);
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn decompress(compressed: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        AdcDecoder::new(compressed).read_to_end(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn round_trip() {
+        assert!(compress(b"").is_empty());
+        assert_eq!(compress(b"a"), [0x80, b'a']);
+
+        let stream = StreamGenerator::new(5)
+            .zeros(100_000)
+            .text(200_000)
+            .random(10_000)
+            .pathological(50_000)
+            .finish();
+        let compressed = compress(&stream.decompressed);
+        assert_eq!(decompress(&compressed), stream.decompressed);
+        assert!(compressed.len() < stream.decompressed.len() / 2);
+    }
+
+    #[test]
+    fn chunk_types() {
+        let mut data = b"abcdabcdabcd".to_vec();
+        data.extend_from_slice(&[0xee; 2000]);
+        data.extend_from_slice(b"abcdefgh");
+        let compressed = compress(&data);
+        assert_eq!(decompress(&compressed), data);
+
+        let kinds: Vec<_> = inspect(&compressed[..])
+            .unwrap()
+            .chunks
+            .iter()
+            .map(|c| c.kind)
+            .collect();
+        assert!(kinds.contains(&AdcChunkType::Plain));
+        assert!(kinds.contains(&AdcChunkType::TwoByte));
+        assert!(kinds.contains(&AdcChunkType::ThreeByte));
+    }
+
+    #[test]
+    fn longest_two_byte_run() {
+        // one literal and a run of 18 bytes at distance 1 fit in a two byte header
+        let data = [b'a'; 1 + MAX_TWO_BYTE_RUN_LEN];
+        for &level in &[CompressionLevel::Best, CompressionLevel::Optimal] {
+            assert_eq!(
+                compress_with(&data, level),
+                [0x80, b'a', 0x3c, 0x00],
+                "{:?}",
+                level
+            );
+        }
+    }
+
+    #[test]
+    fn dictionary() {
+        let stream = StreamGenerator::new(27).text(100_000).finish();
+        let (dictionary, data) = stream.decompressed.split_at(80_000);
+        for &level in LEVELS {
+            let mut encoder = AdcEncoder::with_level(Vec::new(), level).with_dictionary(dictionary);
+            encoder.write_all(data).unwrap();
+            let compressed = encoder.finish().unwrap();
+            if level != CompressionLevel::Store {
+                assert!(compressed.len() < compress_with(data, level).len());
+            }
+
+            let mut d = AdcDecoder::new(&compressed[..]);
+            d.set_dictionary(dictionary);
+            let mut decompressed = Vec::new();
+            d.read_to_end(&mut decompressed).unwrap();
+            assert!(decompressed == data, "{:?}", level);
+        }
+    }
+
+    #[test]
+    fn zeros() {
+        let compressed = compress(&[0; 1 << 20]);
+        assert_eq!(decompress(&compressed), vec![0; 1 << 20]);
+        // one plain chunk, then runs of the maximum length
+        assert!(compressed.len() < (1 << 20) / MAX_RUN_CHUNK_LEN * 3 + 10);
+    }
+
+    #[test]
+    fn flush() {
+        let mut encoder = AdcEncoder::new(Vec::new());
+        encoder.write_all(b"hello hello").unwrap();
+        encoder.flush().unwrap();
+        let flushed = encoder.output.as_ref().unwrap().clone();
+        assert_eq!(decompress(&flushed), b"hello hello");
+
+        encoder.write_all(b" hello").unwrap();
+        assert_eq!(decompress(&encoder.finish().unwrap()), b"hello hello hello");
+    }
+
+    /// Takes at most 7 bytes per write and blocks every other call.
+    struct Choppy {
+        data: Vec<u8>,
+        calls: usize,
+    }
+
+    impl Write for Choppy {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.calls += 1;
+            if self.calls & 1 == 0 {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            let n = cmp::min(buf.len(), 7);
+            self.data.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn non_blocking_output() {
+        let stream = StreamGenerator::new(28)
+            .text(200_000)
+            .random(50_000)
+            .finish();
+        let mut encoder = AdcEncoder::new(Choppy {
+            data: Vec::new(),
+            calls: 0,
+        });
+        let mut input = &stream.decompressed[..];
+        while !input.is_empty() {
+            match encoder.write(&input[..cmp::min(input.len(), 10_000)]) {
+                Ok(n) => input = &input[n..],
+                Err(e) => assert_eq!(e.kind(), io::ErrorKind::WouldBlock),
+            }
+        }
+        while let Err(e) = encoder.try_finish() {
+            assert_eq!(e.kind(), io::ErrorKind::WouldBlock);
+        }
+        let output = encoder.finish().unwrap();
+
+        assert!(output.calls > 100);
+        assert_eq!(output.data, compress(&stream.decompressed));
+    }
+
+    #[test]
+    fn reader() {
+        let data = StreamGenerator::new(6).text(300_000).finish().decompressed;
+        let mut reader = AdcEncoderReader::new(&data[..]);
+        let mut compressed = Vec::new();
+        let mut buf = [0; 1000];
+        loop {
+            let n = reader.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            compressed.extend_from_slice(&buf[..n]);
+        }
+
+        assert_eq!(compressed, compress(&data));
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn levels() {
+        let data = StreamGenerator::new(7)
+            .text(200_000)
+            .pathological(50_000)
+            .finish()
+            .decompressed;
+        let sizes: Vec<_> = LEVELS
+            .iter()
+            .map(|&level| {
+                let compressed = compress_with(&data, level);
+                assert_eq!(decompress(&compressed), data);
+                compressed.len()
+            })
+            .collect();
+
+        assert!(sizes.windows(2).all(|w| w[0] >= w[1]), "{:?}", sizes);
+    }
+
+    #[test]
+    fn store() {
+        let data = StreamGenerator::new(9)
+            .zeros(1000)
+            .text(1000)
+            .finish()
+            .decompressed;
+        let compressed = compress_with(&data, CompressionLevel::Store);
+        assert_eq!(decompress(&compressed), data);
+        assert_eq!(compressed.len(), data.len() + data.len().div_ceil(128));
+
+        let map = inspect(&compressed[..]).unwrap();
+        assert!(map.chunks.iter().all(|c| c.kind == AdcChunkType::Plain));
+    }
+
+    #[test]
+    fn chain_lengths() {
+        let data = StreamGenerator::new(8).text(100_000).finish().decompressed;
+        let compress_chain = |chain_length| {
+            let mut encoder = AdcEncoder::new(Vec::new()).chain_length(chain_length);
+            encoder.write_all(&data).unwrap();
+            let compressed = encoder.finish().unwrap();
+            assert_eq!(decompress(&compressed), data);
+            compressed.len()
+        };
+
+        assert!(compress_chain(1) > compress_chain(8));
+        assert!(compress_chain(8) > compress_chain(128));
+    }
+
+    #[test]
+    fn restart_interval() {
+        let data = StreamGenerator::new(11)
+            .text(50_000)
+            .zeros(20_000)
+            .finish()
+            .decompressed;
+        let mut encoder = AdcEncoder::new(Vec::new()).restart_interval(10_000);
+        let mut ends = Vec::new();
+        for part in data.chunks(2500) {
+            encoder.write_all(part).unwrap();
+            if encoder.total_in() % 10_000 == 0 {
+                ends.push(encoder.total_out() as usize);
+            }
+        }
+        assert_eq!(encoder.total_in(), 70_000);
+        let compressed = encoder.finish().unwrap();
+        assert_eq!(decompress(&compressed), data);
+
+        // every segment decodes on its own
+        assert_eq!(ends.len(), 7);
+        assert_eq!(ends[6], compressed.len());
+        let mut start = 0;
+        for (i, &end) in ends.iter().enumerate() {
+            let segment = decompress(&compressed[start..end]);
+            assert!(segment == data[i * 10_000..(i + 1) * 10_000], "{}", i);
+            start = end;
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn round_trips_generated_data(
+            seed: u64,
+            lens in prop::collection::vec(0..20_000usize, 4),
+            write_size in 1..100_000usize,
+            level in prop::sample::select(LEVELS),
+        ) {
+            let data = StreamGenerator::new(seed)
+                .zeros(lens[0])
+                .text(lens[1])
+                .random(lens[2])
+                .pathological(lens[3])
+                .finish()
+                .decompressed;
+
+            let mut encoder = AdcEncoder::with_level(Vec::new(), level);
+            for part in data.chunks(write_size) {
+                encoder.write_all(part).unwrap();
+            }
+            let compressed = encoder.finish().unwrap();
+
+            prop_assert_eq!(decompress(&compressed), data.clone());
+            prop_assert!(compressed.len() <= data.len() + data.len() / MAX_PLAIN_CHUNK_LEN + 1);
+        }
+    }
+}
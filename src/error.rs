@@ -0,0 +1,193 @@
+//! Error type of the decoder.
+
+use std::{error::Error, fmt, io};
+
+/// Failure while decoding an ADC stream.
+///
+/// The decoder implements `Read`, so its errors are returned as [`io::Error`] with an
+/// `AdcError` as the payload. Converting such an `io::Error` back with `AdcError::from`
+/// recovers the original error to match on.
+///
+/// ```
+/// use adc::{AdcDecoder, AdcError};
+/// use std::io::Read;
+///
+/// let input: &[u8] = &[0x81, 0xab, 0xcd, 0x40, 0x00, 0x05];
+/// let err = AdcDecoder::new(input).read_to_end(&mut Vec::new()).unwrap_err();
+/// match AdcError::from(err) {
+///     AdcError::InvalidOffset(context) => assert_eq!(context.compressed_offset, 3),
+///     err => panic!("{}", err),
+/// }
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum AdcError {
+    /// A run referenced data before the start of the output
+    InvalidOffset(ChunkContext),
+    /// The stream ended in the middle of a chunk
+    TruncatedChunk(ChunkContext),
+    /// The stream was finished before all of its data was read
+    UnreadData(ChunkContext),
+    /// Input was left after the end of the stream, at the given compressed offset
+    TrailingData(u64),
+    /// The stream decompresses to more than the given maximum number of bytes
+    OutputLimit(u64),
+    /// The stream decompresses to more than the expected number of bytes
+    TooLong {
+        /// Expected decompressed size
+        expected: u64,
+    },
+    /// The stream ended before decompressing to the expected number of bytes
+    TooShort {
+        /// Expected decompressed size
+        expected: u64,
+        /// Number of bytes the stream decompressed to
+        actual: u64,
+    },
+    /// Reading the input failed
+    Io(io::Error),
+}
+
+/// Location of the chunk a decoding error occurred in.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub struct ChunkContext {
+    /// Index of the chunk in the stream
+    pub index: u64,
+    /// Offset of the chunk header in the compressed input
+    pub compressed_offset: u64,
+    /// Offset of the chunk data in the decompressed output
+    pub decompressed_offset: u64,
+    /// Header bytes of the chunk, incomplete if the stream ended within the header
+    pub header: Vec<u8>,
+}
+
+impl ChunkContext {
+    pub(crate) fn new(
+        index: u64,
+        compressed_offset: u64,
+        decompressed_offset: u64,
+        header: &[u8],
+    ) -> ChunkContext {
+        ChunkContext {
+            index,
+            compressed_offset,
+            decompressed_offset,
+            header: header.to_vec(),
+        }
+    }
+}
+
+impl fmt::Display for ChunkContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "chunk {} at compressed offset {}, decompressed offset {} (header",
+            self.index, self.compressed_offset, self.decompressed_offset
+        )?;
+        for byte in &self.header {
+            write!(f, " {:02x}", byte)?;
+        }
+        f.write_str(")")
+    }
+}
+
+impl AdcError {
+    /// Get the kind of the `io::Error` this error converts to
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            AdcError::InvalidOffset(_) => io::ErrorKind::InvalidData,
+            AdcError::TruncatedChunk(_) => io::ErrorKind::UnexpectedEof,
+            AdcError::UnreadData(_)
+            | AdcError::TrailingData(_)
+            | AdcError::OutputLimit(_)
+            | AdcError::TooLong { .. } => io::ErrorKind::InvalidData,
+            AdcError::TooShort { .. } => io::ErrorKind::UnexpectedEof,
+            AdcError::Io(err) => err.kind(),
+        }
+    }
+}
+
+impl fmt::Display for AdcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdcError::InvalidOffset(context) => write!(f, "invalid chunk offset in {}", context),
+            AdcError::TruncatedChunk(context) => write!(f, "truncated chunk in {}", context),
+            AdcError::UnreadData(context) => write!(f, "unread data in {}", context),
+            AdcError::TrailingData(offset) => {
+                write!(f, "trailing data at compressed offset {}", offset)
+            }
+            AdcError::OutputLimit(max) => {
+                write!(f, "decompressed data exceeds the limit of {} bytes", max)
+            }
+            AdcError::TooLong { expected } => {
+                write!(f, "stream decompresses to more than {} bytes", expected)
+            }
+            AdcError::TooShort { expected, actual } => write!(
+                f,
+                "stream decompressed to {} bytes, expected {}",
+                actual, expected
+            ),
+            AdcError::Io(err) => err.fmt(f),
+        }
+    }
+}
+
+impl Error for AdcError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AdcError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<AdcError> for io::Error {
+    fn from(err: AdcError) -> io::Error {
+        match err {
+            AdcError::Io(err) => err,
+            err => {
+                // every decoding error passes through here on its way to the caller
+                trace_event!(debug, error = %err, "decoding failed");
+                io::Error::new(err.kind(), err)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for AdcError {
+    fn from(err: io::Error) -> AdcError {
+        if err.get_ref().is_some_and(|inner| inner.is::<AdcError>()) {
+            let inner = err.into_inner().expect("the error has a payload");
+            return *inner.downcast().expect("the payload is an AdcError");
+        }
+        AdcError::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn io_round_trip() {
+        let context = ChunkContext::new(2, 7, 9, &[0x40]);
+        let err = io::Error::from(AdcError::TruncatedChunk(context.clone()));
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(
+            err.to_string(),
+            "truncated chunk in chunk 2 at compressed offset 7, decompressed offset 9 (header 40)"
+        );
+        assert!(matches!(AdcError::from(err), AdcError::TruncatedChunk(c) if c == context));
+
+        // other errors pass through unchanged
+        let err = AdcError::from(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "disk on fire",
+        ));
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert!(err.source().is_some());
+        let err = io::Error::from(err);
+        assert_eq!(err.to_string(), "disk on fire");
+    }
+}
@@ -0,0 +1,144 @@
+//! Decoding of compressed extents stored inside a larger file.
+//!
+//! Disk images keep each compressed block at some offset inside the image file. [`FileExtent`]
+//! exposes such a byte range as a `Read` using positioned reads, so the block can be decoded
+//! without loading it into memory first. Positioned reads don't depend on the file cursor,
+//! so several extents of the same file can be decoded at the same time.
+//!
+//! On Unix the file cursor is left untouched. On Windows positioned reads move it to the
+//! end of the data read, so don't mix extents with reads through the cursor of the same
+//! file there.
+
+use crate::AdcDecoder;
+use std::{
+    cmp,
+    fs::File,
+    io::{self, prelude::*, BufReader},
+};
+
+/// Decoder reading its compressed input from a [`FileExtent`].
+pub type ExtentDecoder<'a> = AdcDecoder<BufReader<FileExtent<'a>>>;
+
+/// A byte range of a file, readable through positioned reads.
+pub struct FileExtent<'a> {
+    file: &'a File,
+    pos: u64,
+    end: u64,
+}
+
+impl<'a> FileExtent<'a> {
+    /// Create a reader for `len` bytes of `file` starting at `offset`
+    pub fn new(file: &'a File, offset: u64, len: u64) -> FileExtent<'a> {
+        FileExtent {
+            file,
+            pos: offset,
+            end: offset.saturating_add(len),
+        }
+    }
+
+    /// Number of bytes left in the extent
+    pub fn remaining(&self) -> u64 {
+        self.end - self.pos
+    }
+}
+
+impl Read for FileExtent<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = cmp::min(buf.len() as u64, self.remaining()) as usize;
+        if len == 0 {
+            return Ok(0);
+        }
+
+        let n = read_at(self.file, &mut buf[..len], self.pos)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+/// Create a decoder for the compressed extent of `len` bytes at `offset` in `file`.
+///
+/// The extent is read in small buffered pieces, so memory usage does not depend on its size.
+pub fn decode_extent(file: &File, offset: u64, len: u64) -> ExtentDecoder<'_> {
+    AdcDecoder::new(BufReader::new(FileExtent::new(file, offset, len)))
+}
+
+#[cfg(unix)]
+pub(crate) fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::unix::fs::FileExt::read_at(file, buf, offset)
+}
+
+/// Moves the file cursor, unlike `read_at` on Unix.
+#[cfg(windows)]
+pub(crate) fn read_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    std::os::windows::fs::FileExt::seek_read(file, buf, offset)
+}
+
+#[cfg(unix)]
+pub(crate) fn write_all_at(file: &File, buf: &[u8], offset: u64) -> io::Result<()> {
+    std::os::unix::fs::FileExt::write_all_at(file, buf, offset)
+}
+
+#[cfg(windows)]
+pub(crate) fn write_all_at(file: &File, mut buf: &[u8], mut offset: u64) -> io::Result<()> {
+    while !buf.is_empty() {
+        match std::os::windows::fs::FileExt::seek_write(file, buf, offset) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => {
+                buf = &buf[n..];
+                offset += n as u64;
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::{Seek, SeekFrom};
+
+    /// Create an unlinked temporary file holding `contents`.
+    pub(crate) fn temp_file(name: &str, contents: &[u8]) -> File {
+        let path = std::env::temp_dir().join(format!("adc-{}-{}", name, std::process::id()));
+        let mut file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap();
+        std::fs::remove_file(&path).ok();
+        file.write_all(contents).unwrap();
+        file
+    }
+
+    #[test]
+    fn decode_extent_in_file() {
+        let mut contents = vec![0xaa; 100];
+        contents.extend_from_slice(&[0x83, 0xfe, 0xed, 0xfa, 0xce, 0x00, 0x00]);
+        contents.extend_from_slice(&[0xbb; 100]);
+        let mut file = temp_file("extent", &contents);
+        file.seek(SeekFrom::Start(3)).unwrap();
+
+        let mut data = Vec::new();
+        decode_extent(&file, 100, 7).read_to_end(&mut data).unwrap();
+
+        assert_eq!(data, [0xfe, 0xed, 0xfa, 0xce, 0xce, 0xce, 0xce]);
+        // the file cursor is left untouched, except on Windows
+        #[cfg(unix)]
+        assert_eq!(file.stream_position().unwrap(), 3);
+    }
+
+    #[test]
+    fn extent_past_end_of_file() {
+        let file = temp_file("extent-eof", &[1, 2, 3]);
+        let mut extent = FileExtent::new(&file, 1, 10);
+
+        let mut data = Vec::new();
+        extent.read_to_end(&mut data).unwrap();
+
+        assert_eq!(data, [2, 3]);
+    }
+}
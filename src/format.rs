@@ -0,0 +1,93 @@
+//! Constants and header predicates of the ADC format.
+
+use crate::AdcChunkType;
+
+/// Number of previous output bytes that runs can copy from.
+pub const WINDOW_SIZE: usize = 0x10000;
+
+/// Largest window offset of a run chunk, the run starts `offset + 1` bytes back.
+pub const MAX_OFFSET: u16 = 0xffff;
+
+/// Largest window offset of a two byte run chunk.
+pub const MAX_TWO_BYTE_OFFSET: u16 = 0x3ff;
+
+/// Largest number of bytes in a plain chunk.
+pub const MAX_PLAIN_CHUNK_LEN: usize = 128;
+
+/// Smallest number of bytes in a run chunk.
+pub const MIN_RUN_CHUNK_LEN: usize = 3;
+
+/// Largest number of bytes in a two byte run chunk.
+pub const MAX_TWO_BYTE_RUN_LEN: usize = 18;
+
+/// Smallest number of bytes in a three byte run chunk.
+pub const MIN_THREE_BYTE_RUN_LEN: usize = 4;
+
+/// Largest number of bytes in a run chunk.
+pub const MAX_RUN_CHUNK_LEN: usize = 67;
+
+/// Check if `byte` is the header of a plain chunk.
+pub const fn is_plain_header(byte: u8) -> bool {
+    byte & 0x80 != 0
+}
+
+/// Check if `byte` is the first header byte of a two byte run chunk.
+pub const fn is_two_byte_header(byte: u8) -> bool {
+    byte & 0xc0 == 0
+}
+
+/// Check if `byte` is the first header byte of a three byte run chunk.
+pub const fn is_three_byte_header(byte: u8) -> bool {
+    byte & 0xc0 == 0x40
+}
+
+/// Get the type of the chunk starting with header byte `byte`.
+pub const fn chunk_type(byte: u8) -> AdcChunkType {
+    if is_plain_header(byte) {
+        AdcChunkType::Plain
+    } else if is_three_byte_header(byte) {
+        AdcChunkType::ThreeByte
+    } else {
+        AdcChunkType::TwoByte
+    }
+}
+
+/// Get the length of the header of the chunk starting with `byte`.
+pub const fn header_len(byte: u8) -> usize {
+    match chunk_type(byte) {
+        AdcChunkType::Plain => 1,
+        AdcChunkType::TwoByte => 2,
+        AdcChunkType::ThreeByte => 3,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_predicates() {
+        for byte in 0..=255u8 {
+            let matches = [
+                is_plain_header(byte),
+                is_two_byte_header(byte),
+                is_three_byte_header(byte),
+            ];
+            assert_eq!(matches.iter().filter(|&&m| m).count(), 1, "{:02x}", byte);
+        }
+        assert_eq!(chunk_type(0x3f), AdcChunkType::TwoByte);
+        assert_eq!(chunk_type(0x40), AdcChunkType::ThreeByte);
+        assert_eq!(header_len(0x80), 1);
+        assert_eq!(header_len(0x7f), 3);
+    }
+
+    #[test]
+    fn length_limits() {
+        let len = |header: &[u8]| crate::AdcChunk::parse(header).unwrap().0.decompressed_len();
+        assert_eq!(len(&[0x00, 0x00]), MIN_RUN_CHUNK_LEN);
+        assert_eq!(len(&[0x3c, 0x00]), MAX_TWO_BYTE_RUN_LEN);
+        assert_eq!(len(&[0x40, 0x00, 0x00]), MIN_THREE_BYTE_RUN_LEN);
+        assert_eq!(len(&[0x7f, 0x00, 0x00]), MAX_RUN_CHUNK_LEN);
+        assert_eq!(len(&[0xff]), MAX_PLAIN_CHUNK_LEN);
+    }
+}
@@ -0,0 +1,372 @@
+//! Generator for synthetic ADC streams with known decompressed output.
+//!
+//! The generated streams mimic the kind of data found in disk images (long zero runs, text,
+//! incompressible data) and can also contain pathological chunks that stress decoder edge
+//! cases, like maximum offsets and overlapping runs. Every stream comes with its expected
+//! output, which makes the generator useful for tests and for seeding fuzz corpora.
+//!
+//! ```
+//! use adc::{generator::StreamGenerator, AdcDecoder};
+//! use std::io::Read;
+//!
+//! let stream = StreamGenerator::new(42).zeros(1000).text(5000).random(100).finish();
+//!
+//! let mut data = Vec::new();
+//! AdcDecoder::new(&stream.compressed[..]).read_to_end(&mut data).unwrap();
+//! assert_eq!(data, stream.decompressed);
+//! ```
+
+use crate::{
+    MAX_PLAIN_CHUNK_LEN, MAX_RUN_CHUNK_LEN, MAX_TWO_BYTE_RUN_LEN, MIN_RUN_CHUNK_LEN,
+    MIN_THREE_BYTE_RUN_LEN,
+};
+use std::cmp;
+
+const MAX_TWO_BYTE_OFFSET: usize = crate::MAX_TWO_BYTE_OFFSET as usize;
+const MAX_OFFSET: usize = crate::MAX_OFFSET as usize;
+
+const WORDS: &[&str] = &[
+    "the",
+    "of",
+    "and",
+    "a",
+    "to",
+    "in",
+    "is",
+    "file",
+    "disk",
+    "image",
+    "data",
+    "block",
+    "sector",
+    "volume",
+    "apple",
+    "partition",
+    "system",
+    "library",
+    "\n",
+    "user",
+    "with",
+    "for",
+    "on",
+];
+
+/// A generated stream together with the data it decompresses to.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct GeneratedStream {
+    /// The ADC-compressed stream
+    pub compressed: Vec<u8>,
+    /// The expected decompressed output
+    pub decompressed: Vec<u8>,
+}
+
+/// Builder for synthetic ADC streams.
+///
+/// Regions are appended in the order the methods are called. The output is fully determined
+/// by the seed.
+pub struct StreamGenerator {
+    rng: Rng,
+    stream: GeneratedStream,
+}
+
+impl StreamGenerator {
+    /// Create an empty generator with the given random seed
+    pub fn new(seed: u64) -> StreamGenerator {
+        StreamGenerator {
+            rng: Rng::new(seed),
+            stream: GeneratedStream {
+                compressed: Vec::new(),
+                decompressed: Vec::new(),
+            },
+        }
+    }
+
+    /// Append `len` zero bytes, encoded as runs
+    pub fn zeros(&mut self, len: usize) -> &mut StreamGenerator {
+        if len == 0 {
+            return self;
+        }
+        self.plain(&[0]);
+
+        let mut left = len - 1;
+        while left > 0 {
+            let run = cmp::min(left, MAX_RUN_CHUNK_LEN);
+            if run < 3 {
+                self.plain(&vec![0; run]);
+            } else {
+                self.run(0, run);
+            }
+            left -= run;
+        }
+        self
+    }
+
+    /// Append `len` bytes of text-like data, with repeated phrases encoded as runs
+    pub fn text(&mut self, len: usize) -> &mut StreamGenerator {
+        let end = self.stream.decompressed.len() + len;
+        let mut literal = Vec::new();
+
+        while self.stream.decompressed.len() + literal.len() < end {
+            let left = end - self.stream.decompressed.len() - literal.len();
+            let history = self.stream.decompressed.len();
+
+            if self.rng.below(3) == 0 && history >= 8 && left >= 4 {
+                // repeat a phrase from the recent past
+                self.plain(&literal);
+                literal.clear();
+
+                let offset = self.rng.below(cmp::min(history, 4096));
+                let run = 4 + self.rng.below(cmp::min(left, MAX_RUN_CHUNK_LEN) - 3);
+                self.run(offset, run);
+            } else {
+                let word = WORDS[self.rng.below(WORDS.len())].as_bytes();
+                let take = cmp::min(word.len() + 1, left);
+                literal.extend(word.iter().chain(b" ").take(take));
+            }
+        }
+        self.plain(&literal);
+        self
+    }
+
+    /// Append `len` bytes of incompressible data, encoded as plain chunks
+    pub fn random(&mut self, len: usize) -> &mut StreamGenerator {
+        let bytes: Vec<u8> = (0..len).map(|_| self.rng.next() as u8).collect();
+        self.plain(&bytes);
+        self
+    }
+
+    /// Append chunks exercising the boundaries of the format
+    ///
+    /// This includes runs at the largest offset each chunk type can encode, runs longer than
+    /// their offset, and chunks of minimum and maximum length. Offsets are limited by the
+    /// history generated so far, so append other regions first to reach the largest ones.
+    pub fn pathological(&mut self, len: usize) -> &mut StreamGenerator {
+        let end = self.stream.decompressed.len() + len;
+        if self.stream.decompressed.is_empty() && len > 0 {
+            self.plain(&[0xa5]);
+        }
+
+        while self.stream.decompressed.len() < end {
+            let left = end - self.stream.decompressed.len();
+            let history = self.stream.decompressed.len();
+            let choice = self.rng.below(7);
+
+            if left < 4 {
+                let bytes: Vec<u8> = (0..left).map(|_| self.rng.next() as u8).collect();
+                self.plain(&bytes);
+                continue;
+            }
+
+            match choice {
+                // largest offsets of both run chunk types
+                0 => self.run(
+                    cmp::min(history - 1, MAX_OFFSET),
+                    cmp::min(left, MAX_RUN_CHUNK_LEN),
+                ),
+                1 => self.run(cmp::min(history - 1, MAX_TWO_BYTE_OFFSET), 3),
+                // runs much longer than their offset
+                2 => {
+                    let offset = self.rng.below(cmp::min(history, 4));
+                    self.run(offset, cmp::min(left, MAX_RUN_CHUNK_LEN))
+                }
+                // shortest chunks of each type
+                3 => self.run(0, 4),
+                4 => {
+                    let byte = self.rng.next() as u8;
+                    self.plain(&[byte])
+                }
+                // the longest two byte run
+                5 => self.run(
+                    cmp::min(history - 1, MAX_TWO_BYTE_OFFSET),
+                    cmp::min(left, MAX_TWO_BYTE_RUN_LEN),
+                ),
+                // a maximum length plain chunk
+                _ => {
+                    let bytes: Vec<u8> = (0..cmp::min(left, MAX_PLAIN_CHUNK_LEN))
+                        .map(|_| self.rng.next() as u8)
+                        .collect();
+                    self.plain(&bytes)
+                }
+            }
+        }
+        self
+    }
+
+    /// Get the generated stream
+    pub fn finish(&self) -> GeneratedStream {
+        self.stream.clone()
+    }
+
+    fn plain(&mut self, bytes: &[u8]) {
+        for chunk in bytes.chunks(MAX_PLAIN_CHUNK_LEN) {
+            self.stream.compressed.push(0x80 | (chunk.len() - 1) as u8);
+            self.stream.compressed.extend_from_slice(chunk);
+            self.stream.decompressed.extend_from_slice(chunk);
+        }
+    }
+
+    /// Emit a run of `len` bytes copied from `offset + 1` bytes back.
+    fn run(&mut self, offset: usize, len: usize) {
+        debug_assert!(offset < self.stream.decompressed.len() && offset <= MAX_OFFSET);
+
+        let out = &mut self.stream.compressed;
+        if (MIN_RUN_CHUNK_LEN..=MAX_TWO_BYTE_RUN_LEN).contains(&len)
+            && offset <= MAX_TWO_BYTE_OFFSET
+        {
+            out.push((((len - 3) as u8) << 2) | (offset >> 8) as u8);
+            out.push(offset as u8);
+        } else {
+            debug_assert!((MIN_THREE_BYTE_RUN_LEN..=MAX_RUN_CHUNK_LEN).contains(&len));
+            out.push(0x40 | (len - 4) as u8);
+            out.extend_from_slice(&(offset as u16).to_be_bytes());
+        }
+
+        let data = &mut self.stream.decompressed;
+        for _ in 0..len {
+            data.push(data[data.len() - 1 - offset]);
+        }
+    }
+}
+
+/// Small deterministic xorshift generator, good enough for test data.
+struct Rng(u64);
+
+/// Mixed into seeds so that small seeds don't start out with mostly zero bits.
+const SEED_MIX: u64 = 0x9e37_79b9_7f4a_7c15;
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        // xorshift never leaves the all-zero state
+        match seed ^ SEED_MIX {
+            0 => Rng(SEED_MIX),
+            state => Rng(state),
+        }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AdcDecoder;
+    use proptest::prelude::*;
+    use std::io::Read;
+
+    fn check(stream: &GeneratedStream) {
+        let mut data = Vec::new();
+        AdcDecoder::new(&stream.compressed[..])
+            .read_to_end(&mut data)
+            .unwrap();
+        assert_eq!(data, stream.decompressed);
+    }
+
+    #[test]
+    fn region_lengths() {
+        let stream = StreamGenerator::new(1).zeros(1000).finish();
+        assert_eq!(stream.decompressed, vec![0; 1000]);
+        check(&stream);
+
+        for len in 0..200 {
+            let stream = StreamGenerator::new(len as u64)
+                .text(len)
+                .random(len)
+                .finish();
+            assert_eq!(stream.decompressed.len(), len * 2);
+            check(&stream);
+        }
+    }
+
+    #[test]
+    fn pathological() {
+        for seed in 0..20 {
+            let stream = StreamGenerator::new(seed)
+                .random(70000)
+                .pathological(20000)
+                .finish();
+            assert_eq!(stream.decompressed.len(), 90000);
+            check(&stream);
+
+            let longest_two_byte = crate::inspect(&stream.compressed[..])
+                .unwrap()
+                .chunks
+                .iter()
+                .filter(|c| c.kind == crate::AdcChunkType::TwoByte)
+                .map(|c| c.decompressed_len)
+                .max();
+            assert_eq!(longest_two_byte, Some(MAX_TWO_BYTE_RUN_LEN as u64));
+        }
+    }
+
+    #[test]
+    fn zero_state_seed() {
+        let mut rng = super::Rng::new(SEED_MIX);
+        assert_ne!(rng.next(), 0);
+        assert_ne!(rng.next(), rng.next());
+    }
+
+    #[test]
+    fn deterministic() {
+        let a = StreamGenerator::new(7).text(500).pathological(500).finish();
+        let b = StreamGenerator::new(7).text(500).pathological(500).finish();
+        assert_eq!(a, b);
+    }
+
+    #[derive(Clone, Debug)]
+    enum Region {
+        Zeros(usize),
+        Text(usize),
+        Random(usize),
+        Pathological(usize),
+    }
+
+    fn region() -> impl Strategy<Value = Region> {
+        prop_oneof![
+            (0..5000usize).prop_map(Region::Zeros),
+            (0..5000usize).prop_map(Region::Text),
+            (0..1000usize).prop_map(Region::Random),
+            (0..2000usize).prop_map(Region::Pathological),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn decodes_generated_streams(
+            seed: u64,
+            regions in prop::collection::vec(region(), 0..8),
+            read_size in 1..300usize,
+        ) {
+            let mut generator = StreamGenerator::new(seed);
+            for region in &regions {
+                match *region {
+                    Region::Zeros(len) => generator.zeros(len),
+                    Region::Text(len) => generator.text(len),
+                    Region::Random(len) => generator.random(len),
+                    Region::Pathological(len) => generator.pathological(len),
+                };
+            }
+            let stream = generator.finish();
+
+            let mut decoder = AdcDecoder::new(&stream.compressed[..]);
+            let mut data = Vec::new();
+            let mut buf = vec![0; read_size];
+            loop {
+                let n = decoder.read(&mut buf).unwrap();
+                if n == 0 {
+                    break;
+                }
+                data.extend_from_slice(&buf[..n]);
+            }
+            prop_assert_eq!(data, stream.decompressed);
+        }
+    }
+}
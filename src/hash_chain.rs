@@ -0,0 +1,112 @@
+//! Hash chains over the window, for finding earlier occurrences of three byte sequences.
+
+use crate::WINDOW_SIZE;
+
+const HASH_BITS: u32 = 16;
+
+fn hash(bytes: &[u8]) -> usize {
+    let v = u32::from(bytes[0]) << 16 | u32::from(bytes[1]) << 8 | u32::from(bytes[2]);
+    (v.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+}
+
+/// Index of stream positions by the hash of the three bytes starting there.
+///
+/// Positions with the same hash are linked from the most recent one backwards. Links are
+/// kept for the last [`WINDOW_SIZE`] positions only, which is as far back as runs reach.
+pub(crate) struct HashChain {
+    /// Most recent position plus one of each hash, zero if none
+    head: Vec<u64>,
+    /// Previous position plus one with the same hash, by position modulo the window size
+    prev: Vec<u64>,
+}
+
+impl HashChain {
+    pub(crate) fn new() -> HashChain {
+        HashChain {
+            head: vec![0; 1 << HASH_BITS],
+            prev: vec![0; WINDOW_SIZE],
+        }
+    }
+
+    /// Add stream position `pos`, starting with `bytes`
+    ///
+    /// Positions must be added in increasing order.
+    pub(crate) fn insert(&mut self, bytes: &[u8], pos: u64) {
+        let head = &mut self.head[hash(bytes)];
+        self.prev[pos as usize % WINDOW_SIZE] = *head;
+        *head = pos + 1;
+    }
+
+    /// Iterate over positions that may start with `bytes`, most recent first
+    ///
+    /// Stops before `limit`, which must be at most [`WINDOW_SIZE`] positions before the next
+    /// position to be added. The bytes at the positions need to be compared, different
+    /// sequences can have the same hash.
+    pub(crate) fn candidates(&self, bytes: &[u8], limit: u64) -> Candidates<'_> {
+        Candidates {
+            chain: self,
+            next: self.head[hash(bytes)],
+            limit,
+        }
+    }
+}
+
+/// Iterator returned by [`HashChain::candidates`].
+pub(crate) struct Candidates<'a> {
+    chain: &'a HashChain,
+    next: u64,
+    limit: u64,
+}
+
+impl Iterator for Candidates<'_> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        let pos = self.next.checked_sub(1)?;
+        if pos < self.limit {
+            return None;
+        }
+        self.next = self.chain.prev[pos as usize % WINDOW_SIZE];
+        Some(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chain_order() {
+        let data = b"abcXabcYabcZabc";
+        let mut chain = HashChain::new();
+        for pos in 0..data.len() - 2 {
+            chain.insert(&data[pos..], pos as u64);
+        }
+
+        let found: Vec<_> = chain
+            .candidates(b"abc", 0)
+            .filter(|&pos| &data[pos as usize..pos as usize + 3] == b"abc")
+            .collect();
+        assert_eq!(found, [12, 8, 4, 0]);
+        assert_eq!(
+            chain.candidates(b"abc", 5).filter(|&p| p % 4 == 0).count(),
+            2
+        );
+    }
+
+    #[test]
+    fn window_limit() {
+        let mut chain = HashChain::new();
+        let len = WINDOW_SIZE as u64 * 3;
+        for pos in 0..len {
+            chain.insert(b"zzz", pos);
+        }
+
+        // links older than the window were overwritten, but are never reached
+        let limit = len - WINDOW_SIZE as u64;
+        let found: Vec<_> = chain.candidates(b"zzz", limit).collect();
+        assert_eq!(found.len(), WINDOW_SIZE);
+        assert_eq!(found[0], len - 1);
+        assert_eq!(*found.last().unwrap(), limit);
+    }
+}
@@ -0,0 +1,113 @@
+//! Hex dumps of compressed streams, optionally annotated with the chunk structure.
+
+use crate::ChunkIter;
+use std::io::{self, prelude::*};
+
+const BYTES_PER_LINE: usize = 16;
+
+/// Write a hex dump of `bytes` to `out`, with offsets starting at `base`.
+///
+/// Each line shows the offset, up to 16 bytes in hex and their printable ASCII characters.
+pub fn hexdump<W: Write>(bytes: &[u8], base: u64, mut out: W) -> io::Result<()> {
+    for (i, line) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+        write!(out, "{:08x} ", base + (i * BYTES_PER_LINE) as u64)?;
+        for byte in line {
+            write!(out, " {:02x}", byte)?;
+        }
+        let ascii: String = line
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        let pad = 3 * (BYTES_PER_LINE - line.len());
+        writeln!(out, "{:pad$}  |{}|", "", ascii, pad = pad)?;
+    }
+    Ok(())
+}
+
+/// Write a hex dump of the compressed stream `input` to `out`, preceding each chunk with a
+/// comment line describing its header.
+///
+/// If the stream is corrupt, the error is noted and the remaining bytes are dumped without
+/// annotations.
+pub fn annotated_hexdump<W: Write>(input: &[u8], mut out: W) -> io::Result<()> {
+    let mut pos = 0;
+    for (i, chunk) in ChunkIter::new(input).enumerate() {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(err) => {
+                writeln!(out, "; error: {}", err)?;
+                break;
+            }
+        };
+
+        let end = chunk.decompressed_offset + chunk.decompressed_len;
+        match chunk.offset {
+            Some(offset) => writeln!(
+                out,
+                "; chunk {}: {}, {} bytes from offset {} -> output {}..{}",
+                i, chunk.kind, chunk.decompressed_len, offset, chunk.decompressed_offset, end
+            )?,
+            None => writeln!(
+                out,
+                "; chunk {}: {}, {} bytes -> output {}..{}",
+                i, chunk.kind, chunk.decompressed_len, chunk.decompressed_offset, end
+            )?,
+        }
+
+        let start = chunk.compressed_offset as usize;
+        pos = start + chunk.compressed_len as usize;
+        hexdump(&input[start..pos], start as u64, &mut out)?;
+    }
+
+    hexdump(&input[pos..], pos as u64, &mut out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(f: impl FnOnce(&mut Vec<u8>) -> io::Result<()>) -> String {
+        let mut out = Vec::new();
+        f(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn plain_dump() {
+        let bytes: Vec<u8> = (0x30..0x45).collect();
+        assert_eq!(
+            render(|out| hexdump(&bytes, 0x10, out)),
+            [
+                "00000010  30 31 32 33 34 35 36 37 38 39 3a 3b 3c 3d 3e 3f  |0123456789:;<=>?|",
+                "00000020  40 41 42 43 44                                   |@ABCD|",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn annotated_dump() {
+        let input: &[u8] = &[0x81, 0x41, 0x0a, 0x00, 0x01, 0x00, 0x09];
+        assert_eq!(
+            render(|out| annotated_hexdump(input, out)),
+            [
+                "; chunk 0: plain, 2 bytes -> output 0..2",
+                "00000000  81 41 0a                                         |.A.|",
+                "; chunk 1: two_byte, 3 bytes from offset 1 -> output 2..5",
+                "00000003  00 01                                            |..|",
+                "; error: invalid chunk offset in chunk 2 at compressed offset 5, decompressed \
+                 offset 5 (header 00 09)",
+                "00000005  00 09                                            |..|",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+}
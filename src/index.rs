@@ -0,0 +1,432 @@
+//! Random access into compressed streams.
+//!
+//! ADC runs reach back up to [`WINDOW_SIZE`](crate::WINDOW_SIZE) bytes, so decoding can only
+//! start in the middle of a stream with the window of output before it. [`StreamIndex`] scans
+//! a stream once and keeps [`Checkpoint`]s with that window at regular intervals, reading
+//! at any offset then only decodes from the closest checkpoint before it.
+
+use crate::{
+    bytes::{ReadBe, WriteBe},
+    AdcDecoder, Window, WINDOW_SIZE,
+};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "serde")]
+use std::convert::TryFrom;
+use std::{
+    cmp,
+    io::{self, prelude::*, SeekFrom},
+};
+
+/// First bytes of a serialized index.
+const MAGIC: &[u8; 4] = b"ADCI";
+
+/// Version of the serialized index format.
+const VERSION: u32 = 1;
+
+/// Position at a chunk boundary of a stream, with everything needed to resume decoding there.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[non_exhaustive]
+pub struct Checkpoint {
+    /// Offset of the next chunk in the compressed stream
+    pub compressed_offset: u64,
+    /// Offset of the next chunk in the decompressed data
+    pub decompressed_offset: u64,
+    /// Number of chunks before the checkpoint
+    chunks: u64,
+    /// Window of output before the checkpoint, oldest byte first
+    window: Vec<u8>,
+}
+
+impl Checkpoint {
+    /// Get the output before the checkpoint runs can refer to, oldest byte first
+    pub fn window(&self) -> &[u8] {
+        &self.window
+    }
+}
+
+/// Checkpoints into a compressed stream for decoding from arbitrary offsets.
+///
+/// Each checkpoint holds up to 64 KiB of window, the interval trades the memory of the
+/// index against the data to decode before reaching an offset.
+///
+/// ```
+/// use adc::{compress_to_vec, CompressionLevel, StreamIndex};
+/// use std::io::{Cursor, Read};
+///
+/// let data: Vec<u8> = (0..100_000u32).map(|i| (i % 251) as u8).collect();
+/// let compressed = compress_to_vec(&data, CompressionLevel::Default);
+/// let index = StreamIndex::build(&compressed[..], 16 * 1024).unwrap();
+///
+/// let mut d = index.decoder_at(Cursor::new(&compressed), 70_000).unwrap();
+/// let mut buf = [0; 4];
+/// d.read_exact(&mut buf).unwrap();
+/// assert_eq!(buf, data[70_000..70_004]);
+/// ```
+///
+/// # Serialization
+///
+/// Indexes can be stored next to the stream with [`write_to`](StreamIndex::write_to) and
+/// loaded with [`read_from`](StreamIndex::read_from). All integers of this format are big
+/// endian:
+///
+/// | Size | Content                                            |
+/// |------|----------------------------------------------------|
+/// | 4    | magic `ADCI`                                       |
+/// | 4    | format version, currently 1                        |
+/// | 8    | length of the compressed stream                    |
+/// | 8    | length of the decompressed data                    |
+/// | 8    | number of checkpoints                              |
+///
+/// followed by each checkpoint:
+///
+/// | Size | Content                                            |
+/// |------|----------------------------------------------------|
+/// | 8    | offset in the compressed stream                    |
+/// | 8    | offset in the decompressed data                    |
+/// | 8    | number of chunks before the checkpoint             |
+/// | 4    | length of the window, at most 65536                |
+/// | n    | window, oldest byte first                          |
+///
+/// With the `serde` feature the index also serializes with serde, e.g. as JSON, as a map
+/// with a `version` field and the same content. Versions other than 1 are rejected.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(into = "VersionedIndex", try_from = "VersionedIndex")
+)]
+pub struct StreamIndex {
+    checkpoints: Vec<Checkpoint>,
+    compressed_len: u64,
+    decompressed_len: u64,
+}
+
+impl StreamIndex {
+    /// Decode the whole stream from `input`, recording a checkpoint at the first chunk
+    /// boundary after every `interval` bytes of output
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    pub fn build<R: Read>(input: R, interval: u64) -> io::Result<StreamIndex> {
+        assert!(interval > 0, "checkpoint interval must not be zero");
+
+        let mut decoder = AdcDecoder::new(input);
+        let mut checkpoints = vec![Checkpoint {
+            compressed_offset: 0,
+            decompressed_offset: 0,
+            chunks: 0,
+            window: Vec::new(),
+        }];
+        let mut next = interval;
+        let mut buf = vec![0; 32 * 1024];
+        while decoder.decode_chunk(&mut buf)? > 0 {
+            if decoder.current_chunk.is_none() && decoder.total_out >= next {
+                checkpoints.push(Checkpoint {
+                    compressed_offset: decoder.total_in,
+                    decompressed_offset: decoder.total_out,
+                    chunks: decoder.chunks,
+                    window: decoder.window.snapshot(),
+                });
+                next = decoder.total_out + interval;
+            }
+        }
+
+        Ok(StreamIndex {
+            checkpoints,
+            compressed_len: decoder.total_in,
+            decompressed_len: decoder.total_out,
+        })
+    }
+
+    /// Get the checkpoints in stream order, the first one at the start of the stream
+    pub fn checkpoints(&self) -> &[Checkpoint] {
+        &self.checkpoints
+    }
+
+    /// Get the length of the compressed stream
+    pub fn compressed_len(&self) -> u64 {
+        self.compressed_len
+    }
+
+    /// Get the length of the decompressed data
+    pub fn decompressed_len(&self) -> u64 {
+        self.decompressed_len
+    }
+
+    /// Write the index in the [binary format](StreamIndex#serialization)
+    ///
+    /// ```
+    /// use adc::{compress_to_vec, CompressionLevel, StreamIndex};
+    ///
+    /// let compressed = compress_to_vec(&[1; 100_000], CompressionLevel::Default);
+    /// let index = StreamIndex::build(&compressed[..], 16 * 1024).unwrap();
+    ///
+    /// let mut sidecar = Vec::new();
+    /// index.write_to(&mut sidecar).unwrap();
+    /// assert_eq!(StreamIndex::read_from(&sidecar[..]).unwrap(), index);
+    /// ```
+    pub fn write_to<W: Write>(&self, mut output: W) -> io::Result<()> {
+        output.write_all(MAGIC)?;
+        output.write_u32_be(VERSION)?;
+        output.write_u64_be(self.compressed_len)?;
+        output.write_u64_be(self.decompressed_len)?;
+        output.write_u64_be(self.checkpoints.len() as u64)?;
+        for checkpoint in &self.checkpoints {
+            output.write_u64_be(checkpoint.compressed_offset)?;
+            output.write_u64_be(checkpoint.decompressed_offset)?;
+            output.write_u64_be(checkpoint.chunks)?;
+            output.write_bytes_be(&checkpoint.window)?;
+        }
+        Ok(())
+    }
+
+    /// Read an index written by [`write_to`](StreamIndex::write_to)
+    ///
+    /// Fails with `InvalidData` if the data isn't an index of a supported version or the
+    /// checkpoints are inconsistent.
+    pub fn read_from<R: Read>(mut input: R) -> io::Result<StreamIndex> {
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_index("not an ADC stream index"));
+        }
+        check_version(input.read_u32_be()?)?;
+
+        let compressed_len = input.read_u64_be()?;
+        let decompressed_len = input.read_u64_be()?;
+        let count = input.read_u64_be()?;
+        // the count can't be trusted before the checkpoints were read
+        let mut checkpoints = Vec::with_capacity(cmp::min(count, 1024) as usize);
+        for _ in 0..count {
+            checkpoints.push(Checkpoint {
+                compressed_offset: input.read_u64_be()?,
+                decompressed_offset: input.read_u64_be()?,
+                chunks: input.read_u64_be()?,
+                window: input.read_bytes_be(WINDOW_SIZE)?,
+            });
+        }
+        StreamIndex::new(checkpoints, compressed_len, decompressed_len)
+    }
+
+    /// Create an index from deserialized parts, checking that they are consistent.
+    fn new(
+        checkpoints: Vec<Checkpoint>,
+        compressed_len: u64,
+        decompressed_len: u64,
+    ) -> io::Result<StreamIndex> {
+        let first = checkpoints
+            .first()
+            .ok_or_else(|| invalid_index("index without checkpoints"))?;
+        if first.compressed_offset != 0 || first.decompressed_offset != 0 || first.chunks != 0 {
+            return Err(invalid_index("first checkpoint is not at the start"));
+        }
+        let ordered = checkpoints.windows(2).all(|pair| {
+            pair[0].compressed_offset < pair[1].compressed_offset
+                && pair[0].decompressed_offset < pair[1].decompressed_offset
+                && pair[0].chunks < pair[1].chunks
+        });
+        let last = &checkpoints[checkpoints.len() - 1];
+        if !ordered
+            || last.compressed_offset > compressed_len
+            || last.decompressed_offset > decompressed_len
+        {
+            return Err(invalid_index("checkpoints out of order"));
+        }
+        let windows_fit = checkpoints.iter().all(|c| {
+            c.window.len() <= WINDOW_SIZE && c.window.len() as u64 <= c.decompressed_offset
+        });
+        if !windows_fit {
+            return Err(invalid_index("checkpoint window out of range"));
+        }
+
+        Ok(StreamIndex {
+            checkpoints,
+            compressed_len,
+            decompressed_len,
+        })
+    }
+
+    /// Create a decoder reading the decompressed data from `offset` on
+    ///
+    /// The stream has to start at offset 0 of `input`. It is positioned at the closest
+    /// checkpoint before `offset` and decoding skips forward from there.
+    pub fn decoder_at<R: Read + Seek>(
+        &self,
+        mut input: R,
+        offset: u64,
+    ) -> io::Result<AdcDecoder<R>> {
+        let pos = self
+            .checkpoints
+            .partition_point(|c| c.decompressed_offset <= offset);
+        let checkpoint = &self.checkpoints[pos - 1];
+        input.seek(SeekFrom::Start(checkpoint.compressed_offset))?;
+
+        trace_event!(
+            debug,
+            compressed_offset = checkpoint.compressed_offset,
+            decompressed_offset = checkpoint.decompressed_offset,
+            "decoder restored from checkpoint"
+        );
+        let mut window = Window::new();
+        window.extend(&checkpoint.window);
+        let mut decoder = AdcDecoder {
+            window,
+            total_in: checkpoint.compressed_offset,
+            total_out: checkpoint.decompressed_offset,
+            chunks: checkpoint.chunks,
+            ..AdcDecoder::new(input)
+        };
+        decoder.skip(offset - checkpoint.decompressed_offset)?;
+        Ok(decoder)
+    }
+}
+
+fn invalid_index(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn check_version(version: u32) -> io::Result<()> {
+    match version {
+        VERSION => Ok(()),
+        _ => Err(invalid_index(&format!(
+            "unsupported index version {}",
+            version
+        ))),
+    }
+}
+
+/// Serde representation of a [`StreamIndex`], tagged with the format version.
+#[cfg(feature = "serde")]
+#[derive(Serialize, Deserialize)]
+struct VersionedIndex {
+    version: u32,
+    compressed_len: u64,
+    decompressed_len: u64,
+    checkpoints: Vec<Checkpoint>,
+}
+
+#[cfg(feature = "serde")]
+impl From<StreamIndex> for VersionedIndex {
+    fn from(index: StreamIndex) -> VersionedIndex {
+        VersionedIndex {
+            version: VERSION,
+            compressed_len: index.compressed_len,
+            decompressed_len: index.decompressed_len,
+            checkpoints: index.checkpoints,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<VersionedIndex> for StreamIndex {
+    type Error = io::Error;
+
+    fn try_from(index: VersionedIndex) -> io::Result<StreamIndex> {
+        check_version(index.version)?;
+        StreamIndex::new(
+            index.checkpoints,
+            index.compressed_len,
+            index.decompressed_len,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generator::StreamGenerator, AdcError};
+    use std::io::Cursor;
+
+    #[test]
+    fn read_at_offsets() {
+        let stream = StreamGenerator::new(19)
+            .text(100_000)
+            .zeros(20_000)
+            .random(10_000)
+            .finish();
+        let index = StreamIndex::build(&stream.compressed[..], 10_000).unwrap();
+        assert!(index.checkpoints().len() >= 13);
+        assert_eq!(index.compressed_len(), stream.compressed.len() as u64);
+        assert_eq!(index.decompressed_len(), stream.decompressed.len() as u64);
+
+        let len = stream.decompressed.len();
+        for &offset in &[0, 1, 9_999, 10_000, 54_321, len - 1, len] {
+            let input = Cursor::new(&stream.compressed);
+            let mut d = index.decoder_at(input, offset as u64).unwrap();
+            let mut data = Vec::new();
+            d.read_to_end(&mut data).unwrap();
+            assert!(data == stream.decompressed[offset..], "offset {}", offset);
+            assert_eq!(d.total_in(), stream.compressed.len() as u64);
+        }
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let stream = StreamGenerator::new(21).text(50_000).finish();
+        let index = StreamIndex::build(&stream.compressed[..], 8192).unwrap();
+        let mut sidecar = Vec::new();
+        index.write_to(&mut sidecar).unwrap();
+        assert_eq!(&sidecar[..8], b"ADCI\0\0\0\x01");
+
+        let read = StreamIndex::read_from(&sidecar[..]).unwrap();
+        assert_eq!(read, index);
+        let mut d = read
+            .decoder_at(Cursor::new(&stream.compressed), 30_000)
+            .unwrap();
+        let mut data = Vec::new();
+        d.read_to_end(&mut data).unwrap();
+        assert!(data == stream.decompressed[30_000..]);
+
+        let invalid = |sidecar: &[u8]| StreamIndex::read_from(sidecar).unwrap_err().to_string();
+        let mut other_version = sidecar.clone();
+        other_version[7] = 2;
+        assert_eq!(invalid(&other_version), "unsupported index version 2");
+        assert_eq!(invalid(b"ADCX\0\0\0\x01"), "not an ADC stream index");
+        // the checkpoint count is past the end of the data
+        let mut missing = sidecar[..28].to_vec();
+        missing[27] = 100;
+        let err = StreamIndex::read_from(&missing[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        // the second checkpoint moved before the first
+        let mut unordered = index.clone();
+        unordered.checkpoints[1].decompressed_offset = 0;
+        let mut sidecar = Vec::new();
+        unordered.write_to(&mut sidecar).unwrap();
+        assert_eq!(invalid(&sidecar), "checkpoints out of order");
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_round_trip() {
+        let stream = StreamGenerator::new(22).text(30_000).finish();
+        let index = StreamIndex::build(&stream.compressed[..], 8192).unwrap();
+
+        let json = serde_json::to_string(&index).unwrap();
+        assert!(json.starts_with("{\"version\":1,"));
+        assert_eq!(serde_json::from_str::<StreamIndex>(&json).unwrap(), index);
+
+        let json = json.replacen("\"version\":1", "\"version\":9", 1);
+        let err = serde_json::from_str::<StreamIndex>(&json).unwrap_err();
+        assert!(err.to_string().contains("unsupported index version 9"));
+    }
+
+    #[test]
+    fn error_context() {
+        let input = [0x81, 0xab, 0xcd, 0x81, 0x01, 0x02, 0x40, 0x00, 0x07];
+        let index = StreamIndex::build(&input[..6], 1).unwrap();
+        assert_eq!(index.checkpoints().len(), 3);
+
+        let err = index
+            .decoder_at(Cursor::new(&input), 3)
+            .unwrap()
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        let expected = "invalid chunk offset in chunk 2 at compressed offset 6, \
+                        decompressed offset 4 (header 40 00 07)";
+        assert_eq!(AdcError::from(err).to_string(), expected);
+    }
+}
@@ -0,0 +1,276 @@
+//! Inspection of the chunk structure of an ADC stream.
+//!
+//! [`inspect`] walks a stream without producing any output and records where each chunk
+//! is located in the compressed input and in the decompressed output.
+
+use crate::{read_header, AdcChunkType, AdcError, ChunkContext};
+use std::{
+    io::{self, prelude::*},
+    iter::FusedIterator,
+};
+
+/// Location and encoding of a single chunk.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub struct ChunkInfo {
+    /// Encoding of the chunk
+    pub kind: AdcChunkType,
+    /// Offset of the chunk header in the compressed stream
+    pub compressed_offset: u64,
+    /// Length of the chunk in the compressed stream, including its header
+    pub compressed_len: u64,
+    /// Offset of the chunk data in the decompressed output
+    pub decompressed_offset: u64,
+    /// Number of bytes the chunk decompresses to
+    pub decompressed_len: u64,
+    /// Window offset for run chunks, the run starts `offset + 1` bytes back
+    pub offset: Option<u16>,
+}
+
+/// Map of all chunks of a stream.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct StreamMap {
+    /// Chunks in stream order
+    pub chunks: Vec<ChunkInfo>,
+}
+
+impl StreamMap {
+    /// Total length of the compressed stream
+    pub fn compressed_len(&self) -> u64 {
+        self.chunks
+            .last()
+            .map_or(0, |c| c.compressed_offset + c.compressed_len)
+    }
+
+    /// Total length of the decompressed output
+    pub fn decompressed_len(&self) -> u64 {
+        self.chunks
+            .last()
+            .map_or(0, |c| c.decompressed_offset + c.decompressed_len)
+    }
+
+    /// Find the chunk covering `offset` in the compressed stream
+    ///
+    /// ```
+    /// use adc::inspect;
+    ///
+    /// let input: &[u8] = &[0x81, 0xab, 0xcd, 0x00, 0x01];
+    /// let map = inspect(input).unwrap();
+    /// assert_eq!(map.chunk_at_compressed(4).unwrap().decompressed_offset, 2);
+    /// assert_eq!(map.chunk_at_decompressed(1).unwrap().compressed_offset, 0);
+    /// assert!(map.chunk_at_compressed(5).is_none());
+    /// ```
+    pub fn chunk_at_compressed(&self, offset: u64) -> Option<&ChunkInfo> {
+        let pos = self
+            .chunks
+            .partition_point(|c| c.compressed_offset + c.compressed_len <= offset);
+        self.chunks.get(pos)
+    }
+
+    /// Find the chunk producing `offset` of the decompressed output
+    pub fn chunk_at_decompressed(&self, offset: u64) -> Option<&ChunkInfo> {
+        let pos = self
+            .chunks
+            .partition_point(|c| c.decompressed_offset + c.decompressed_len <= offset);
+        self.chunks.get(pos)
+    }
+}
+
+/// Reader counting the bytes read through it.
+pub(crate) struct Counter<R> {
+    inner: R,
+    pub(crate) count: u64,
+}
+
+impl<R> Counter<R> {
+    pub(crate) fn new(inner: R) -> Counter<R> {
+        Counter { inner, count: 0 }
+    }
+}
+
+impl<R: Read> Read for Counter<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+}
+
+/// Iterator over the chunks of a stream, skipping over literal data.
+///
+/// Yields the same [`ChunkInfo`] as [`inspect`], one chunk at a time without keeping them,
+/// and stops after the first error.
+///
+/// ```
+/// use adc::{AdcChunkType, ChunkIter};
+///
+/// let input: &[u8] = &[0x81, 0xab, 0xcd, 0x00, 0x01];
+/// let kinds: Vec<_> = ChunkIter::new(input).map(|c| c.unwrap().kind).collect();
+/// assert_eq!(kinds, [AdcChunkType::Plain, AdcChunkType::TwoByte]);
+/// ```
+pub struct ChunkIter<R> {
+    input: Counter<R>,
+    index: u64,
+    decompressed_offset: u64,
+    done: bool,
+}
+
+impl<R: Read> ChunkIter<R> {
+    /// Create an iterator over the chunks of the stream read from `input`
+    pub fn new(input: R) -> ChunkIter<R> {
+        ChunkIter {
+            input: Counter::new(input),
+            index: 0,
+            decompressed_offset: 0,
+            done: false,
+        }
+    }
+
+    fn next_chunk(&mut self) -> io::Result<Option<ChunkInfo>> {
+        let compressed_offset = self.input.count;
+        let header = read_header(&mut self.input)?;
+        if header.len == 0 {
+            return Ok(None);
+        }
+        let (index, decompressed_offset) = (self.index, self.decompressed_offset);
+        let context = || {
+            ChunkContext::new(
+                index,
+                compressed_offset,
+                decompressed_offset,
+                header.as_bytes(),
+            )
+        };
+        let chunk = header
+            .parse()
+            .ok_or_else(|| AdcError::TruncatedChunk(context()))?;
+
+        let offset = match chunk.r#type {
+            AdcChunkType::Plain => {
+                let len = u64::from(chunk.size);
+                if io::copy(&mut (&mut self.input).take(len), &mut io::sink())? < len {
+                    return Err(AdcError::TruncatedChunk(context()).into());
+                }
+                None
+            }
+            _ => {
+                if u64::from(chunk.offset) >= self.decompressed_offset {
+                    return Err(AdcError::InvalidOffset(context()).into());
+                }
+                Some(chunk.offset)
+            }
+        };
+
+        let info = ChunkInfo {
+            kind: chunk.r#type,
+            compressed_offset,
+            compressed_len: self.input.count - compressed_offset,
+            decompressed_offset: self.decompressed_offset,
+            decompressed_len: u64::from(chunk.size),
+            offset,
+        };
+        self.index += 1;
+        self.decompressed_offset += u64::from(chunk.size);
+        Ok(Some(info))
+    }
+}
+
+impl<R: Read> Iterator for ChunkIter<R> {
+    type Item = io::Result<ChunkInfo>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let res = self.next_chunk().transpose();
+        if !matches!(res, Some(Ok(_))) {
+            self.done = true;
+        }
+        res
+    }
+}
+
+impl<R: Read> FusedIterator for ChunkIter<R> {}
+
+/// Build the chunk map of the stream read from `input`.
+///
+/// Fails like the decoder does if the stream is truncated or a run references data before
+/// the start of the output.
+pub fn inspect<R: Read>(input: R) -> io::Result<StreamMap> {
+    let chunks = ChunkIter::new(input).collect::<io::Result<_>>()?;
+    Ok(StreamMap { chunks })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn all_types() {
+        let input: &[u8] = &[0x83, 0xfe, 0xed, 0xfa, 0xce, 0x00, 0x00, 0x40, 0x00, 0x06];
+        let map = inspect(input).unwrap();
+
+        assert_eq!(
+            map.chunks,
+            [
+                ChunkInfo {
+                    kind: AdcChunkType::Plain,
+                    compressed_offset: 0,
+                    compressed_len: 5,
+                    decompressed_offset: 0,
+                    decompressed_len: 4,
+                    offset: None,
+                },
+                ChunkInfo {
+                    kind: AdcChunkType::TwoByte,
+                    compressed_offset: 5,
+                    compressed_len: 2,
+                    decompressed_offset: 4,
+                    decompressed_len: 3,
+                    offset: Some(0),
+                },
+                ChunkInfo {
+                    kind: AdcChunkType::ThreeByte,
+                    compressed_offset: 7,
+                    compressed_len: 3,
+                    decompressed_offset: 7,
+                    decompressed_len: 4,
+                    offset: Some(6),
+                },
+            ]
+        );
+        assert_eq!(map.compressed_len(), 10);
+        assert_eq!(map.decompressed_len(), 11);
+
+        let index = |chunk: Option<&ChunkInfo>| chunk.map(|c| c.compressed_offset);
+        for &(offset, expected) in &[(0, Some(0)), (4, Some(0)), (6, Some(5)), (9, Some(7))] {
+            assert_eq!(index(map.chunk_at_compressed(offset)), expected);
+        }
+        assert_eq!(index(map.chunk_at_compressed(10)), None);
+        for &(offset, expected) in &[(0, Some(0)), (3, Some(0)), (4, Some(5)), (10, Some(7))] {
+            assert_eq!(index(map.chunk_at_decompressed(offset)), expected);
+        }
+        assert_eq!(index(map.chunk_at_decompressed(11)), None);
+    }
+
+    #[test]
+    fn stops_after_error() {
+        let input: &[u8] = &[0x81, 0xab, 0xcd, 0x40, 0x00, 0x07, 0x00, 0x00];
+        let mut chunks = ChunkIter::new(input);
+        assert_eq!(chunks.next().unwrap().unwrap().compressed_len, 3);
+        let err = chunks.next().unwrap().unwrap_err();
+        assert!(matches!(AdcError::from(err), AdcError::InvalidOffset(c) if c.index == 1));
+        assert!(chunks.next().is_none());
+    }
+
+    #[test]
+    fn invalid_input() {
+        let truncated: &[u8] = &[0x83, 0xfe, 0xed];
+        let err = inspect(truncated).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let bad_offset: &[u8] = &[0x00, 0x00];
+        let err = inspect(bad_offset).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,130 @@
+//! Compressed input from iterators.
+
+use crate::AdcDecoder;
+use std::io::{self, Read};
+
+mod sealed {
+    use std::io;
+
+    /// Item types an [`IterReader`](super::IterReader) accepts: `u8` and `io::Result<u8>`.
+    pub trait Byte {
+        fn into_byte(self) -> io::Result<u8>;
+    }
+
+    impl Byte for u8 {
+        fn into_byte(self) -> io::Result<u8> {
+            Ok(self)
+        }
+    }
+
+    impl Byte for io::Result<u8> {
+        fn into_byte(self) -> io::Result<u8> {
+            self
+        }
+    }
+}
+
+/// Reader over the bytes of an iterator.
+///
+/// The items are either `u8` or `io::Result<u8>`, errors are returned from `read`.
+pub struct IterReader<I> {
+    iter: I,
+    error: Option<io::Error>,
+}
+
+impl<I> IterReader<I>
+where
+    I: Iterator,
+    I::Item: sealed::Byte,
+{
+    /// Read the bytes yielded by `iter`
+    pub fn new<T: IntoIterator<IntoIter = I>>(iter: T) -> IterReader<I> {
+        IterReader {
+            iter: iter.into_iter(),
+            error: None,
+        }
+    }
+}
+
+impl<I> Read for IterReader<I>
+where
+    I: Iterator,
+    I::Item: sealed::Byte,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+
+        let mut len = 0;
+        while len < buf.len() {
+            match self.iter.next().map(sealed::Byte::into_byte) {
+                Some(Ok(byte)) => {
+                    buf[len] = byte;
+                    len += 1;
+                }
+                // return the bytes read so far first
+                Some(Err(err)) if len > 0 => {
+                    self.error = Some(err);
+                    break;
+                }
+                Some(Err(err)) => return Err(err),
+                None => break,
+            }
+        }
+        Ok(len)
+    }
+}
+
+impl<I> AdcDecoder<IterReader<I>>
+where
+    I: Iterator,
+    I::Item: sealed::Byte,
+{
+    /// Create a decoder reading the compressed data from an iterator
+    ///
+    /// The iterator can yield `u8` or `io::Result<u8>`.
+    ///
+    /// ```
+    /// use adc::AdcDecoder;
+    /// use std::io::Read;
+    ///
+    /// let input = vec![0x81, 0xab, 0xcd, 0x00, 0x01];
+    /// let mut data = Vec::new();
+    /// AdcDecoder::from_bytes(input).read_to_end(&mut data).unwrap();
+    /// assert_eq!(data, [0xab, 0xcd, 0xab, 0xcd, 0xab]);
+    /// ```
+    pub fn from_bytes<T: IntoIterator<IntoIter = I>>(iter: T) -> AdcDecoder<IterReader<I>> {
+        AdcDecoder::new(IterReader::new(iter))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fallible_items() {
+        let items = vec![
+            Ok(0x82),
+            Ok(0xab),
+            Ok(0xcd),
+            Err(io::Error::other("source failed")),
+        ];
+        let mut d = AdcDecoder::from_bytes(items);
+        let err = d.read_to_end(&mut Vec::new()).unwrap_err();
+
+        assert_eq!(err.to_string(), "source failed");
+    }
+
+    #[test]
+    fn short_reads() {
+        let mut r = IterReader::new((0..10u8).map(Ok::<_, io::Error>));
+        let mut buf = [0; 4];
+        assert_eq!(r.read(&mut buf).unwrap(), 4);
+        assert_eq!(r.read(&mut buf).unwrap(), 4);
+        assert_eq!(r.read(&mut buf).unwrap(), 2);
+        assert_eq!(buf[..2], [8, 9]);
+        assert_eq!(r.read(&mut buf).unwrap(), 0);
+    }
+}
@@ -1,6 +1,7 @@
 //! Implementation of the Apple Data Compression scheme in Rust
 //!
-//! ADC is a rather basic run length compression scheme. This library implements decompression only.
+//! ADC is a rather basic run length compression scheme. This library implements decompression
+//! with [`AdcDecoder`] and compression with [`AdcEncoder`].
 //!
 //! # Example
 //!
@@ -17,124 +18,908 @@
 //! };
 //! println!("{:?} bytes decompressed", bytes_out);
 //! ````
+//!
+//! # Features
+//!
+//! The library has no dependencies by default. Everything beyond it is opt-in:
+//!
+//! - `std` (enabled by default): everything built on `std::io`. Without it the crate is
+//!   `no_std` and decoding works through the [`stream`] module
+//! - `alloc` (enabled by `std`): the window lives on the heap. Without it the crate doesn't
+//!   allocate at all and the window is a fixed array inside [`stream::AdcState`]
+//! - `async`: [`AsyncAdcDecoder`] and [`AsyncAdcDecoderWriter`] for decompressing through
+//!   tokio's `AsyncRead` and `AsyncWrite`
+//! - `futures`: the same for the `futures-io` traits used by async-std and smol
+//! - `capi`: C functions for decompressing from C and C++ (the `capi` module)
+//! - `wasm`: `wasm-bindgen` bindings for decompressing in the browser (the `wasm` module)
+//! - `serde`: serialization of [`DecoderState`] for resuming decoding in another process,
+//!   and of [`StreamIndex`]
+//! - `dmg`: parsing of UDIF disk images (the `dmg` module)
+//! - `tracing`: `tracing` events for the chunks [`AdcDecoder`] parses, decoder resets,
+//!   damage skipped in recovery mode and decoding errors with their offsets
+//! - `rayon`: [`decompress_blocks_parallel`] for decoding many independent blocks on the
+//!   rayon thread pool
+//! - `test-support`: builders for ADC chunks and streams (the `test_support` module)
+//! - `fallible-alloc`: allocations sized by the input (the window, the prefix examined by
+//!   `detect`, the list of corruptions, the output of `decompress_to_vec`) fail with an
+//!   `OutOfMemory` error instead of aborting
+//! - `cli`: the `adc` command line tool
 
-use byteorder::{ReadBytesExt, BE};
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+use core::fmt;
+#[cfg(feature = "std")]
 use std::{
     cmp,
-    collections::VecDeque,
-    io::{self, prelude::*},
-    u16,
+    io::{self, prelude::*, SeekFrom},
 };
 
-#[derive(PartialEq, Debug)]
-enum AdcChunkType {
+/// Emit a `tracing` event with the `tracing` feature, nothing without it.
+#[cfg(feature = "std")]
+macro_rules! trace_event {
+    ($level:ident, $($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::$level!($($arg)*);
+    };
+}
+
+pub mod stream;
+
+#[cfg(feature = "std")]
+pub mod bufread;
+#[cfg(feature = "std")]
+pub mod generator;
+#[cfg(feature = "std")]
+pub mod read;
+#[cfg(feature = "std")]
+pub mod write;
+
+#[cfg(any(feature = "async", feature = "futures"))]
+mod async_io;
+#[cfg(feature = "std")]
+mod bytes;
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "dmg")]
+pub mod dmg;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(feature = "test-support")]
+pub mod test_support;
+
+mod format;
+mod window;
+
+#[cfg(feature = "std")]
+mod block;
+#[cfg(feature = "std")]
+mod checksum;
+#[cfg(feature = "std")]
+mod decoder_writer;
+#[cfg(feature = "std")]
+mod encoder;
+#[cfg(feature = "std")]
+mod error;
+#[cfg(feature = "std")]
+mod hash_chain;
+#[cfg(feature = "std")]
+mod hexdump;
+#[cfg(feature = "std")]
+mod index;
+#[cfg(feature = "std")]
+mod inspect;
+#[cfg(feature = "std")]
+mod iter;
+#[cfg(feature = "std")]
+mod oneshot;
+#[cfg(feature = "std")]
+mod rewrite;
+#[cfg(feature = "std")]
+mod sectors;
+#[cfg(feature = "std")]
+mod segment;
+#[cfg(feature = "std")]
+mod slice;
+#[cfg(feature = "std")]
+mod state;
+#[cfg(feature = "std")]
+mod stats;
+#[cfg(feature = "std")]
+mod verify;
+
+#[cfg(kani)]
+mod proofs;
+
+#[cfg(all(feature = "std", any(unix, windows)))]
+mod extent;
+#[cfg(all(feature = "std", any(unix, windows)))]
+mod parallel;
+
+pub use format::{
+    chunk_type, header_len, is_plain_header, is_three_byte_header, is_two_byte_header, MAX_OFFSET,
+    MAX_PLAIN_CHUNK_LEN, MAX_RUN_CHUNK_LEN, MAX_TWO_BYTE_OFFSET, MAX_TWO_BYTE_RUN_LEN,
+    MIN_RUN_CHUNK_LEN, MIN_THREE_BYTE_RUN_LEN, WINDOW_SIZE,
+};
+pub use window::Window;
+
+#[cfg(feature = "std")]
+pub use block::{detect, AnyBlockDecoder, BlockKind, Sniffed, DETECT_LEN};
+#[cfg(feature = "std")]
+pub use checksum::{crc32, ChecksumDecoder, ChecksumMismatch, Crc32};
+#[cfg(feature = "std")]
+pub use decoder_writer::AdcDecoderWriter;
+#[cfg(feature = "std")]
+pub use encoder::{AdcEncoder, AdcEncoderReader, CompressionLevel};
+#[cfg(feature = "std")]
+pub use error::{AdcError, ChunkContext};
+#[cfg(feature = "std")]
+pub use hexdump::{annotated_hexdump, hexdump};
+#[cfg(feature = "std")]
+pub use index::{Checkpoint, StreamIndex};
+#[cfg(feature = "std")]
+pub use inspect::{inspect, ChunkInfo, ChunkIter, StreamMap};
+#[cfg(feature = "std")]
+pub use iter::IterReader;
+#[cfg(feature = "rayon")]
+pub use oneshot::decompress_blocks_parallel;
+#[cfg(feature = "std")]
+pub use oneshot::{compress, compress_to_vec, decompress, decompress_to_vec, recompress};
+#[cfg(feature = "std")]
+pub use rewrite::{rewrite, ChunkWriter, InputChunk};
+#[cfg(feature = "std")]
+pub use sectors::{Sector, Sectors};
+#[cfg(feature = "std")]
+pub use segment::{resegment, Segment};
+#[cfg(feature = "std")]
+pub use slice::AdcSliceDecoder;
+#[cfg(feature = "std")]
+pub use state::DecoderState;
+#[cfg(feature = "std")]
+pub use stats::DecodeStats;
+#[cfg(feature = "std")]
+pub use verify::{LengthMismatch, VerifyingDecoder, SECTOR_SIZE};
+
+#[cfg(any(feature = "async", feature = "futures"))]
+pub use async_io::{AsyncAdcDecoder, AsyncAdcDecoderWriter};
+#[cfg(all(feature = "std", any(unix, windows)))]
+pub use extent::{decode_extent, ExtentDecoder, FileExtent};
+#[cfg(all(feature = "std", any(unix, windows)))]
+pub use parallel::{Block, ParallelExtractor};
+
+/// Encoding of a chunk in an ADC stream.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AdcChunkType {
+    /// Literal bytes following a one byte header
     Plain,
+    /// Run from the window with a two byte header
     TwoByte,
+    /// Run from the window with a three byte header
     ThreeByte,
 }
 
-#[derive(PartialEq, Debug)]
-struct AdcChunk {
+impl fmt::Display for AdcChunkType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            AdcChunkType::Plain => "plain",
+            AdcChunkType::TwoByte => "two_byte",
+            AdcChunkType::ThreeByte => "three_byte",
+        })
+    }
+}
+
+/// Header of a single chunk, the literal bytes of plain chunks follow it in the stream.
+///
+/// ```
+/// use adc::{AdcChunk, AdcChunkType};
+///
+/// let (chunk, len) = AdcChunk::parse(&[0x40, 0x01, 0x00, 0xff]).unwrap();
+/// assert_eq!(len, 3);
+/// assert_eq!(chunk.kind(), AdcChunkType::ThreeByte);
+/// assert_eq!((chunk.offset(), chunk.decompressed_len()), (Some(0x100), 4));
+///
+/// let mut header = Vec::new();
+/// AdcChunk::run(0x100, 4).encode(&mut header);
+/// assert_eq!(header, [0x05, 0x00]);
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct AdcChunk {
     r#type: AdcChunkType,
     size: u8,
     offset: u16,
 }
 
-/// Window into the decompressed output.
-///
-/// Used to get output bytes for the run-length chunks.
-/// Implemented as a non-growable ring buffer.
-struct Window(VecDeque<u8>);
+impl AdcChunk {
+    /// Create the header of a plain chunk holding `len` literal bytes
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `len` is 1 to 128.
+    pub fn plain(len: usize) -> AdcChunk {
+        assert!(
+            (1..=MAX_PLAIN_CHUNK_LEN).contains(&len),
+            "plain chunks hold 1 to 128 bytes"
+        );
+        AdcChunk {
+            r#type: AdcChunkType::Plain,
+            size: len as u8,
+            offset: 0,
+        }
+    }
+
+    /// Create the header of a run copying `len` bytes from `offset + 1` bytes back
+    ///
+    /// Uses the two byte encoding where possible, for runs of up to 18 bytes at offsets up to
+    /// 0x3ff, and the three byte one otherwise.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `len` is 3 to 67, and at least 4 for offsets above 0x3ff.
+    pub fn run(offset: u16, len: usize) -> AdcChunk {
+        let r#type = if offset <= MAX_TWO_BYTE_OFFSET && len <= MAX_TWO_BYTE_RUN_LEN {
+            AdcChunkType::TwoByte
+        } else {
+            AdcChunkType::ThreeByte
+        };
+        let min_len = match r#type {
+            AdcChunkType::TwoByte => MIN_RUN_CHUNK_LEN,
+            _ => MIN_THREE_BYTE_RUN_LEN,
+        };
+        assert!(
+            (min_len..=MAX_RUN_CHUNK_LEN).contains(&len),
+            "runs are 3 to 67 bytes, at least 4 for offsets above 0x3ff"
+        );
+        AdcChunk {
+            r#type,
+            size: len as u8,
+            offset,
+        }
+    }
+
+    /// Decode the header at the start of `bytes`, returns the chunk and the length of the
+    /// header
+    ///
+    /// Any first byte starts a valid header, `None` means that `bytes` ends within it.
+    pub fn parse(bytes: &[u8]) -> Option<(AdcChunk, usize)> {
+        let len = header_len(*bytes.first()?);
+        let mut header = RawHeader::default();
+        header.bytes[..len].copy_from_slice(bytes.get(..len)?);
+        header.len = len as u8;
+        Some((header.parse()?, len))
+    }
+
+    /// Append the header to `output`
+    #[cfg(feature = "alloc")]
+    pub fn encode(&self, output: &mut alloc::vec::Vec<u8>) {
+        let len = self.size;
+        let [hi, lo] = self.offset.to_be_bytes();
+        match self.r#type {
+            AdcChunkType::Plain => output.push(0x80 | (len - 1)),
+            AdcChunkType::TwoByte => output.extend_from_slice(&[((len - 3) << 2) | hi, lo]),
+            AdcChunkType::ThreeByte => output.extend_from_slice(&[0x40 | (len - 4), hi, lo]),
+        }
+    }
+
+    /// Get the encoding of the chunk
+    pub fn kind(&self) -> AdcChunkType {
+        self.r#type
+    }
+
+    /// Get the number of bytes the chunk decompresses to
+    pub fn decompressed_len(&self) -> usize {
+        usize::from(self.size)
+    }
 
-impl Window {
-    // The windows needs to fit `max offset` bytes.
-    const SIZE: usize = u16::MAX as usize + 1;
+    /// Get the window offset of a run, it starts `offset + 1` bytes back
+    pub fn offset(&self) -> Option<u16> {
+        match self.r#type {
+            AdcChunkType::Plain => None,
+            _ => Some(self.offset),
+        }
+    }
 
-    fn new() -> Self {
-        Self(VecDeque::with_capacity(Self::SIZE))
+    /// Get the length of the encoded header
+    pub fn header_len(&self) -> usize {
+        match self.r#type {
+            AdcChunkType::Plain => 1,
+            AdcChunkType::TwoByte => 2,
+            AdcChunkType::ThreeByte => 3,
+        }
     }
+}
+
+/// Header bytes of a chunk as read from the input.
+#[derive(Clone, Copy, Default, Debug)]
+struct RawHeader {
+    bytes: [u8; 3],
+    len: u8,
+}
 
-    fn extend(&mut self, bytes: &[u8]) {
-        // remove from the back to ensure we have enough room
-        let max_size = Self::SIZE - bytes.len();
-        self.0.truncate(max_size);
+impl RawHeader {
+    /// Get the bytes read so far
+    fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..usize::from(self.len)]
+    }
 
-        // push new bytes to the front
-        for &byte in bytes {
-            self.0.push_front(byte);
+    /// Decode the header, `None` if it is incomplete
+    fn parse(&self) -> Option<AdcChunk> {
+        let byte = *self.as_bytes().first()?;
+        if self.as_bytes().len() < header_len(byte) {
+            return None;
         }
+
+        let r#type = chunk_type(byte);
+        let chunk = match r#type {
+            AdcChunkType::Plain => AdcChunk {
+                r#type,
+                size: (byte & 0x7f) + 1,
+                offset: 0,
+            },
+            AdcChunkType::TwoByte => AdcChunk {
+                r#type,
+                size: ((byte & 0x3f) >> 2) + 3,
+                offset: ((u16::from(byte) & 0x3) << 8) + u16::from(self.bytes[1]),
+            },
+            AdcChunkType::ThreeByte => AdcChunk {
+                r#type,
+                size: (byte & 0x3f) + 4,
+                offset: u16::from_be_bytes([self.bytes[1], self.bytes[2]]),
+            },
+        };
+        Some(chunk)
     }
+}
 
-    fn get(&self, idx: u16) -> Option<u8> {
-        self.0.get(idx as usize).copied()
+/// Read the next chunk header from `input`.
+///
+/// The header is empty at the end of the stream and incomplete if the stream ends within it.
+#[cfg(feature = "std")]
+fn read_header<R: Read>(input: &mut R) -> io::Result<RawHeader> {
+    let mut header = RawHeader::default();
+    fill_header(input, &mut header)?;
+    Ok(header)
+}
+
+/// Read the rest of a partially read chunk header from `input`.
+///
+/// The bytes read before an error stay in `header`, so a later call can pick up where this
+/// one failed, e.g. after `WouldBlock`.
+#[cfg(feature = "std")]
+fn fill_header<R: Read>(input: &mut R, header: &mut RawHeader) -> io::Result<()> {
+    while header.parse().is_none() {
+        let len = header
+            .as_bytes()
+            .first()
+            .map_or(1, |&byte| header_len(byte));
+        let filled = usize::from(header.len);
+        match input.read(&mut header.bytes[filled..len]) {
+            Ok(0) => break,
+            Ok(n) => header.len += n as u8,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
     }
+    Ok(())
 }
 
+/// Position of the chunk being decoded, for the context of errors.
+///
+/// Errors only carry a context with `std`, without it the position is tracked but not read.
+#[derive(Clone, Copy, Default, Debug)]
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+struct ChunkStart {
+    index: u64,
+    compressed_offset: u64,
+    decompressed_offset: u64,
+    header: RawHeader,
+}
+
+#[cfg(feature = "std")]
+impl ChunkStart {
+    fn context(&self) -> ChunkContext {
+        ChunkContext::new(
+            self.index,
+            self.compressed_offset,
+            self.decompressed_offset,
+            self.header.as_bytes(),
+        )
+    }
+}
+
+/// Kind of damage skipped over in recovery mode.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum CorruptionKind {
+    /// A run referenced data before the start of the output
+    InvalidOffset,
+    /// The stream ended in the middle of a chunk
+    Truncated,
+}
+
+impl fmt::Display for CorruptionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            CorruptionKind::InvalidOffset => "invalid chunk offset",
+            CorruptionKind::Truncated => "truncated stream",
+        })
+    }
+}
+
+/// A damaged region of the output, filled with zeros in recovery mode.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub struct Corruption {
+    /// Kind of damage
+    pub kind: CorruptionKind,
+    /// Offset of the zero-filled region in the decompressed output
+    pub decompressed_offset: u64,
+    /// Length of the zero-filled region
+    pub len: u64,
+}
+
+/// Size of the buffer behind the `BufRead` implementation of the decoder.
+#[cfg(feature = "std")]
+const BUF_SIZE: usize = 8 * 1024;
+
+/// Most bytes `read_to_end` reserves ahead of decoding, sizes from headers can't be trusted.
+#[cfg(feature = "std")]
+const MAX_RESERVE: u64 = 64 * 1024;
+
 /// Main type for decompressing ADC data.
+///
+/// The decoder can be cloned if the input can, which forks the decoding position including
+/// the window of previous output. For in-memory input like `&[u8]` this is cheap apart from
+/// copying the window.
+///
+/// Errors of the input leave the decoder in a consistent state, including within a chunk
+/// header, so reading can be retried after `WouldBlock` on a non-blocking input.
+///
+/// ADC streams have no header or end marker and the runs of a stream never reach back
+/// before its start, so back-to-back streams in one input decode like a single stream to
+/// the concatenation of their data. No special mode is needed for files of several
+/// members, but the boundaries between them can't be recovered from the data alone.
+///
+/// The decoder has its own chunk loop instead of driving [`stream::decompress`] like
+/// `AsyncAdcDecoder`: it reads exactly the bytes of each chunk from the input, so
+/// [`total_in`](AdcDecoder::total_in) and [`into_inner`](AdcDecoder::into_inner) stay exact
+/// for streams embedded in other data, and plain data goes straight into the output buffer.
+/// Both share the header parsing and the window, the `differential_stream` fuzz target
+/// checks that they agree.
+///
+/// The window is always the full [`WINDOW_SIZE`] that any stream may refer back into.
+/// Decoding with a smaller window to save memory is only possible with
+/// [`stream::AdcState::sized`].
+#[cfg(feature = "std")]
+#[derive(Clone)]
 pub struct AdcDecoder<R> {
     input: R,
     current_chunk: Option<AdcChunk>,
+    /// Header of the next chunk, partially read if the input failed within it
+    header: RawHeader,
     window: Window,
+    total_in: u64,
+    total_out: u64,
+    chunks: u64,
+    start: ChunkStart,
+    recover: bool,
+    corruptions: Vec<Corruption>,
+    stats: DecodeStats,
+    progress: Option<ProgressHook>,
+    max_output: Option<u64>,
+    expected_size: Option<u64>,
+    /// Decompressed data for `BufRead`, allocated on first use
+    buf: Vec<u8>,
+    pos: usize,
+    filled: usize,
+    pending_error: PendingError,
 }
 
+/// Error held back by the decoder to return the data decoded before it first.
+///
+/// Clones get an error of the same kind and message, as `io::Error` can not be cloned.
+#[cfg(feature = "std")]
+#[derive(Default)]
+struct PendingError(Option<io::Error>);
+
+#[cfg(feature = "std")]
+impl Clone for PendingError {
+    fn clone(&self) -> PendingError {
+        PendingError(
+            self.0
+                .as_ref()
+                .map(|err| io::Error::new(err.kind(), err.to_string())),
+        )
+    }
+}
+
+/// Callback of [`AdcDecoder::on_progress`], shared between clones of the decoder.
+#[cfg(feature = "std")]
+#[derive(Clone)]
+struct ProgressHook {
+    callback: std::sync::Arc<dyn Fn(u64, u64) + Send + Sync>,
+    interval: u64,
+    /// Output position of the last call
+    reported: u64,
+}
+
+#[cfg(feature = "std")]
 impl<R: Read> AdcDecoder<R> {
     /// Create a new decoder instance from a readable input
     pub fn new(input: R) -> AdcDecoder<R> {
         AdcDecoder {
             input,
             current_chunk: None,
+            header: RawHeader::default(),
+            // reserved on the first read with fallible allocation
+            #[cfg(feature = "fallible-alloc")]
+            window: Window::default(),
+            #[cfg(not(feature = "fallible-alloc"))]
             window: Window::new(),
+            total_in: 0,
+            total_out: 0,
+            chunks: 0,
+            start: ChunkStart::default(),
+            recover: false,
+            corruptions: Vec::new(),
+            stats: DecodeStats::default(),
+            progress: None,
+            max_output: None,
+            expected_size: None,
+            buf: Vec::new(),
+            pos: 0,
+            filled: 0,
+            pending_error: PendingError::default(),
         }
     }
 
-    fn next_nonempty_chunk(&mut self) -> io::Result<Option<AdcChunk>> {
-        while let Some(chunk) = self.next_chunk()? {
-            if chunk.size > 0 {
-                return Ok(Some(chunk));
+    /// Create a decoder that recovers from corrupt input on a best-effort basis
+    ///
+    /// Instead of failing, runs with invalid offsets and the missing part of a truncated
+    /// chunk are filled with zeros. The damaged regions are available from
+    /// [`corruptions`](AdcDecoder::corruptions).
+    pub fn with_recovery(input: R) -> AdcDecoder<R> {
+        AdcDecoder {
+            recover: true,
+            ..AdcDecoder::new(input)
+        }
+    }
+
+    /// Create a decoder for a stream known to decompress to exactly `size` bytes
+    ///
+    /// Fails with [`AdcError::TooLong`] instead of decompressing more and with
+    /// [`AdcError::TooShort`] if the stream ends early.
+    ///
+    /// ```
+    /// use adc::AdcDecoder;
+    /// use std::io::Read;
+    ///
+    /// let input: &[u8] = &[0x81, 0xab, 0xcd, 0x00, 0x01];
+    /// let d = AdcDecoder::with_expected_size(input, 6);
+    /// assert_eq!(d.size_hint(), Some(6));
+    /// assert!(d.take(10).read_to_end(&mut Vec::new()).is_err());
+    /// ```
+    pub fn with_expected_size(input: R, size: u64) -> AdcDecoder<R> {
+        AdcDecoder {
+            expected_size: Some(size),
+            ..AdcDecoder::new(input)
+        }
+    }
+
+    /// Get the number of bytes left to decompress if the size of the stream is known
+    pub fn size_hint(&self) -> Option<u64> {
+        self.expected_size
+            .map(|size| size.saturating_sub(self.total_out))
+    }
+
+    /// Fail with [`AdcError::OutputLimit`] instead of decompressing more than `max` bytes
+    ///
+    /// Guards against small inputs expanding to huge outputs. Streams of exactly `max` bytes
+    /// decode normally.
+    ///
+    /// ```
+    /// use adc::AdcDecoder;
+    /// use std::io::Read;
+    ///
+    /// let input: &[u8] = &[0x81, 0xab, 0xcd, 0x00, 0x01];
+    /// let mut d = AdcDecoder::new(input).max_output_size(4);
+    /// assert!(d.read_to_end(&mut Vec::new()).is_err());
+    /// ```
+    pub fn max_output_size(mut self, max: u64) -> AdcDecoder<R> {
+        self.max_output = Some(max);
+        self
+    }
+
+    /// Call `callback` with [`total_in`](AdcDecoder::total_in) and the number of bytes
+    /// decompressed so far whenever another `interval` bytes were decompressed
+    ///
+    /// The callback runs within the read producing the data, at most once per read, and a
+    /// last time at the end of the stream. Clones of the decoder share the callback.
+    ///
+    /// ```
+    /// use adc::AdcDecoder;
+    /// use std::{io::Read, sync::mpsc};
+    ///
+    /// let input: &[u8] = &[0x81, 0xab, 0xcd, 0x00, 0x01];
+    /// let (tx, rx) = mpsc::channel();
+    /// let mut d = AdcDecoder::new(input).on_progress(4, move |input, output| {
+    ///     tx.send((input, output)).unwrap();
+    /// });
+    /// d.read_exact(&mut [0; 2]).unwrap();
+    /// d.read_to_end(&mut Vec::new()).unwrap();
+    /// drop(d);
+    /// assert_eq!(rx.iter().collect::<Vec<_>>(), [(5, 5)]);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    pub fn on_progress<F>(mut self, interval: u64, callback: F) -> AdcDecoder<R>
+    where
+        F: Fn(u64, u64) + Send + Sync + 'static,
+    {
+        assert!(interval > 0, "progress interval must not be zero");
+        self.progress = Some(ProgressHook {
+            callback: std::sync::Arc::new(callback),
+            interval,
+            reported: 0,
+        });
+        self
+    }
+
+    /// Preload the window with `dictionary`, as if it was output before the start of the
+    /// stream
+    ///
+    /// Runs of the stream can then refer back into the last [`WINDOW_SIZE`] bytes of the
+    /// dictionary, for container formats continuing a stream across blocks. Replaces any
+    /// dictionary set before.
+    ///
+    /// ```
+    /// use adc::AdcDecoder;
+    /// use std::io::Read;
+    ///
+    /// let previous_block = b"abc";
+    /// let mut d = AdcDecoder::new(&[0x04, 0x02][..]);
+    /// d.set_dictionary(previous_block);
+    /// let mut data = Vec::new();
+    /// d.read_to_end(&mut data).unwrap();
+    /// assert_eq!(data, b"abca");
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if decoding already started.
+    pub fn set_dictionary(&mut self, dictionary: &[u8]) {
+        assert!(
+            self.total_in == 0 && self.total_out == 0,
+            "the dictionary has to be set before decoding"
+        );
+        self.window.clear();
+        self.window.extend(dictionary);
+    }
+
+    /// Get the damaged regions found so far in recovery mode
+    pub fn corruptions(&self) -> &[Corruption] {
+        &self.corruptions
+    }
+
+    /// Get the chunk counts of the stream so far, see [`DecodeStats`]
+    pub fn stats(&self) -> DecodeStats {
+        self.stats
+    }
+
+    /// Check that the stream was read completely and ended on a chunk boundary
+    ///
+    /// Fails with [`AdcError::UnreadData`] if decompressed data is left, including the rest
+    /// of a partially read chunk, and with [`AdcError::TruncatedChunk`] if the input ends
+    /// within a chunk header.
+    pub fn try_finish(&mut self) -> io::Result<()> {
+        if self.read(&mut [0])? > 0 {
+            return Err(AdcError::UnreadData(self.start.context()).into());
+        }
+        Ok(())
+    }
+
+    /// Check that the input has no bytes left after the end of the stream
+    ///
+    /// For streams embedded in larger data that should end exactly where the input does.
+    /// Call it once all data was read, it fails with [`AdcError::UnreadData`] within a chunk
+    /// and with [`AdcError::TrailingData`] if the input continues. Either way the first
+    /// byte following the stream is consumed.
+    pub fn ensure_exhausted(&mut self) -> io::Result<()> {
+        if self.current_chunk.is_some() || self.pos < self.filled {
+            return Err(AdcError::UnreadData(self.start.context()).into());
+        }
+        if read_full(&mut self.input, &mut [0])? > 0 {
+            return Err(AdcError::TrailingData(self.total_in).into());
+        }
+        Ok(())
+    }
+
+    /// Skip over the next `n` bytes of decompressed data
+    ///
+    /// The skipped data still goes through the window but is never copied out, returns the
+    /// number of bytes skipped, less than `n` only at the end of the stream.
+    ///
+    /// ```
+    /// use adc::AdcDecoder;
+    /// use std::io::Read;
+    ///
+    /// let input: &[u8] = &[0x83, 0xfe, 0xed, 0xfa, 0xce, 0x00, 0x00];
+    /// let mut d = AdcDecoder::new(input);
+    /// assert_eq!(d.skip(5).unwrap(), 5);
+    /// let mut data = Vec::new();
+    /// d.read_to_end(&mut data).unwrap();
+    /// assert_eq!(data, [0xce, 0xce]);
+    /// ```
+    pub fn skip(&mut self, n: u64) -> io::Result<u64> {
+        let buffered = cmp::min((self.filled - self.pos) as u64, n);
+        self.pos += buffered as usize;
+
+        let mut left = n - buffered;
+        let mut scratch = [0; 4096];
+        while left > 0 {
+            let len = cmp::min(left, scratch.len() as u64) as usize;
+            match self.decode(&mut scratch[..len])? {
+                0 => break,
+                read => left -= read as u64,
             }
         }
-        Ok(None)
+        Ok(n - left)
     }
 
-    fn next_chunk(&mut self) -> io::Result<Option<AdcChunk>> {
-        let byte = match self.input.read_u8() {
-            Ok(val) => val,
-            Err(_) => return Ok(None),
-        };
+    /// Get the number of compressed bytes consumed so far
+    ///
+    /// The decoder reads no further than the chunks it decoded, once the stream ended this
+    /// is its length in the input.
+    pub fn total_in(&self) -> u64 {
+        self.total_in
+    }
 
-        let chunk_type = if (byte & 0x80) != 0 {
-            AdcChunkType::Plain
-        } else if (byte & 0x40) != 0 {
-            AdcChunkType::ThreeByte
-        } else {
-            AdcChunkType::TwoByte
-        };
+    /// Get the number of decompressed bytes read so far
+    ///
+    /// Data decompressed into the buffer of [`fill_buf`](BufRead::fill_buf) only counts once
+    /// it is consumed, so this is the position in the decompressed data.
+    pub fn total_out(&self) -> u64 {
+        self.total_out - (self.filled - self.pos) as u64
+    }
 
-        let chunk = match chunk_type {
-            AdcChunkType::Plain => AdcChunk {
-                r#type: chunk_type,
-                size: (byte & 0x7f) + 1,
-                offset: 0,
-            },
-            AdcChunkType::TwoByte => {
-                let byte2 = self.input.read_u8()?;
-                AdcChunk {
-                    r#type: chunk_type,
-                    size: ((byte & 0x3f) >> 2) + 3,
-                    offset: ((u16::from(byte) & 0x3) << 8) + u16::from(byte2),
-                }
+    /// Get a reference to the input
+    pub fn get_ref(&self) -> &R {
+        &self.input
+    }
+
+    /// Get a mutable reference to the input
+    ///
+    /// Reading from it or seeking it makes the decoder continue with other data than the
+    /// rest of the stream.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.input
+    }
+
+    /// Return the input, positioned right after the last chunk read
+    ///
+    /// Data decompressed into the buffer of [`fill_buf`](BufRead::fill_buf) but not
+    /// consumed yet is lost.
+    pub fn into_inner(self) -> R {
+        self.input
+    }
+
+    /// Check the end of the stream like [`try_finish`](AdcDecoder::try_finish) and return
+    /// the input
+    ///
+    /// ```
+    /// use adc::AdcDecoder;
+    /// use std::io::Read;
+    ///
+    /// let input: &[u8] = &[0x81, 0xab, 0xcd, 0x00, 0x01];
+    /// let mut d = AdcDecoder::new(input);
+    /// let mut data = [0; 4];
+    /// d.read_exact(&mut data).unwrap();
+    /// assert!(d.finish().is_err());
+    /// ```
+    pub fn finish(mut self) -> io::Result<R> {
+        self.try_finish()?;
+        Ok(self.input)
+    }
+
+    /// Start decoding a new stream from `input` and return the previous input
+    ///
+    /// Everything about the previous stream is dropped, including buffered data and the
+    /// damaged regions found in recovery mode, but the window and buffer allocations are
+    /// reused. Recovery mode and the size limits stay set.
+    ///
+    /// ```
+    /// use adc::AdcDecoder;
+    /// use std::io::Read;
+    ///
+    /// let mut d = AdcDecoder::new(&[0x81, 0xab, 0xcd][..]);
+    /// let mut data = Vec::new();
+    /// d.read_to_end(&mut data).unwrap();
+    /// d.reset(&[0x80, 0xef, 0x00, 0x00][..]);
+    /// d.read_to_end(&mut data).unwrap();
+    /// assert_eq!(data, [0xab, 0xcd, 0xef, 0xef, 0xef, 0xef]);
+    /// ```
+    pub fn reset(&mut self, input: R) -> R {
+        self.reset_with_dictionary(input, &[])
+    }
+
+    /// Like [`reset`](AdcDecoder::reset), but runs of the new stream can refer back into
+    /// `dictionary` as if it was output before the start of the stream
+    ///
+    /// Only the last [`WINDOW_SIZE`] bytes of the dictionary can be referred to.
+    pub fn reset_with_dictionary(&mut self, input: R, dictionary: &[u8]) -> R {
+        trace_event!(debug, dictionary_len = dictionary.len(), "decoder reset");
+        self.current_chunk = None;
+        self.header = RawHeader::default();
+        self.total_in = 0;
+        self.total_out = 0;
+        self.set_dictionary(dictionary);
+        self.chunks = 0;
+        self.start = ChunkStart::default();
+        self.corruptions.clear();
+        self.stats = DecodeStats::default();
+        if let Some(ref mut hook) = self.progress {
+            hook.reported = 0;
+        }
+        self.pos = 0;
+        self.filled = 0;
+        self.pending_error = PendingError::default();
+        std::mem::replace(&mut self.input, input)
+    }
+
+    fn next_nonempty_chunk(&mut self) -> io::Result<Option<AdcChunk>> {
+        loop {
+            fill_header(&mut self.input, &mut self.header)?;
+            let header = std::mem::take(&mut self.header);
+            if header.len == 0 {
+                return Ok(None);
             }
-            AdcChunkType::ThreeByte => {
-                let offset = self.input.read_u16::<BE>()?;
-                AdcChunk {
-                    r#type: chunk_type,
-                    size: (byte & 0x3f) + 4,
-                    offset,
+            self.start = ChunkStart {
+                index: self.chunks,
+                compressed_offset: self.total_in,
+                decompressed_offset: self.total_out,
+                header,
+            };
+            self.chunks += 1;
+            self.total_in += u64::from(header.len);
+
+            match header.parse() {
+                Some(chunk) if chunk.size == 0 => {}
+                Some(chunk) => {
+                    trace_event!(
+                        trace,
+                        index = self.start.index,
+                        compressed_offset = self.start.compressed_offset,
+                        kind = %chunk.r#type,
+                        len = chunk.size,
+                        offset = chunk.offset,
+                        "chunk"
+                    );
+                    let len = u64::from(chunk.size);
+                    let data_len = if chunk.r#type == AdcChunkType::Plain {
+                        len
+                    } else {
+                        0
+                    };
+                    let compressed_len = u64::from(header.len) + data_len;
+                    self.stats.record(chunk.r#type, compressed_len, len);
+                    return Ok(Some(chunk));
                 }
+                None if self.recover => {
+                    report(
+                        &mut self.corruptions,
+                        CorruptionKind::Truncated,
+                        self.total_out,
+                        0,
+                    )?;
+                    return Ok(None);
+                }
+                None => return Err(AdcError::TruncatedChunk(self.start.context()).into()),
             }
-        };
-
-        Ok(Some(chunk))
+        }
     }
 
     fn read_from_chunk(&mut self, buf: &mut [u8]) -> io::Result<usize> {
@@ -143,27 +928,53 @@ impl<R: Read> AdcDecoder<R> {
             None => return Ok(0),
         };
 
-        let read_len = cmp::min(chunk.size as usize, buf.len());
-        let buf = &mut buf[..read_len];
+        let mut read_len = cmp::min(chunk.size as usize, buf.len());
 
         if chunk.r#type == AdcChunkType::Plain {
-            self.input.read_exact(buf)?;
-            self.window.extend(buf);
+            // a single read, so no data is lost if the input fails after a short read
+            let n = loop {
+                match self.input.read(&mut buf[..read_len]) {
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    result => break result?,
+                }
+            };
+            self.total_in += n as u64;
+            if n == 0 && read_len > 0 {
+                if !self.recover {
+                    return Err(AdcError::TruncatedChunk(self.start.context()).into());
+                }
+                buf[..read_len].fill(0);
+                let len = read_len as u64;
+                report(
+                    &mut self.corruptions,
+                    CorruptionKind::Truncated,
+                    self.total_out,
+                    len,
+                )?;
+            } else {
+                read_len = n;
+            }
+            self.window.extend(&buf[..read_len]);
         } else {
-            // read run of bytes from the output window
-            for elem in buf.iter_mut() {
-                let byte = match self.window.get(chunk.offset) {
-                    Some(b) => b,
-                    None => {
-                        return Err(io::Error::new(
-                            io::ErrorKind::InvalidData,
-                            "invalid chunk offset",
-                        ))
-                    }
-                };
-
-                *elem = byte;
-                self.window.extend(&[byte]);
+            // read run of bytes from the output window, the window only grows, if the
+            // first byte of the run is there all of it is
+            let buf = &mut buf[..read_len];
+            if !self.window.copy_run(chunk.offset, buf) {
+                if !self.recover {
+                    return Err(AdcError::InvalidOffset(self.start.context()).into());
+                }
+                // the whole rest of the run is damaged, pointing it at the zeros written
+                // now fills it with zeros
+                let len = u64::from(chunk.size);
+                report(
+                    &mut self.corruptions,
+                    CorruptionKind::InvalidOffset,
+                    self.total_out,
+                    len,
+                )?;
+                buf.fill(0);
+                self.window.extend(buf);
+                chunk.offset = 0;
             }
         }
 
@@ -171,24 +982,358 @@ impl<R: Read> AdcDecoder<R> {
         if chunk.size == 0 {
             self.current_chunk = None;
         }
+        self.total_out += read_len as u64;
 
         Ok(read_len)
     }
-}
 
-impl<R: Read> Read for AdcDecoder<R> {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    /// Decompress the next bytes of the stream into `buf`, bypassing the buffer
+    ///
+    /// Decodes as many chunks as fit instead of stopping at the end of a chunk. An error after
+    /// some data was decoded is held back until the next call, except for `WouldBlock` and
+    /// `Interrupted`, which only ask to retry.
+    fn decode(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(err) = self.pending_error.0.take() {
+            return Err(err);
+        }
+        let mut len = 0;
+        loop {
+            match self.decode_chunk(&mut buf[len..]) {
+                Ok(0) => break,
+                Ok(n) => len += n,
+                Err(err) if len == 0 => return Err(err),
+                Err(err) => {
+                    if !matches!(
+                        err.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted
+                    ) {
+                        self.pending_error.0 = Some(err);
+                    }
+                    break;
+                }
+            }
+            if len == buf.len() {
+                break;
+            }
+        }
+
+        if let Some(ref mut hook) = self.progress {
+            let end = len == 0 && !buf.is_empty() && self.pending_error.0.is_none();
+            let out = self.total_out;
+            if out >= hook.reported + hook.interval || (end && out > hook.reported) {
+                hook.reported = out;
+                (hook.callback)(self.total_in, out);
+            }
+        }
+        Ok(len)
+    }
+
+    /// Decompress the next bytes of the current or next chunk into `buf`.
+    fn decode_chunk(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        #[cfg(feature = "fallible-alloc")]
+        self.window.try_reserve().map_err(out_of_memory)?;
+
         if self.current_chunk.is_none() {
             self.current_chunk = self.next_nonempty_chunk()?;
+            match self.expected_size {
+                Some(expected) if self.current_chunk.is_none() && self.total_out < expected => {
+                    let actual = self.total_out;
+                    return Err(AdcError::TooShort { expected, actual }.into());
+                }
+                _ => {}
+            }
         }
 
+        let limit = match (self.max_output, self.expected_size) {
+            (Some(max), Some(expected)) => Some(cmp::min(max, expected)),
+            (max, expected) => max.or(expected),
+        };
+        let buf = match limit {
+            Some(limit) if self.current_chunk.is_some() && !buf.is_empty() => {
+                if self.total_out >= limit {
+                    return Err(match self.expected_size {
+                        Some(expected) if expected == limit => AdcError::TooLong { expected },
+                        _ => AdcError::OutputLimit(limit),
+                    }
+                    .into());
+                }
+                let len = cmp::min(buf.len() as u64, limit - self.total_out) as usize;
+                &mut buf[..len]
+            }
+            _ => buf,
+        };
         self.read_from_chunk(buf)
     }
 }
 
-#[cfg(test)]
+/// Record `len` zero-filled bytes at output position `pos`, merging with the last region.
+#[cfg(feature = "std")]
+fn report(
+    corruptions: &mut Vec<Corruption>,
+    kind: CorruptionKind,
+    pos: u64,
+    len: u64,
+) -> io::Result<()> {
+    trace_event!(warn, %kind, decompressed_offset = pos, len, "filled damaged data with zeros");
+    if let Some(last) = corruptions.last_mut() {
+        if last.kind == kind && last.decompressed_offset + last.len == pos {
+            last.len += len;
+            return Ok(());
+        }
+    }
+    #[cfg(feature = "fallible-alloc")]
+    corruptions.try_reserve(1).map_err(out_of_memory)?;
+    corruptions.push(Corruption {
+        kind,
+        decompressed_offset: pos,
+        len,
+    });
+    Ok(())
+}
+
+/// Error for an allocation that failed with the `fallible-alloc` feature.
+#[cfg(all(feature = "std", feature = "fallible-alloc"))]
+fn out_of_memory(err: std::collections::TryReserveError) -> io::Error {
+    io::Error::new(io::ErrorKind::OutOfMemory, err)
+}
+
+/// Read into `buf` until it is full or the input ends, returns the number of bytes read.
+#[cfg(feature = "std")]
+fn read_full<R: Read>(input: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    read_full_counted(input, buf, &mut len)?;
+    Ok(len)
+}
+
+/// [`read_full`] counting the bytes read in `len`, which includes those read before an error.
+#[cfg(feature = "std")]
+fn read_full_counted<R: Read>(input: &mut R, buf: &mut [u8], len: &mut usize) -> io::Result<()> {
+    while *len < buf.len() {
+        match input.read(&mut buf[*len..]) {
+            Ok(0) => break,
+            Ok(n) => *len += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+impl<R: Read> Read for AdcDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.filled {
+            return self.decode(buf);
+        }
+        let len = cmp::min(buf.len(), self.filled - self.pos);
+        buf[..len].copy_from_slice(&self.buf[self.pos..][..len]);
+        self.pos += len;
+        Ok(len)
+    }
+
+    /// Decodes straight into the spare room of `data` in large pieces. The rest of the
+    /// [`size_hint`](AdcDecoder::size_hint) is reserved up front, up to 64 KiB, and the
+    /// vector grows as data keeps coming.
+    fn read_to_end(&mut self, data: &mut Vec<u8>) -> io::Result<usize> {
+        let start = data.len();
+        data.extend_from_slice(&self.buf[self.pos..self.filled]);
+        self.pos = self.filled;
+
+        let hint = match (self.size_hint(), self.max_output) {
+            (Some(left), Some(max)) => cmp::min(left, max.saturating_sub(self.total_out)),
+            (hint, _) => hint.unwrap_or(0),
+        };
+        let mut len = data.len();
+        let mut probe = [0; 64];
+        let reserve = cmp::min(hint, MAX_RESERVE) as usize;
+        let result = oneshot::reserve(data, reserve).and_then(|()| loop {
+            data.resize(data.capacity(), 0);
+            // a full vector only grows once a small probe shows that more data follows
+            let full = len == data.len();
+            let buf = if full {
+                &mut probe[..]
+            } else {
+                &mut data[len..]
+            };
+            let n = match self.decode(buf) {
+                Ok(0) => return Ok(len - start),
+                Ok(n) => n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if full {
+                oneshot::reserve(data, len - start)?;
+                data.extend_from_slice(&probe[..n]);
+            }
+            len += n;
+        });
+        data.truncate(len);
+        result
+    }
+}
+
+/// Seeking only works forward, from the current position or to an absolute position after
+/// it, and is implemented with [`skip`](AdcDecoder::skip). Seeking past the end of the
+/// stream stops at the end.
+#[cfg(feature = "std")]
+impl<R: Read> Seek for AdcDecoder<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let current = self.total_out();
+        let target = match pos {
+            SeekFrom::Current(offset) if offset >= 0 => current + offset as u64,
+            SeekFrom::Start(target) if target >= current => target,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "the decoder can only seek forward",
+                ))
+            }
+        };
+        Ok(current + self.skip(target - current)?)
+    }
+}
+
+/// Decompressed data can be peeked at through an internal buffer, it is allocated the first
+/// time [`fill_buf`](BufRead::fill_buf) is called.
+///
+/// ```
+/// use adc::AdcDecoder;
+/// use std::io::BufRead;
+///
+/// let input: &[u8] = &[0x85, b'a', b'b', b'\n', b'c', b'd', b'\n'];
+/// let lines: Vec<String> = AdcDecoder::new(input).lines().map(Result::unwrap).collect();
+/// assert_eq!(lines, ["ab", "cd"]);
+/// ```
+#[cfg(feature = "std")]
+impl<R: Read> BufRead for AdcDecoder<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.filled {
+            if self.buf.is_empty() {
+                #[cfg(feature = "fallible-alloc")]
+                self.buf
+                    .try_reserve_exact(BUF_SIZE)
+                    .map_err(out_of_memory)?;
+                self.buf.resize(BUF_SIZE, 0);
+            }
+            let mut buf = std::mem::take(&mut self.buf);
+            let result = self.decode(&mut buf);
+            self.buf = buf;
+            self.pos = 0;
+            self.filled = result?;
+        }
+        Ok(&self.buf[self.pos..self.filled])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = cmp::min(self.pos + amt, self.filled);
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use super::*;
+    use crate::generator::StreamGenerator;
+    use std::{fs, path::Path};
+
+    #[test]
+    fn golden_corpus() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/golden");
+        let mut count = 0;
+        for entry in fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension() != Some("adc".as_ref()) {
+                continue;
+            }
+
+            let input = fs::read(&path).unwrap();
+            let expected = fs::read(path.with_extension("out")).unwrap();
+            let mut data = Vec::new();
+            AdcDecoder::new(&input[..])
+                .read_to_end(&mut data)
+                .unwrap_or_else(|err| panic!("{}: {}", path.display(), err));
+            assert!(data == expected, "{}: wrong output", path.display());
+            count += 1;
+        }
+        assert!(count > 0, "no vectors in {}", dir.display());
+    }
+
+    /// Decode every possible first header byte followed by boundary values.
+    fn header_table() -> String {
+        let mut table = String::from(
+            "# first header byte, following bytes -> type, size, offset, header length\n\
+             # generated by the header_table test with ADC_UPDATE_TABLES=1, check changes carefully\n",
+        );
+        let follow = [
+            [0x00, 0x00],
+            [0x00, 0x01],
+            [0x01, 0x00],
+            [0x03, 0xff],
+            [0xff, 0xff],
+        ];
+        for byte in 0..=255u8 {
+            for &[b1, b2] in &follow {
+                let input = [byte, b1, b2];
+                let mut reader = &input[..];
+                let chunk = read_header(&mut reader).unwrap().parse().unwrap();
+                table += &format!(
+                    "{:02x} {:02x} {:02x} -> {} {} {} {}\n",
+                    byte,
+                    b1,
+                    b2,
+                    chunk.r#type,
+                    chunk.size,
+                    chunk.offset,
+                    input.len() - reader.len()
+                );
+            }
+        }
+        table
+    }
+
+    #[test]
+    fn header_decoding() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/headers.txt");
+        let table = header_table();
+        if std::env::var_os("ADC_UPDATE_TABLES").is_some() {
+            fs::write(&path, &table).unwrap();
+        }
+
+        let expected = fs::read_to_string(&path).unwrap();
+        for (line, (actual, expected)) in table.lines().zip(expected.lines()).enumerate() {
+            assert_eq!(actual, expected, "{}:{}", path.display(), line + 1);
+        }
+        assert_eq!(table.lines().count(), expected.lines().count());
+    }
+
+    #[test]
+    fn truncated_headers() {
+        for byte in 0..=0x7fu8 {
+            let header_len = if byte & 0x40 != 0 { 3 } else { 2 };
+            for len in 1..header_len {
+                let input = [byte, 0, 0];
+                let header = read_header(&mut &input[..len]).unwrap();
+                assert_eq!(header.as_bytes(), &input[..len]);
+                assert!(header.parse().is_none(), "{:02x}", byte);
+            }
+        }
+    }
+
+    #[test]
+    fn clone_mid_stream() {
+        let input: &[u8] = &[0x83, 0xfe, 0xed, 0xfa, 0xce, 0x00, 0x00, 0x40, 0x00, 0x06];
+        let mut d = AdcDecoder::new(input);
+        let mut head = [0; 5];
+        d.read_exact(&mut head).unwrap();
+
+        let mut fork = d.clone();
+        let mut rest = Vec::new();
+        d.read_to_end(&mut rest).unwrap();
+        let mut fork_rest = Vec::new();
+        fork.read_to_end(&mut fork_rest).unwrap();
+
+        assert_eq!(rest, [0xce, 0xce, 0xfe, 0xed, 0xfa, 0xce]);
+        assert_eq!(fork_rest, rest);
+    }
 
     #[test]
     fn all_types() {
@@ -240,6 +1385,365 @@ mod tests {
         assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
     }
 
+    #[test]
+    fn error_context() {
+        let input: &[u8] = &[0x81, 0xab, 0xcd, 0x00, 0x05];
+        let err = AdcDecoder::new(input)
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid chunk offset in chunk 1 at compressed offset 3, decompressed offset 2 \
+             (header 00 05)"
+        );
+        match AdcError::from(err) {
+            AdcError::InvalidOffset(context) => {
+                assert_eq!(context.index, 1);
+                assert_eq!(context.header, [0x00, 0x05]);
+            }
+            err => panic!("unexpected error {:?}", err),
+        }
+
+        let input: &[u8] = &[0x81, 0xab, 0xcd, 0x40, 0x00];
+        let err = AdcDecoder::new(input)
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(
+            err.to_string(),
+            "truncated chunk in chunk 1 at compressed offset 3, decompressed offset 2 \
+             (header 40 00)"
+        );
+    }
+
+    #[test]
+    fn finish() {
+        let input: &[u8] = &[0x83, 0xfe, 0xed, 0xfa, 0xce, 0x00, 0x00, 0x40, 0x00, 0x06];
+        let mut d = AdcDecoder::new(input);
+        d.read_to_end(&mut Vec::new()).unwrap();
+        assert!(d.finish().unwrap().is_empty());
+
+        // stopped at a chunk boundary, the next chunk is still unread
+        let mut d = AdcDecoder::new(input);
+        d.read_exact(&mut [0; 4]).unwrap();
+        let err = d.try_finish().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(matches!(AdcError::from(err), AdcError::UnreadData(c) if c.index == 1));
+
+        // all data read, but the stream continues with half a header
+        let mut d = AdcDecoder::new(&[0x80, 0xfe, 0x40, 0x00][..]);
+        d.read_exact(&mut [0; 1]).unwrap();
+        let err = d.finish().unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn trailing_data() {
+        // a stream of 5 decompressed bytes followed by unrelated data
+        let input: &[u8] = &[0x81, 0xab, 0xcd, 0x00, 0x01, 0x12, 0x34];
+        let mut d = AdcDecoder::new(input);
+        d.read_exact(&mut [0; 5]).unwrap();
+        assert_eq!(d.total_in(), 5);
+        assert_eq!(d.clone().into_inner(), [0x12, 0x34]);
+
+        let err = d.ensure_exhausted().unwrap_err();
+        assert!(matches!(AdcError::from(err), AdcError::TrailingData(5)));
+
+        let mut d = AdcDecoder::new(&input[..5]);
+        d.read_exact(&mut [0; 5]).unwrap();
+        d.ensure_exhausted().unwrap();
+
+        let mut d = AdcDecoder::new(&input[..5]);
+        d.read_exact(&mut [0; 4]).unwrap();
+        let err = d.ensure_exhausted().unwrap_err();
+        assert!(matches!(AdcError::from(err), AdcError::UnreadData(_)));
+    }
+
+    #[test]
+    fn max_output_size() {
+        let input: &[u8] = &[0x83, 0xfe, 0xed, 0xfa, 0xce, 0x00, 0x00, 0x40, 0x00, 0x06];
+        let mut data = Vec::new();
+        AdcDecoder::new(input)
+            .max_output_size(11)
+            .read_to_end(&mut data)
+            .unwrap();
+        assert_eq!(data.len(), 11);
+
+        let mut d = AdcDecoder::new(input).max_output_size(10);
+        let mut data = Vec::new();
+        let err = d.read_to_end(&mut data).unwrap_err();
+        assert_eq!(data.len(), 10);
+        assert_eq!(
+            err.to_string(),
+            "decompressed data exceeds the limit of 10 bytes"
+        );
+        assert!(matches!(AdcError::from(err), AdcError::OutputLimit(10)));
+    }
+
+    #[test]
+    fn expected_size() {
+        let input: &[u8] = &[0x83, 0xfe, 0xed, 0xfa, 0xce, 0x00, 0x00, 0x40, 0x00, 0x06];
+        let mut d = AdcDecoder::with_expected_size(input, 11);
+        let mut data = Vec::new();
+        d.read_to_end(&mut data).unwrap();
+        assert_eq!(data.len(), 11);
+        assert_eq!(d.size_hint(), Some(0));
+
+        let err = AdcDecoder::with_expected_size(input, 7)
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert!(matches!(
+            AdcError::from(err),
+            AdcError::TooLong { expected: 7 }
+        ));
+
+        let err = AdcDecoder::with_expected_size(input, 12)
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        assert_eq!(
+            err.to_string(),
+            "stream decompressed to 11 bytes, expected 12"
+        );
+
+        // the smaller of the two limits applies
+        let err = AdcDecoder::with_expected_size(input, 11)
+            .max_output_size(5)
+            .read_to_end(&mut Vec::new())
+            .unwrap_err();
+        assert!(matches!(AdcError::from(err), AdcError::OutputLimit(5)));
+    }
+
+    #[test]
+    fn header_read_errors() {
+        let items = vec![
+            Ok(0x80),
+            Ok(0xfe),
+            Err(io::ErrorKind::Interrupted.into()),
+            Ok(0x80),
+            Ok(0xed),
+            Err(io::ErrorKind::PermissionDenied.into()),
+        ];
+        let mut d = AdcDecoder::new(IterReader::new(items));
+        let mut data = Vec::new();
+        let err = d.read_to_end(&mut data).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(data, [0xfe, 0xed]);
+    }
+
+    #[test]
+    fn would_block() {
+        let stream = StreamGenerator::new(14).text(3000).zeros(500).finish();
+        let items = stream.compressed.iter().flat_map(|&byte| {
+            let err = io::Error::from(io::ErrorKind::WouldBlock);
+            vec![Ok(byte), Err(err)]
+        });
+        let mut d = AdcDecoder::new(IterReader::new(items));
+        let mut data = Vec::new();
+        let mut buf = [0; 7];
+        loop {
+            match d.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => data.extend_from_slice(&buf[..n]),
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => panic!("{}", e),
+            }
+        }
+        assert!(data == stream.decompressed);
+        assert_eq!(d.total_in(), stream.compressed.len() as u64);
+    }
+
+    #[test]
+    fn fills_buffer() {
+        let stream = StreamGenerator::new(15).text(5000).zeros(2000).finish();
+        let mut d = AdcDecoder::new(&stream.compressed[..]);
+        let mut buf = vec![0; 4000];
+        assert_eq!(d.read(&mut buf).unwrap(), 4000);
+        assert!(buf[..] == stream.decompressed[..4000]);
+
+        // the error comes after the data of the chunks before it
+        let input: &[u8] = &[0x81, 0xab, 0xcd, 0x00, 0x01, 0x00, 0x07];
+        let mut d = AdcDecoder::new(input);
+        assert_eq!(d.read(&mut buf).unwrap(), 5);
+        let err = d.read(&mut buf).unwrap_err();
+        assert!(matches!(AdcError::from(err), AdcError::InvalidOffset(c) if c.index == 2));
+    }
+
+    #[test]
+    fn read_to_end() {
+        let stream = StreamGenerator::new(18).text(30_000).zeros(5000).finish();
+        let len = stream.decompressed.len();
+        let mut d = AdcDecoder::with_expected_size(&stream.compressed[..], len as u64);
+        let mut data = b"prefix".to_vec();
+        assert_eq!(d.fill_buf().unwrap()[..3], stream.decompressed[..3]);
+        assert_eq!(d.read_to_end(&mut data).unwrap(), len);
+        assert!(data[6..] == stream.decompressed[..]);
+        assert_eq!(d.total_out(), len as u64);
+        assert!(data.capacity() < 2 * len);
+
+        // a huge expected size doesn't allocate before anything is decoded
+        let mut d = AdcDecoder::with_expected_size(&stream.compressed[..], u64::MAX);
+        let mut data = Vec::new();
+        assert!(d.read_to_end(&mut data).is_err());
+        assert!(data == stream.decompressed);
+        assert!(data.capacity() < 4 * len);
+
+        let input: &[u8] = &[0x81, 0xab, 0xcd, 0x00, 0x05];
+        let mut data = Vec::new();
+        assert!(AdcDecoder::new(input).read_to_end(&mut data).is_err());
+        assert_eq!(data, [0xab, 0xcd]);
+    }
+
+    #[test]
+    fn reset() {
+        let first = StreamGenerator::new(23).text(10_000).finish();
+        let second = StreamGenerator::new(24).zeros(3000).random(500).finish();
+        let mut d = AdcDecoder::with_recovery(&first.compressed[..]);
+        d.fill_buf().unwrap();
+
+        let previous = d.reset(&second.compressed[..]);
+        assert!(previous.len() < first.compressed.len());
+        let mut data = Vec::new();
+        d.read_to_end(&mut data).unwrap();
+        assert!(data == second.decompressed);
+        assert_eq!(d.total_in(), second.compressed.len() as u64);
+
+        // a run right at the start reaches into the dictionary
+        d.reset_with_dictionary(&[0x00, 0x01, 0x80, 0x07][..], b"xyz");
+        let mut data = Vec::new();
+        d.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"yzy\x07");
+        assert!(d.corruptions().is_empty());
+    }
+
+    #[test]
+    fn input_access() {
+        let input: &[u8] = &[0x81, 0xab, 0xcd, 0x00, 0x01, 0xff];
+        let mut d = AdcDecoder::new(input);
+        d.read_exact(&mut [0; 5]).unwrap();
+        assert_eq!((d.total_in(), d.total_out()), (5, 5));
+        assert_eq!(d.get_ref(), &[0xff]);
+
+        // the data following the stream can be read through the decoder's input
+        let mut rest = Vec::new();
+        d.get_mut().read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, [0xff]);
+        assert!(d.into_inner().is_empty());
+    }
+
+    #[test]
+    fn progress() {
+        let stream = StreamGenerator::new(26).text(50_000).finish();
+        let calls = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let hook_calls = calls.clone();
+        let mut d = AdcDecoder::new(&stream.compressed[..]).on_progress(10_000, move |_, out| {
+            hook_calls.lock().unwrap().push(out);
+        });
+        io::copy(&mut d, &mut io::sink()).unwrap();
+
+        let calls = calls.lock().unwrap();
+        assert!(calls.len() >= 2);
+        // the last call at the end of the stream can come sooner
+        let regular = &calls[..calls.len() - 1];
+        assert!(regular.windows(2).all(|w| w[1] - w[0] >= 10_000));
+        assert_eq!(calls.last(), Some(&(stream.decompressed.len() as u64)));
+    }
+
+    #[test]
+    fn concatenated_streams() {
+        let first = StreamGenerator::new(28).text(10_000).zeros(100).finish();
+        let second = StreamGenerator::new(29).zeros(2000).text(3000).finish();
+        let input = [&first.compressed[..], &second.compressed[..]].concat();
+
+        let mut data = Vec::new();
+        AdcDecoder::new(&input[..]).read_to_end(&mut data).unwrap();
+        assert!(data == [&first.decompressed[..], &second.decompressed[..]].concat());
+    }
+
+    #[test]
+    fn buf_read() {
+        let stream = StreamGenerator::new(17).text(20_000).zeros(3000).finish();
+        let mut d = AdcDecoder::new(&stream.compressed[..]);
+        let mut data = Vec::new();
+        // alternate between peeking into the buffer and reading past it
+        loop {
+            let buf = d.fill_buf().unwrap();
+            if buf.is_empty() {
+                break;
+            }
+            let len = buf.len().min(100);
+            data.extend_from_slice(&buf[..len]);
+            d.consume(len);
+
+            let mut buf = [0; 150];
+            let n = d.read(&mut buf).unwrap();
+            data.extend_from_slice(&buf[..n]);
+            assert_eq!(d.total_out(), data.len() as u64);
+        }
+        assert!(data == stream.decompressed);
+
+        let input: &[u8] = &[0x81, 0xab, 0xcd];
+        let mut d = AdcDecoder::new(input);
+        assert_eq!(d.fill_buf().unwrap(), [0xab, 0xcd]);
+        d.consume(1);
+        assert!(d.ensure_exhausted().is_err());
+    }
+
+    #[test]
+    fn seek_forward() {
+        let stream = StreamGenerator::new(18).text(20_000).zeros(3000).finish();
+        let mut d = AdcDecoder::new(&stream.compressed[..]);
+        assert_eq!(d.skip(10_000).unwrap(), 10_000);
+        assert_eq!(d.stream_position().unwrap(), 10_000);
+
+        let mut buf = [0; 10];
+        d.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, stream.decompressed[10_000..10_010]);
+
+        // skip over buffered data
+        d.fill_buf().unwrap();
+        assert_eq!(d.seek(SeekFrom::Current(5)).unwrap(), 10_015);
+        assert_eq!(d.seek(SeekFrom::Start(15_000)).unwrap(), 15_000);
+        d.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, stream.decompressed[15_000..15_010]);
+
+        assert!(d.seek(SeekFrom::Start(0)).is_err());
+        assert!(d.seek(SeekFrom::End(0)).is_err());
+        let len = stream.decompressed.len() as u64;
+        assert_eq!(d.seek(SeekFrom::Start(len + 100)).unwrap(), len);
+    }
+
+    #[test]
+    fn chunk_round_trip() {
+        for byte in 0..=u8::MAX {
+            let header = [byte, 0x03, 0x21];
+            let (chunk, len) = AdcChunk::parse(&header).unwrap();
+            assert_eq!(len, chunk.header_len());
+            assert!(AdcChunk::parse(&header[..len - 1]).is_none());
+
+            let mut encoded = Vec::new();
+            chunk.encode(&mut encoded);
+            assert_eq!(encoded, header[..len], "{:02x}", byte);
+        }
+
+        assert_eq!(AdcChunk::run(0x3ff, 10).kind(), AdcChunkType::TwoByte);
+        assert_eq!(
+            AdcChunk::run(0x3ff, MAX_TWO_BYTE_RUN_LEN).kind(),
+            AdcChunkType::TwoByte
+        );
+        assert_eq!(AdcChunk::run(0x400, 18).kind(), AdcChunkType::ThreeByte);
+        assert_eq!(AdcChunk::run(0x400, 4).kind(), AdcChunkType::ThreeByte);
+        assert_eq!(AdcChunk::run(0, 19).kind(), AdcChunkType::ThreeByte);
+        assert_eq!(AdcChunk::plain(128).offset(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "runs are 3 to 67 bytes")]
+    fn short_far_run() {
+        AdcChunk::run(0x400, 3);
+    }
+
     #[test]
     fn empty() {
         let input: &[u8] = &[];
@@ -251,4 +1755,59 @@ mod tests {
 
         assert_eq!(output[..], data[..]);
     }
+
+    #[test]
+    fn recover_invalid_offset() {
+        // run at position 0, then a plain chunk and a run with an offset too big
+        let input: &[u8] = &[0x00, 0x00, 0x81, 0xab, 0xcd, 0x40, 0x00, 0x05];
+
+        let mut d = AdcDecoder::with_recovery(input);
+        let mut data = Vec::new();
+        d.read_to_end(&mut data).unwrap();
+
+        assert_eq!(data, [0, 0, 0, 0xab, 0xcd, 0, 0, 0, 0]);
+        assert_eq!(
+            d.corruptions(),
+            [
+                Corruption {
+                    kind: CorruptionKind::InvalidOffset,
+                    decompressed_offset: 0,
+                    len: 3,
+                },
+                Corruption {
+                    kind: CorruptionKind::InvalidOffset,
+                    decompressed_offset: 5,
+                    len: 4,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn recover_truncated() {
+        let input: &[u8] = &[0x83, 0xfe, 0xed];
+
+        let mut d = AdcDecoder::with_recovery(input);
+        let mut data = Vec::new();
+        d.read_to_end(&mut data).unwrap();
+
+        assert_eq!(data, [0xfe, 0xed, 0, 0]);
+        assert_eq!(
+            d.corruptions(),
+            [Corruption {
+                kind: CorruptionKind::Truncated,
+                decompressed_offset: 2,
+                len: 2,
+            }]
+        );
+
+        // missing 2nd header byte
+        let input: &[u8] = &[0x80, 0xfe, 0x00];
+        let mut d = AdcDecoder::with_recovery(input);
+        let mut data = Vec::new();
+        d.read_to_end(&mut data).unwrap();
+
+        assert_eq!(data, [0xfe]);
+        assert_eq!(d.corruptions()[0].kind, CorruptionKind::Truncated);
+    }
 }
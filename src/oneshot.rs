@@ -0,0 +1,238 @@
+//! One call helpers for in-memory data and streams.
+
+#[cfg(feature = "rayon")]
+use crate::AdcError;
+use crate::{inspect::Counter, AdcDecoder, AdcEncoder, AdcSliceDecoder, CompressionLevel};
+use std::io::{self, prelude::*};
+
+/// Decompress `input` into a new vector
+///
+/// Fails if `input` is not a valid ADC stream.
+///
+/// ```
+/// let data = adc::decompress_to_vec(&[0x81, 0xab, 0xcd, 0x00, 0x01]).unwrap();
+/// assert_eq!(data, [0xab, 0xcd, 0xab, 0xcd, 0xab]);
+/// ```
+pub fn decompress_to_vec(input: &[u8]) -> io::Result<Vec<u8>> {
+    // the headers tell the exact size, so the output is allocated once
+    let mut data = Vec::new();
+    reserve(&mut data, AdcSliceDecoder::decompressed_len(input) as usize)?;
+    data.resize(data.capacity(), 0);
+    let len = AdcSliceDecoder::decompress_into(input, &mut data)?;
+    data.truncate(len);
+    Ok(data)
+}
+
+/// Decompress each of `blocks` into a new vector, spread over the rayon thread pool
+///
+/// Blocks are decoded independently, like the runs of a DMG block table, and the results
+/// are in the order of `blocks`. A failing block doesn't stop the others.
+///
+/// ```
+/// let blocks: &[&[u8]] = &[&[0x81, 0xab, 0xcd, 0x00, 0x01], &[0x00, 0x00]];
+/// let results = adc::decompress_blocks_parallel(blocks);
+/// assert_eq!(results[0].as_ref().unwrap(), &[0xab, 0xcd, 0xab, 0xcd, 0xab]);
+/// assert!(matches!(results[1], Err(adc::AdcError::InvalidOffset(_))));
+/// ```
+#[cfg(feature = "rayon")]
+pub fn decompress_blocks_parallel(blocks: &[&[u8]]) -> Vec<Result<Vec<u8>, AdcError>> {
+    use rayon::prelude::*;
+
+    blocks
+        .par_iter()
+        .map(|block| decompress_to_vec(block).map_err(AdcError::from))
+        .collect()
+}
+
+/// Reserve room for at least `additional` more bytes, with at least a small minimum.
+pub(crate) fn reserve(data: &mut Vec<u8>, additional: usize) -> io::Result<()> {
+    let additional = additional.max(64);
+    #[cfg(feature = "fallible-alloc")]
+    data.try_reserve(additional).map_err(crate::out_of_memory)?;
+    #[cfg(not(feature = "fallible-alloc"))]
+    data.reserve(additional);
+    Ok(())
+}
+
+/// Compress `input` with the given level into a new vector
+///
+/// ```
+/// use adc::CompressionLevel;
+///
+/// let compressed = adc::compress_to_vec(b"abcabcabcabc", CompressionLevel::Default);
+/// assert_eq!(adc::decompress_to_vec(&compressed).unwrap(), b"abcabcabcabc");
+/// ```
+pub fn compress_to_vec(input: &[u8], level: CompressionLevel) -> Vec<u8> {
+    let mut encoder = AdcEncoder::with_level(Vec::new(), level);
+    encoder
+        .write_all(input)
+        .and_then(|_| encoder.finish())
+        .expect("writing to a Vec can't fail")
+}
+
+/// Decompress all of `input` to `output`
+///
+/// Returns the number of compressed bytes read and decompressed bytes written. Reads stop
+/// at the end of the input, the stream is not checked for trailing data.
+///
+/// ```
+/// let input: &[u8] = &[0x81, 0xab, 0xcd, 0x00, 0x01];
+/// let mut data = Vec::new();
+/// assert_eq!(adc::decompress(input, &mut data).unwrap(), (5, 5));
+/// assert_eq!(data, [0xab, 0xcd, 0xab, 0xcd, 0xab]);
+/// ```
+pub fn decompress<R: Read, W: Write>(input: R, mut output: W) -> io::Result<(u64, u64)> {
+    let mut decoder = AdcDecoder::new(Counter::new(input));
+    let written = io::copy(&mut decoder, &mut output)?;
+    Ok((decoder.input.count, written))
+}
+
+/// Compress all of `input` to `output` with the given level
+///
+/// Returns the number of bytes read and compressed bytes written. Together with
+/// [`decompress`] and [`recompress`] this converts between ADC and raw data without holding
+/// all of it in memory.
+///
+/// ```
+/// use adc::CompressionLevel;
+///
+/// let input: &[u8] = b"abcabcabcabc";
+/// let mut compressed = Vec::new();
+/// let (read, written) = adc::compress(input, &mut compressed, CompressionLevel::Default).unwrap();
+/// assert_eq!((read, written), (12, compressed.len() as u64));
+/// assert_eq!(adc::decompress_to_vec(&compressed).unwrap(), b"abcabcabcabc");
+/// ```
+pub fn compress<R: Read, W: Write>(
+    mut input: R,
+    output: W,
+    level: CompressionLevel,
+) -> io::Result<(u64, u64)> {
+    let mut encoder = AdcEncoder::with_level(output, level);
+    io::copy(&mut input, &mut encoder)?;
+    encoder.try_finish()?;
+    Ok((encoder.total_in(), encoder.total_out()))
+}
+
+/// Decompress all of `input` and compress the data again with `encoder`
+///
+/// Changes the level or the [restart interval](AdcEncoder::restart_interval) of a stream
+/// while streaming it. Returns the number of compressed bytes read and written, the
+/// encoder is finished.
+///
+/// ```
+/// use adc::{AdcEncoder, CompressionLevel};
+///
+/// let input = adc::compress_to_vec(&[7; 1000], CompressionLevel::Store);
+/// let mut output = Vec::new();
+/// let encoder = AdcEncoder::with_level(&mut output, CompressionLevel::Best);
+/// let (read, written) = adc::recompress(&input[..], encoder).unwrap();
+/// assert_eq!(read, input.len() as u64);
+/// assert!(written < 100);
+/// assert_eq!(adc::decompress_to_vec(&output).unwrap(), [7; 1000]);
+/// ```
+pub fn recompress<R: Read, W: Write>(
+    input: R,
+    mut encoder: AdcEncoder<W>,
+) -> io::Result<(u64, u64)> {
+    let mut decoder = AdcDecoder::new(Counter::new(input));
+    io::copy(&mut decoder, &mut encoder)?;
+    encoder.try_finish()?;
+    Ok((decoder.input.count, encoder.total_out()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::StreamGenerator;
+
+    #[test]
+    fn round_trip() {
+        let stream = StreamGenerator::new(10).zeros(100_000).text(5000).finish();
+        assert_eq!(
+            decompress_to_vec(&stream.compressed).unwrap(),
+            stream.decompressed
+        );
+
+        let compressed = compress_to_vec(&stream.decompressed, CompressionLevel::Best);
+        assert_eq!(decompress_to_vec(&compressed).unwrap(), stream.decompressed);
+
+        assert!(decompress_to_vec(&[]).unwrap().is_empty());
+        assert!(compress_to_vec(&[], CompressionLevel::Default).is_empty());
+    }
+
+    #[test]
+    fn transcode() {
+        let stream = StreamGenerator::new(12).text(100_000).random(5000).finish();
+        let mut compressed = Vec::new();
+        let (read, written) = compress(
+            &stream.decompressed[..],
+            &mut compressed,
+            CompressionLevel::Fastest,
+        )
+        .unwrap();
+        assert_eq!(read, stream.decompressed.len() as u64);
+        assert_eq!(written, compressed.len() as u64);
+
+        let mut segmented = Vec::new();
+        let encoder =
+            AdcEncoder::with_level(&mut segmented, CompressionLevel::Best).restart_interval(30_000);
+        let (read, written) = recompress(&compressed[..], encoder).unwrap();
+        assert_eq!(read, compressed.len() as u64);
+        assert_eq!(written, segmented.len() as u64);
+        assert!(segmented.len() < compressed.len());
+
+        let mut data = Vec::new();
+        assert_eq!(
+            decompress(&segmented[..], &mut data).unwrap(),
+            (written, stream.decompressed.len() as u64)
+        );
+        assert!(data == stream.decompressed);
+
+        let err = recompress(&[0x00, 0x00][..], AdcEncoder::new(io::sink())).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn invalid_input() {
+        let err = decompress_to_vec(&[0x00, 0x00]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let err = decompress(&[0x83, 0xfe][..], io::sink()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_blocks() {
+        let streams: Vec<_> = (0..16)
+            .map(|seed| StreamGenerator::new(seed).text(20_000).zeros(5000).finish())
+            .collect();
+        let mut blocks: Vec<&[u8]> = streams.iter().map(|s| &s.compressed[..]).collect();
+        blocks.insert(3, &[0x83, 0xfe]);
+
+        let results = decompress_blocks_parallel(&blocks);
+        assert_eq!(results.len(), 17);
+        assert!(matches!(results[3], Err(AdcError::TruncatedChunk(_))));
+        let decoded: Vec<_> = results.into_iter().filter_map(Result::ok).collect();
+        for (data, stream) in decoded.iter().zip(&streams) {
+            assert!(*data == stream.decompressed);
+        }
+        assert_eq!(decoded.len(), streams.len());
+    }
+
+    #[test]
+    fn streaming() {
+        let stream = StreamGenerator::new(11).text(300_000).finish();
+        let mut data = Vec::new();
+        let counts = decompress(&stream.compressed[..], &mut data).unwrap();
+
+        assert_eq!(
+            counts,
+            (
+                stream.compressed.len() as u64,
+                stream.decompressed.len() as u64
+            )
+        );
+        assert_eq!(data, stream.decompressed);
+    }
+}
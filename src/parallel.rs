@@ -0,0 +1,501 @@
+//! Concurrent extraction of many independent compressed blocks.
+//!
+//! Every block of a disk image is a separate ADC stream, so blocks can be decoded on several
+//! threads at once. [`ParallelExtractor`] runs a small worker pool over a list of [`Block`]s
+//! and places each decoded block at its output offset in the destination.
+
+use crate::extent::{decode_extent, write_all_at};
+use std::{
+    cmp,
+    collections::BTreeMap,
+    fs::File,
+    io::{self, prelude::*},
+    sync::mpsc,
+    thread,
+};
+
+#[cfg(adc_loom)]
+use loom::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Condvar, Mutex,
+};
+#[cfg(not(adc_loom))]
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Condvar, Mutex,
+};
+
+/// Number of blocks per thread that may be decoded ahead of the next block to write.
+const LOOKAHEAD_PER_THREAD: usize = 4;
+
+/// Location of a compressed block in the source and of its decoded data in the destination.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Block {
+    /// Offset of the compressed data in the source file
+    pub input_offset: u64,
+    /// Length of the compressed data
+    pub input_len: u64,
+    /// Offset of the decompressed data in the destination
+    pub output_offset: u64,
+}
+
+/// Worker pool decoding blocks concurrently.
+#[derive(Clone, Debug)]
+pub struct ParallelExtractor {
+    threads: usize,
+}
+
+impl ParallelExtractor {
+    /// Create an extractor using one thread per available CPU
+    pub fn new() -> ParallelExtractor {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        ParallelExtractor { threads }
+    }
+
+    /// Set the number of worker threads
+    pub fn threads(mut self, threads: usize) -> ParallelExtractor {
+        self.threads = cmp::max(threads, 1);
+        self
+    }
+
+    /// Decode `blocks` from `src` and write them to their offsets in `dst` using positioned
+    /// writes.
+    ///
+    /// Blocks may be given in any order. Returns the total number of decompressed bytes.
+    pub fn extract_to_file(&self, src: &File, blocks: &[Block], dst: &File) -> io::Result<u64> {
+        let queue = WorkQueue::new(blocks.len());
+
+        thread::scope(|s| {
+            let workers: Vec<_> = (0..self.worker_count(blocks))
+                .map(|_| {
+                    s.spawn(|| {
+                        let mut written = 0;
+                        let mut buf = Vec::new();
+                        while let Some(idx) = queue.claim() {
+                            let block = &blocks[idx];
+                            buf.clear();
+                            let res = decode_block(src, block, &mut buf)
+                                .and_then(|_| write_all_at(dst, &buf, block.output_offset));
+                            if let Err(err) = res {
+                                queue.fail();
+                                return Err(err);
+                            }
+                            written += buf.len() as u64;
+                        }
+                        Ok(written)
+                    })
+                })
+                .collect();
+
+            workers
+                .into_iter()
+                .map(|worker| worker.join().expect("worker thread panicked"))
+                .sum()
+        })
+    }
+
+    /// Decode `blocks` from `src` and write them to `dst` in order.
+    ///
+    /// The output offsets of the blocks must be ascending and must not overlap. Gaps between
+    /// blocks are filled with zeros. Returns the total number of decompressed bytes, not
+    /// counting the gaps.
+    ///
+    /// Workers only run a few blocks per thread ahead of the next block to write, so a slow
+    /// block holds back the others instead of letting decoded data pile up in memory.
+    pub fn extract_to_writer<W: Write>(
+        &self,
+        src: &File,
+        blocks: &[Block],
+        dst: &mut W,
+    ) -> io::Result<u64> {
+        let queue = WorkQueue::new(blocks.len());
+        let threads = self.worker_count(blocks);
+        let lookahead = Lookahead::new(threads * LOOKAHEAD_PER_THREAD);
+
+        thread::scope(|s| {
+            let (tx, rx) = mpsc::sync_channel(threads);
+            for _ in 0..threads {
+                let tx = tx.clone();
+                let queue = &queue;
+                let lookahead = &lookahead;
+                s.spawn(move || {
+                    while let Some(idx) = queue.claim() {
+                        if !lookahead.wait(idx) {
+                            break;
+                        }
+                        let mut buf = Vec::new();
+                        let res = decode_block(src, &blocks[idx], &mut buf).map(|_| buf);
+                        // the receiver is gone if writing failed
+                        if tx.send((idx, res)).is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            drop(tx);
+
+            // blocks finish out of order, hold them back until it's their turn
+            let mut write = || {
+                let mut reorder = Reorder::new();
+                let mut pos = 0;
+                let mut written = 0;
+                for (idx, res) in &rx {
+                    reorder.push(idx, res);
+                    while let Some((idx, res)) = reorder.pop() {
+                        let block = &blocks[idx];
+                        if block.output_offset < pos {
+                            return Err(io::Error::new(
+                                io::ErrorKind::InvalidInput,
+                                "overlapping or unordered blocks",
+                            ));
+                        }
+                        let data = res?;
+
+                        let gap = block.output_offset - pos;
+                        io::copy(&mut io::repeat(0).take(gap), dst)?;
+                        dst.write_all(&data)?;
+
+                        pos = block.output_offset + data.len() as u64;
+                        written += data.len() as u64;
+                        lookahead.advance(idx + 1);
+                    }
+                }
+                Ok(written)
+            };
+            let result = write();
+            // workers waiting for their turn give up if writing failed
+            queue.fail();
+            lookahead.advance(usize::MAX);
+            result
+        })
+    }
+
+    fn worker_count(&self, blocks: &[Block]) -> usize {
+        cmp::max(cmp::min(self.threads, blocks.len()), 1)
+    }
+}
+
+impl Default for ParallelExtractor {
+    fn default() -> ParallelExtractor {
+        ParallelExtractor::new()
+    }
+}
+
+/// Work list shared by the threads of a pool, handing out every index once.
+///
+/// After [`fail`](WorkQueue::fail) no more work is handed out, so the pool winds down after
+/// the first error.
+struct WorkQueue {
+    next: AtomicUsize,
+    len: usize,
+    failed: AtomicBool,
+}
+
+impl WorkQueue {
+    fn new(len: usize) -> WorkQueue {
+        WorkQueue {
+            next: AtomicUsize::new(0),
+            len,
+            failed: AtomicBool::new(false),
+        }
+    }
+
+    /// Get the index of the next item to work on, `None` once all are taken
+    fn claim(&self) -> Option<usize> {
+        if self.failed.load(Ordering::Relaxed) {
+            return None;
+        }
+        let idx = self.next.fetch_add(1, Ordering::Relaxed);
+        if idx < self.len {
+            Some(idx)
+        } else {
+            None
+        }
+    }
+
+    /// Stop handing out work
+    fn fail(&self) {
+        self.failed.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Bound on how far workers run ahead of the consumer of their results.
+///
+/// Items are consumed in index order, an item may only be worked on once it is less than
+/// `limit` items ahead of the next one to consume.
+struct Lookahead {
+    /// Number of items consumed
+    consumed: Mutex<usize>,
+    changed: Condvar,
+    limit: usize,
+}
+
+impl Lookahead {
+    fn new(limit: usize) -> Lookahead {
+        Lookahead {
+            consumed: Mutex::new(0),
+            changed: Condvar::new(),
+            limit,
+        }
+    }
+
+    /// Wait until item `idx` may be worked on, `false` if the consumer stopped
+    fn wait(&self, idx: usize) -> bool {
+        let mut consumed = self.consumed.lock().unwrap();
+        while idx >= consumed.saturating_add(self.limit) {
+            consumed = self.changed.wait(consumed).unwrap();
+        }
+        *consumed != usize::MAX
+    }
+
+    /// Record that the first `consumed` items are done, `usize::MAX` releases all waiters
+    fn advance(&self, consumed: usize) {
+        *self.consumed.lock().unwrap() = consumed;
+        self.changed.notify_all();
+    }
+}
+
+/// Buffer putting results that arrive in any order back into index order.
+struct Reorder<T> {
+    pending: BTreeMap<usize, T>,
+    next: usize,
+}
+
+impl<T> Reorder<T> {
+    fn new() -> Reorder<T> {
+        Reorder {
+            pending: BTreeMap::new(),
+            next: 0,
+        }
+    }
+
+    fn push(&mut self, idx: usize, item: T) {
+        self.pending.insert(idx, item);
+    }
+
+    /// Take the next result in order, if it has arrived
+    fn pop(&mut self) -> Option<(usize, T)> {
+        let item = self.pending.remove(&self.next)?;
+        self.next += 1;
+        Some((self.next - 1, item))
+    }
+}
+
+fn decode_block(src: &File, block: &Block, buf: &mut Vec<u8>) -> io::Result<usize> {
+    decode_extent(src, block.input_offset, block.input_len).read_to_end(buf)
+}
+
+#[cfg(all(test, not(adc_loom)))]
+mod tests {
+    use super::*;
+    use crate::extent::tests::temp_file;
+
+    const BLOCK1: &[u8] = &[0x83, 0xfe, 0xed, 0xfa, 0xce, 0x00, 0x00];
+    const BLOCK2: &[u8] = &[0x81, 0xab, 0xcd, 0x00, 0x01];
+
+    fn source() -> (File, Vec<Block>) {
+        let file = temp_file("parallel-src", &[BLOCK1, BLOCK2].concat());
+        let blocks = vec![
+            Block {
+                input_offset: BLOCK1.len() as u64,
+                input_len: BLOCK2.len() as u64,
+                output_offset: 10,
+            },
+            Block {
+                input_offset: 0,
+                input_len: BLOCK1.len() as u64,
+                output_offset: 0,
+            },
+        ];
+        (file, blocks)
+    }
+
+    #[test]
+    fn to_file() {
+        let (src, blocks) = source();
+        let mut dst = temp_file("parallel-dst", &[]);
+
+        let written = ParallelExtractor::new()
+            .threads(2)
+            .extract_to_file(&src, &blocks, &dst)
+            .unwrap();
+
+        let mut data = Vec::new();
+        dst.read_to_end(&mut data).unwrap();
+        assert_eq!(written, 12);
+        assert_eq!(
+            data,
+            [0xfe, 0xed, 0xfa, 0xce, 0xce, 0xce, 0xce, 0, 0, 0, 0xab, 0xcd, 0xab, 0xcd, 0xab]
+        );
+    }
+
+    #[test]
+    fn to_writer() {
+        let (src, mut blocks) = source();
+        blocks.reverse();
+
+        let mut data = Vec::new();
+        let written = ParallelExtractor::new()
+            .threads(2)
+            .extract_to_writer(&src, &blocks, &mut data)
+            .unwrap();
+
+        assert_eq!(written, 12);
+        assert_eq!(
+            data,
+            [0xfe, 0xed, 0xfa, 0xce, 0xce, 0xce, 0xce, 0, 0, 0, 0xab, 0xcd, 0xab, 0xcd, 0xab]
+        );
+    }
+
+    #[test]
+    fn to_writer_many_blocks() {
+        // more blocks than the workers may run ahead, one of them corrupt
+        let src = temp_file("parallel-many", &BLOCK2.repeat(100));
+        let mut blocks: Vec<_> = (0..100)
+            .map(|i| Block {
+                input_offset: i * BLOCK2.len() as u64,
+                input_len: BLOCK2.len() as u64,
+                output_offset: i * 5,
+            })
+            .collect();
+
+        let mut data = Vec::new();
+        let extractor = ParallelExtractor::new().threads(3);
+        let written = extractor
+            .extract_to_writer(&src, &blocks, &mut data)
+            .unwrap();
+        assert_eq!(written, 500);
+        assert!(data == [0xab, 0xcd, 0xab, 0xcd, 0xab].repeat(100));
+
+        // starts in the middle of a plain chunk
+        blocks[50].input_offset += 1;
+        let err = extractor
+            .extract_to_writer(&src, &blocks, &mut Vec::new())
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn to_writer_unordered() {
+        let (src, blocks) = source();
+
+        let err = ParallelExtractor::new()
+            .extract_to_writer(&src, &blocks, &mut Vec::new())
+            .unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
+
+/// Model checked tests of the pool's shared state, run with
+/// `RUSTFLAGS="--cfg adc_loom" cargo test --release --lib parallel`.
+#[cfg(all(test, adc_loom))]
+mod loom_tests {
+    use super::*;
+    use loom::{
+        sync::{mpsc, Arc},
+        thread,
+    };
+
+    fn drain(queue: &WorkQueue) -> Vec<usize> {
+        let mut claimed = Vec::new();
+        while let Some(idx) = queue.claim() {
+            claimed.push(idx);
+        }
+        claimed
+    }
+
+    #[test]
+    fn every_item_claimed_once() {
+        loom::model(|| {
+            let queue = Arc::new(WorkQueue::new(3));
+            let other = {
+                let queue = queue.clone();
+                thread::spawn(move || drain(&queue))
+            };
+
+            let mut claimed = drain(&queue);
+            claimed.extend(other.join().unwrap());
+            claimed.sort_unstable();
+            assert_eq!(claimed, [0, 1, 2]);
+        });
+    }
+
+    #[test]
+    fn failure_stops_work() {
+        loom::model(|| {
+            let queue = Arc::new(WorkQueue::new(3));
+            let failing = {
+                let queue = queue.clone();
+                thread::spawn(move || {
+                    let idx = queue.claim();
+                    queue.fail();
+                    idx
+                })
+            };
+
+            let claimed = drain(&queue);
+            let failed = failing.join().unwrap();
+            assert!(claimed.len() + failed.iter().count() <= 3);
+            assert!(!failed.is_some_and(|idx| claimed.contains(&idx)));
+            assert_eq!(queue.claim(), None);
+        });
+    }
+
+    #[test]
+    fn lookahead_waits_for_consumer() {
+        loom::model(|| {
+            let lookahead = Arc::new(Lookahead::new(1));
+            let worker = {
+                let lookahead = lookahead.clone();
+                thread::spawn(move || lookahead.wait(1))
+            };
+
+            lookahead.advance(1);
+            assert!(worker.join().unwrap());
+        });
+    }
+
+    #[test]
+    fn lookahead_released_on_stop() {
+        loom::model(|| {
+            let lookahead = Arc::new(Lookahead::new(1));
+            let worker = {
+                let lookahead = lookahead.clone();
+                thread::spawn(move || lookahead.wait(5))
+            };
+
+            lookahead.advance(usize::MAX);
+            assert!(!worker.join().unwrap());
+        });
+    }
+
+    #[test]
+    fn results_written_in_order() {
+        loom::model(|| {
+            let queue = Arc::new(WorkQueue::new(3));
+            let (tx, rx) = mpsc::channel();
+            for _ in 0..2 {
+                let queue = queue.clone();
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    while let Some(idx) = queue.claim() {
+                        tx.send((idx, idx * 10)).unwrap();
+                    }
+                });
+            }
+            drop(tx);
+
+            let mut reorder = Reorder::new();
+            let mut written = Vec::new();
+            for _ in 0..3 {
+                let (idx, item) = rx.recv().unwrap();
+                reorder.push(idx, item);
+                while let Some((_, item)) = reorder.pop() {
+                    written.push(item);
+                }
+            }
+            assert_eq!(written, [0, 10, 20]);
+        });
+    }
+}
@@ -0,0 +1,88 @@
+//! Kani proof harnesses for the core decoder.
+//!
+//! Run with `cargo kani`. Kani also checks every arithmetic operation for overflow and every
+//! index for bounds along the way, so the harnesses only assert the properties on top.
+
+use crate::{
+    AdcChunkType, AdcDecoder, RawHeader, Window, MAX_PLAIN_CHUNK_LEN, MAX_RUN_CHUNK_LEN,
+    MAX_TWO_BYTE_OFFSET, MAX_TWO_BYTE_RUN_LEN, MIN_RUN_CHUNK_LEN, MIN_THREE_BYTE_RUN_LEN,
+};
+use std::io::Read;
+
+/// Chunk headers never declare sizes or offsets outside the format's limits.
+#[kani::proof]
+fn chunk_header_limits() {
+    let header = RawHeader {
+        bytes: kani::any(),
+        len: 3,
+    };
+    let chunk = header.parse().unwrap();
+
+    let size = usize::from(chunk.size);
+    match chunk.r#type {
+        AdcChunkType::Plain => assert!((1..=MAX_PLAIN_CHUNK_LEN).contains(&size)),
+        AdcChunkType::TwoByte => {
+            assert!((MIN_RUN_CHUNK_LEN..=MAX_TWO_BYTE_RUN_LEN).contains(&size));
+            assert!(chunk.offset <= MAX_TWO_BYTE_OFFSET);
+        }
+        AdcChunkType::ThreeByte => {
+            assert!((MIN_THREE_BYTE_RUN_LEN..=MAX_RUN_CHUNK_LEN).contains(&size))
+        }
+    }
+}
+
+/// The window returns exactly the bytes written, most recent first, for any index.
+#[kani::proof]
+#[kani::unwind(6)]
+fn window_bounds() {
+    let bytes: [u8; 4] = kani::any();
+    let len: usize = kani::any();
+    kani::assume(len <= bytes.len());
+
+    let mut window = Window::new();
+    window.extend(&bytes[..len]);
+
+    let idx: u16 = kani::any();
+    match window.get(idx) {
+        Some(byte) => {
+            assert!((idx as usize) < len);
+            assert_eq!(byte, bytes[len - 1 - idx as usize]);
+        }
+        None => assert!(idx as usize >= len),
+    }
+}
+
+/// A read never returns more than the buffer size, and only returns more than the rest of the
+/// current chunk after parsing the chunks that follow it.
+#[kani::proof]
+#[kani::unwind(8)]
+fn read_within_chunk() {
+    let input: [u8; 5] = kani::any();
+    let buf_len: usize = kani::any();
+    kani::assume(buf_len > 0 && buf_len <= 4);
+
+    let mut decoder = AdcDecoder::new(&input[..]);
+    let mut buf = [0; 4];
+    for _ in 0..3 {
+        if decoder.current_chunk.is_none() {
+            decoder.current_chunk = match decoder.next_nonempty_chunk() {
+                Ok(chunk) => chunk,
+                Err(_) => return,
+            };
+        }
+        let left = decoder
+            .current_chunk
+            .as_ref()
+            .map_or(0, |c| c.size as usize);
+        let before = decoder.total_out;
+        let chunks = decoder.chunks;
+
+        let n = match decoder.read(&mut buf[..buf_len]) {
+            Ok(n) => n,
+            Err(_) => return,
+        };
+        assert!(n <= buf_len);
+        assert!(n <= left || decoder.chunks > chunks);
+        assert_eq!(decoder.total_out, before + n as u64);
+    }
+}
@@ -0,0 +1,17 @@
+//! Types reading compressed or decompressed data from a `Read`.
+//!
+//! This module follows the `read`/`write`/`bufread` layout used by crates like `flate2`,
+//! so code moving between codecs finds the same shapes.
+//!
+//! ```
+//! use adc::read::Decoder;
+//! use std::io::Read;
+//!
+//! let input: &[u8] = &[0x81, 0xab, 0xcd, 0x00, 0x01];
+//! let mut data = Vec::new();
+//! Decoder::new(input).read_to_end(&mut data).unwrap();
+//! assert_eq!(data, [0xab, 0xcd, 0xab, 0xcd, 0xab]);
+//! ```
+
+pub use crate::AdcDecoder as Decoder;
+pub use crate::AdcEncoderReader as Encoder;
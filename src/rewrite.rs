@@ -0,0 +1,291 @@
+//! Chunk-level rewriting of ADC streams.
+//!
+//! [`rewrite`] decodes a stream chunk by chunk and hands every chunk to a callback, which
+//! decides what to emit in its place through a [`ChunkWriter`]: the chunk unchanged, other
+//! literal data, other runs or nothing at all. The writer makes sure the result is a valid
+//! stream, e.g. for redacting regions of disk image data:
+//!
+//! ```
+//! use adc::{rewrite, AdcDecoder};
+//! use std::io::Read;
+//!
+//! // "secret", then a run repeating it
+//! let input: &[u8] = &[0x85, b's', b'e', b'c', b'r', b'e', b't', 0x0c, 0x05];
+//! let output = rewrite(input, Vec::new(), |chunk, writer| {
+//!     if chunk.decompressed_offset == 0 {
+//!         writer.literal(&vec![b'x'; chunk.data.len()])
+//!     } else {
+//!         chunk.copy_to(writer)
+//!     }
+//! })
+//! .unwrap();
+//!
+//! let mut data = Vec::new();
+//! AdcDecoder::new(&output[..]).read_to_end(&mut data).unwrap();
+//! assert_eq!(data, b"xxxxxxxxxxxx");
+//! ```
+
+use crate::{
+    encoder::{min_run_len, write_plain, write_run},
+    inspect::Counter,
+    read_full, read_header, AdcChunkType, AdcError, ChunkContext, Window, MAX_PLAIN_CHUNK_LEN,
+};
+use std::io::{self, prelude::*};
+
+/// A chunk of the stream being rewritten.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub struct InputChunk<'a> {
+    /// Encoding of the chunk
+    pub kind: AdcChunkType,
+    /// Offset of the chunk data in the decompressed input
+    pub decompressed_offset: u64,
+    /// Window offset for run chunks, the run starts `offset + 1` bytes back
+    pub offset: Option<u16>,
+    /// The bytes the chunk decodes to
+    pub data: &'a [u8],
+}
+
+impl InputChunk<'_> {
+    /// Emit the chunk unchanged, literal data as literal data and runs as runs
+    ///
+    /// Runs still refer to the output written so far, so they copy different bytes if
+    /// earlier chunks were changed.
+    pub fn copy_to<W: Write>(&self, writer: &mut ChunkWriter<W>) -> io::Result<()> {
+        match self.offset {
+            Some(offset) => writer.run(offset, self.data.len()),
+            None => writer.literal(self.data),
+        }
+    }
+}
+
+/// Writer emitting a valid ADC stream from literal data and runs.
+///
+/// Adjacent literal data is merged into as few plain chunks as possible, so
+/// [`finish`](ChunkWriter::finish) must be called to write out the last of it.
+pub struct ChunkWriter<W> {
+    output: W,
+    window: Window,
+    literal: Vec<u8>,
+    total_out: u64,
+}
+
+impl<W: Write> ChunkWriter<W> {
+    /// Create a writer emitting the stream to `output`
+    pub fn new(output: W) -> ChunkWriter<W> {
+        ChunkWriter {
+            output,
+            window: Window::new(),
+            literal: Vec::new(),
+            total_out: 0,
+        }
+    }
+
+    /// Emit literal data
+    pub fn literal(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.literal.extend_from_slice(bytes);
+        self.window.extend(bytes);
+        self.total_out += bytes.len() as u64;
+
+        // keep any incomplete chunk, more literal data may follow
+        let full = self.literal.len() - self.literal.len() % MAX_PLAIN_CHUNK_LEN;
+        if full > 0 {
+            write_plain(&mut self.output, &self.literal[..full])?;
+            self.literal.drain(..full);
+        }
+        Ok(())
+    }
+
+    /// Emit a run of `len` bytes copied from `offset + 1` bytes back in the output
+    ///
+    /// Long runs are split into several chunks. Runs too short to be encoded as a chunk
+    /// are emitted as literal data instead.
+    ///
+    /// Fails with `InvalidInput` if the run reaches before the start of the output.
+    pub fn run(&mut self, offset: u16, len: usize) -> io::Result<()> {
+        if len > 0 && usize::from(offset) >= self.window.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "run reaches before the start of the output",
+            ));
+        }
+
+        if len < min_run_len(offset) {
+            let offset = usize::from(offset);
+            let mut bytes = Vec::with_capacity(len);
+            for i in 0..len {
+                let byte = if i > offset {
+                    bytes[i - offset - 1]
+                } else {
+                    self.window.get((offset - i) as u16).unwrap_or(0)
+                };
+                bytes.push(byte);
+            }
+            return self.literal(&bytes);
+        }
+
+        write_plain(&mut self.output, &self.literal)?;
+        self.literal.clear();
+        write_run(&mut self.output, offset, len)?;
+        for _ in 0..len {
+            let byte = self.window.get(offset).unwrap_or(0);
+            self.window.push(byte);
+        }
+        self.total_out += len as u64;
+        Ok(())
+    }
+
+    /// Number of bytes the stream emitted so far decodes to
+    pub fn total_out(&self) -> u64 {
+        self.total_out
+    }
+
+    /// Write out pending literal data and return the output
+    pub fn finish(mut self) -> io::Result<W> {
+        write_plain(&mut self.output, &self.literal)?;
+        Ok(self.output)
+    }
+}
+
+/// Rewrite the stream read from `input` chunk by chunk
+///
+/// `f` is called with every chunk of the input and emits its replacement through the
+/// [`ChunkWriter`]. Emitting nothing drops the chunk. Returns `output` once the input is
+/// exhausted, fails on corrupt input or if `f` fails.
+pub fn rewrite<R, W, F>(input: R, output: W, mut f: F) -> io::Result<W>
+where
+    R: Read,
+    W: Write,
+    F: FnMut(&InputChunk<'_>, &mut ChunkWriter<W>) -> io::Result<()>,
+{
+    let mut input = Counter::new(input);
+    let mut writer = ChunkWriter::new(output);
+    let mut window = Window::new();
+    let mut data = Vec::with_capacity(MAX_PLAIN_CHUNK_LEN);
+    let mut decompressed_offset = 0;
+
+    for index in 0.. {
+        let compressed_offset = input.count;
+        let header = read_header(&mut input)?;
+        if header.len == 0 {
+            break;
+        }
+        let context = || {
+            ChunkContext::new(
+                index,
+                compressed_offset,
+                decompressed_offset,
+                header.as_bytes(),
+            )
+        };
+        let chunk = header
+            .parse()
+            .ok_or_else(|| AdcError::TruncatedChunk(context()))?;
+
+        data.clear();
+        let offset = if chunk.r#type == AdcChunkType::Plain {
+            data.resize(usize::from(chunk.size), 0);
+            if read_full(&mut input, &mut data)? < data.len() {
+                return Err(AdcError::TruncatedChunk(context()).into());
+            }
+            window.extend(&data);
+            None
+        } else {
+            data.resize(usize::from(chunk.size), 0);
+            if !window.copy_run(chunk.offset, &mut data) {
+                return Err(AdcError::InvalidOffset(context()).into());
+            }
+            Some(chunk.offset)
+        };
+
+        f(
+            &InputChunk {
+                kind: chunk.r#type,
+                decompressed_offset,
+                offset,
+                data: &data,
+            },
+            &mut writer,
+        )?;
+        decompressed_offset += data.len() as u64;
+    }
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{generator::StreamGenerator, inspect, AdcDecoder};
+
+    fn decode(compressed: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        AdcDecoder::new(compressed).read_to_end(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn identity() {
+        let stream = StreamGenerator::new(4)
+            .text(20000)
+            .pathological(5000)
+            .finish();
+        let output = rewrite(&stream.compressed[..], Vec::new(), |chunk, writer| {
+            chunk.copy_to(writer)
+        })
+        .unwrap();
+
+        assert_eq!(decode(&output), stream.decompressed);
+        assert!(output.len() <= stream.compressed.len());
+    }
+
+    #[test]
+    fn drop_chunks() {
+        // "abcd" followed by runs of 4, then a final literal
+        let input: &[u8] = &[0x83, b'a', b'b', b'c', b'd', 0x40, 0x00, 0x03, 0x80, b'e'];
+        let output = rewrite(input, Vec::new(), |chunk, writer| {
+            if chunk.kind == AdcChunkType::ThreeByte {
+                Ok(())
+            } else {
+                chunk.copy_to(writer)
+            }
+        })
+        .unwrap();
+
+        assert_eq!(decode(&output), b"abcde");
+        // the literal data is merged into one chunk
+        assert_eq!(inspect(&output[..]).unwrap().chunks.len(), 1);
+    }
+
+    #[test]
+    fn invalid_runs() {
+        let input: &[u8] = &[0x81, b'a', b'b', 0x00, 0x01];
+        let err = rewrite(input, Vec::new(), |chunk, writer| match chunk.offset {
+            Some(_) => chunk.copy_to(writer),
+            None => Ok(()),
+        })
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let err = rewrite(&[0x00, 0x00][..], Vec::new(), |_, _| Ok(())).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn short_runs() {
+        let mut writer = ChunkWriter::new(Vec::new());
+        writer.literal(&[0x11; 0x400]).unwrap();
+        writer.literal(b"xy").unwrap();
+        writer.run(1, 2).unwrap();
+        writer.run(0x401, 3).unwrap();
+        writer.run(0x401, 70).unwrap();
+        assert_eq!(writer.total_out(), 0x400 + 77);
+        let output = writer.finish().unwrap();
+
+        let mut expected = vec![0x11; 0x400];
+        expected.extend_from_slice(b"xyxy");
+        for _ in 0..73 {
+            expected.push(expected[expected.len() - 0x402]);
+        }
+        assert_eq!(decode(&output), expected);
+    }
+}
@@ -0,0 +1,177 @@
+//! Iteration over decompressed data in fixed-size sectors.
+
+use crate::{read_full_counted, SECTOR_SIZE};
+use std::io::{self, prelude::*};
+
+/// A sector of decompressed data.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub struct Sector {
+    /// Index of the sector in the stream
+    pub index: u64,
+    /// Sector contents, zero-padded to the full size if padding is enabled
+    pub data: Vec<u8>,
+    /// Number of bytes that came from the stream
+    pub len: usize,
+    /// True if the stream ended, or reading it failed, before the sector was full
+    pub partial: bool,
+}
+
+/// Iterator yielding the data of a reader as fixed-size [`Sector`]s.
+///
+/// The final sector is shorter than the sector size if the stream does not end on a sector
+/// boundary, unless padding is enabled with [`pad_last`](Sectors::pad_last). If reading
+/// fails within a sector, the data read before the error is returned as a partial sector
+/// and the error by the next call.
+pub struct Sectors<R> {
+    input: R,
+    sector_size: usize,
+    pad_last: bool,
+    index: u64,
+    done: bool,
+    /// Error that ended the partial sector returned last
+    error: Option<io::Error>,
+}
+
+impl<R: Read> Sectors<R> {
+    /// Iterate over `input` in sectors of [`SECTOR_SIZE`] bytes
+    pub fn new(input: R) -> Sectors<R> {
+        Sectors {
+            input,
+            sector_size: SECTOR_SIZE as usize,
+            pad_last: false,
+            index: 0,
+            done: false,
+            error: None,
+        }
+    }
+
+    /// Set the sector size in bytes
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sector_size` is zero.
+    pub fn sector_size(mut self, sector_size: usize) -> Sectors<R> {
+        assert!(sector_size > 0, "sector size must not be zero");
+        self.sector_size = sector_size;
+        self
+    }
+
+    /// Pad a partial final sector with zeros to the full sector size
+    pub fn pad_last(mut self, pad_last: bool) -> Sectors<R> {
+        self.pad_last = pad_last;
+        self
+    }
+}
+
+impl<R: Read> Iterator for Sectors<R> {
+    type Item = io::Result<Sector>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(err) = self.error.take() {
+            return Some(Err(err));
+        }
+        if self.done {
+            return None;
+        }
+
+        let mut data = vec![0; self.sector_size];
+        let mut len = 0;
+        if let Err(err) = read_full_counted(&mut self.input, &mut data, &mut len) {
+            self.done = true;
+            if len == 0 {
+                return Some(Err(err));
+            }
+            self.error = Some(err);
+        }
+
+        let partial = len < data.len();
+        if partial {
+            self.done = true;
+            if len == 0 {
+                return None;
+            }
+            if !self.pad_last {
+                data.truncate(len);
+            }
+        }
+
+        let sector = Sector {
+            index: self.index,
+            data,
+            len,
+            partial,
+        };
+        self.index += 1;
+        Some(Ok(sector))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AdcDecoder;
+
+    // decodes to 11 bytes
+    const INPUT: &[u8] = &[0x83, 0xfe, 0xed, 0xfa, 0xce, 0x00, 0x00, 0x40, 0x00, 0x06];
+
+    #[test]
+    fn partial_last_sector() {
+        let sectors: Vec<_> = Sectors::new(AdcDecoder::new(INPUT))
+            .sector_size(4)
+            .collect::<io::Result<_>>()
+            .unwrap();
+
+        assert_eq!(sectors.len(), 3);
+        assert_eq!(sectors[0].data, [0xfe, 0xed, 0xfa, 0xce]);
+        assert!(!sectors[1].partial);
+        assert_eq!(
+            sectors[2],
+            Sector {
+                index: 2,
+                data: vec![0xed, 0xfa, 0xce],
+                len: 3,
+                partial: true,
+            }
+        );
+    }
+
+    #[test]
+    fn padded_last_sector() {
+        let sectors: Vec<_> = Sectors::new(AdcDecoder::new(INPUT))
+            .sector_size(8)
+            .pad_last(true)
+            .collect::<io::Result<_>>()
+            .unwrap();
+
+        assert_eq!(sectors.len(), 2);
+        assert_eq!(sectors[1].data, [0xed, 0xfa, 0xce, 0, 0, 0, 0, 0]);
+        assert_eq!(sectors[1].len, 3);
+        assert!(sectors[1].partial);
+    }
+
+    #[test]
+    fn error_ends_iteration() {
+        let input: &[u8] = &[0x00, 0x00];
+        let mut sectors = Sectors::new(AdcDecoder::new(input));
+
+        assert!(sectors.next().unwrap().is_err());
+        assert!(sectors.next().is_none());
+    }
+
+    #[test]
+    fn data_before_error() {
+        let input: &[u8] = &[0x83, 0xfe, 0xed, 0xfa, 0xce, 0x81, 0x01, 0x02, 0x00, 0x09];
+        let mut sectors = Sectors::new(AdcDecoder::new(input)).sector_size(4);
+
+        assert_eq!(
+            sectors.next().unwrap().unwrap().data,
+            [0xfe, 0xed, 0xfa, 0xce]
+        );
+        let sector = sectors.next().unwrap().unwrap();
+        assert_eq!((&sector.data[..], sector.len), (&[0x01, 0x02][..], 2));
+        assert!(sector.partial);
+        assert!(sectors.next().unwrap().is_err());
+        assert!(sectors.next().is_none());
+    }
+}
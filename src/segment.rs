@@ -0,0 +1,165 @@
+//! Re-segmentation of streams into independently decodable parts.
+//!
+//! Runs of an ADC stream can reach back into everything decoded before them, so a long
+//! stream can only be decoded from its start. [`resegment`] decodes such a stream and
+//! compresses it again with [restart points](crate::AdcEncoder::restart), so that each
+//! segment can be decoded on its own, in parallel or after fetching just that part.
+
+use crate::{AdcDecoder, AdcEncoder, CompressionLevel};
+use std::io::{self, prelude::*};
+
+/// Part of a re-segmented stream that decodes without the data before it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub struct Segment {
+    /// Offset of the segment in the compressed stream
+    pub compressed_offset: u64,
+    /// Length of the compressed segment
+    pub compressed_len: u64,
+    /// Offset of the segment's data in the decompressed stream
+    pub decompressed_offset: u64,
+    /// Length of the segment's data
+    pub decompressed_len: u64,
+}
+
+impl Segment {
+    /// Get the location of the segment for use with
+    /// [`ParallelExtractor`](crate::ParallelExtractor)
+    ///
+    /// `stream_offset` is the offset of the segmented stream in the file it is stored in.
+    #[cfg(any(unix, windows))]
+    pub fn block(&self, stream_offset: u64) -> crate::Block {
+        crate::Block {
+            input_offset: stream_offset + self.compressed_offset,
+            input_len: self.compressed_len,
+            output_offset: self.decompressed_offset,
+        }
+    }
+}
+
+/// Decompress `input` and write it to `output` again as segments of `segment_size` bytes
+/// of data that decode independently
+///
+/// Returns the segments in stream order, the last one may be shorter. The output is a
+/// single valid stream as well, decoding it as a whole gives the same data as `input`.
+///
+/// ```
+/// use adc::CompressionLevel;
+///
+/// let level = CompressionLevel::Default;
+/// let input = adc::compress_to_vec(&[7; 10_000], level);
+/// let mut output = Vec::new();
+/// let segments = adc::resegment(&input[..], &mut output, 4096, level).unwrap();
+/// assert_eq!(segments.len(), 3);
+///
+/// let last = &segments[2];
+/// let compressed = &output[last.compressed_offset as usize..];
+/// let data = adc::decompress_to_vec(&compressed[..last.compressed_len as usize]).unwrap();
+/// assert_eq!(data.len(), 10_000 - 2 * 4096);
+/// ```
+///
+/// # Panics
+///
+/// Panics if `segment_size` is zero.
+pub fn resegment<R: Read, W: Write>(
+    input: R,
+    output: W,
+    segment_size: u64,
+    level: CompressionLevel,
+) -> io::Result<Vec<Segment>> {
+    assert!(segment_size > 0, "segment size must not be zero");
+    let mut decoder = AdcDecoder::new(input);
+    let mut encoder = AdcEncoder::with_level(output, level);
+    let mut segments = Vec::new();
+
+    loop {
+        let compressed_offset = encoder.total_out();
+        let decompressed_offset = encoder.total_in();
+        let len = io::copy(&mut (&mut decoder).take(segment_size), &mut encoder)?;
+        if len == 0 {
+            break;
+        }
+        encoder.restart()?;
+        segments.push(Segment {
+            compressed_offset,
+            compressed_len: encoder.total_out() - compressed_offset,
+            decompressed_offset,
+            decompressed_len: len,
+        });
+    }
+    encoder.try_finish()?;
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decompress_to_vec, generator::StreamGenerator};
+
+    #[test]
+    fn independent_segments() {
+        let stream = StreamGenerator::new(13)
+            .text(100_000)
+            .zeros(30_000)
+            .random(10_000)
+            .finish();
+        let mut output = Vec::new();
+        let segments = resegment(
+            &stream.compressed[..],
+            &mut output,
+            32 * 1024,
+            CompressionLevel::Default,
+        )
+        .unwrap();
+
+        assert_eq!(segments.len(), 5);
+        assert_eq!(decompress_to_vec(&output).unwrap(), stream.decompressed);
+        let mut next = (0, 0);
+        for segment in &segments {
+            assert_eq!(
+                (segment.compressed_offset, segment.decompressed_offset),
+                next
+            );
+            let start = segment.compressed_offset as usize;
+            let data =
+                decompress_to_vec(&output[start..][..segment.compressed_len as usize]).unwrap();
+            let start = segment.decompressed_offset as usize;
+            assert!(data == stream.decompressed[start..][..segment.decompressed_len as usize]);
+            next = (
+                segment.compressed_offset + segment.compressed_len,
+                segment.decompressed_offset + segment.decompressed_len,
+            );
+        }
+        assert_eq!(
+            next,
+            (output.len() as u64, stream.decompressed.len() as u64)
+        );
+
+        let segments = resegment(&[][..], &mut Vec::new(), 10, CompressionLevel::Default);
+        assert!(segments.unwrap().is_empty());
+        let err = resegment(&[0x00, 0x00][..], io::sink(), 10, CompressionLevel::Default);
+        assert_eq!(err.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    // the extractor's pool only runs inside loom models with adc_loom
+    #[cfg(all(any(unix, windows), not(adc_loom)))]
+    #[test]
+    fn parallel_extraction() {
+        use crate::{extent::tests::temp_file, ParallelExtractor};
+
+        let data = StreamGenerator::new(14).text(50_000).finish().decompressed;
+        let compressed = crate::compress_to_vec(&data, CompressionLevel::Fastest);
+        let mut file = vec![0xee; 10];
+        let segments =
+            resegment(&compressed[..], &mut file, 8192, CompressionLevel::Default).unwrap();
+        let blocks: Vec<_> = segments.iter().map(|s| s.block(10)).collect();
+
+        let source = temp_file("resegment", &file);
+        let mut output = Vec::new();
+        ParallelExtractor::new()
+            .threads(3)
+            .extract_to_writer(&source, &blocks, &mut output)
+            .unwrap();
+        assert!(output == data);
+    }
+}
@@ -0,0 +1,147 @@
+//! Decoder for streams held completely in memory.
+
+use crate::{AdcChunk, AdcChunkType, AdcError, ChunkContext};
+use std::{cmp, io};
+
+/// Decoder for compressed data that is already a slice, like a block read from a DMG.
+///
+/// Headers are parsed straight out of the input and plain data is copied directly into the
+/// output, which also serves as the window for runs. Nothing is read through `Read` or copied
+/// into a separate window, so this is the fastest way to decode a block into a buffer of
+/// known size.
+///
+/// ```
+/// use adc::AdcSliceDecoder;
+///
+/// let mut output = [0; 8];
+/// let len = AdcSliceDecoder::decompress_into(&[0x81, 0xab, 0xcd, 0x00, 0x01], &mut output)
+///     .unwrap();
+/// assert_eq!(output[..len], [0xab, 0xcd, 0xab, 0xcd, 0xab]);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct AdcSliceDecoder;
+
+impl AdcSliceDecoder {
+    /// Get the decompressed length of `input` from its chunk headers
+    ///
+    /// Only the headers are looked at, so this is cheap and suited for sizing the output of
+    /// [`decompress_into`](AdcSliceDecoder::decompress_into). A truncated last chunk counts
+    /// with its full length.
+    ///
+    /// ```
+    /// use adc::AdcSliceDecoder;
+    ///
+    /// assert_eq!(AdcSliceDecoder::decompressed_len(&[0x81, 0xab, 0xcd, 0x00, 0x01]), 5);
+    /// ```
+    pub fn decompressed_len(input: &[u8]) -> u64 {
+        let mut pos = 0;
+        let mut len = 0;
+        while let Some((chunk, header_len)) = input.get(pos..).and_then(AdcChunk::parse) {
+            pos += header_len;
+            if chunk.r#type == AdcChunkType::Plain {
+                pos += usize::from(chunk.size);
+            }
+            len += u64::from(chunk.size);
+        }
+        len
+    }
+
+    /// Decompress all of `input` into the start of `output`
+    ///
+    /// Returns the number of bytes written. Fails with [`AdcError::OutputLimit`] if the data
+    /// does not fit into `output`, which is left partially written on errors.
+    pub fn decompress_into(input: &[u8], output: &mut [u8]) -> io::Result<usize> {
+        let mut pos = 0;
+        let mut len = 0;
+        let mut index = 0;
+        while pos < input.len() {
+            let start = pos;
+            let context = |header_end: usize| {
+                let header_end = cmp::min(header_end, input.len());
+                ChunkContext::new(index, start as u64, len as u64, &input[start..header_end])
+            };
+
+            let (chunk, header_len) = match AdcChunk::parse(&input[pos..]) {
+                Some(chunk) => chunk,
+                None => return Err(AdcError::TruncatedChunk(context(input.len())).into()),
+            };
+            pos += header_len;
+            let size = usize::from(chunk.size);
+            let end = len + size;
+            if end > output.len() {
+                return Err(AdcError::OutputLimit(output.len() as u64).into());
+            }
+
+            if chunk.r#type == AdcChunkType::Plain {
+                let data = match input.get(pos..pos + size) {
+                    Some(data) => data,
+                    None => return Err(AdcError::TruncatedChunk(context(pos)).into()),
+                };
+                output[len..end].copy_from_slice(data);
+                pos += size;
+            } else {
+                let distance = usize::from(chunk.offset) + 1;
+                if distance > len {
+                    return Err(AdcError::InvalidOffset(context(pos)).into());
+                }
+                // copying from the start of the run doubles the repeated data with every
+                // step, the copied length stays a multiple of the distance
+                let src = len - distance;
+                let mut dst = len;
+                while dst < end {
+                    let n = cmp::min(end - dst, dst - src);
+                    output.copy_within(src..src + n, dst);
+                    dst += n;
+                }
+            }
+            len = end;
+            index += 1;
+        }
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::StreamGenerator;
+
+    #[test]
+    fn matches_decoder() {
+        let stream = StreamGenerator::new(22)
+            .text(50_000)
+            .zeros(10_000)
+            .random(5000)
+            .pathological(5000)
+            .finish();
+        assert_eq!(
+            AdcSliceDecoder::decompressed_len(&stream.compressed),
+            stream.decompressed.len() as u64
+        );
+        let mut output = vec![0; stream.decompressed.len() + 10];
+        let len = AdcSliceDecoder::decompress_into(&stream.compressed, &mut output).unwrap();
+        assert!(output[..len] == stream.decompressed[..]);
+
+        let short = &mut output[..stream.decompressed.len() - 1];
+        let err = AdcSliceDecoder::decompress_into(&stream.compressed, short).unwrap_err();
+        assert!(matches!(AdcError::from(err), AdcError::OutputLimit(_)));
+    }
+
+    #[test]
+    fn errors() {
+        let mut output = [0; 16];
+        let err = AdcSliceDecoder::decompress_into(&[0x81, 0xab, 0xcd, 0x00, 0x05], &mut output)
+            .unwrap_err();
+        let expected = "invalid chunk offset in chunk 1 at compressed offset 3, \
+                        decompressed offset 2 (header 00 05)";
+        assert_eq!(err.to_string(), expected);
+
+        let err = AdcSliceDecoder::decompress_into(&[0x83, 0xfe, 0xed], &mut output).unwrap_err();
+        assert!(matches!(AdcError::from(err), AdcError::TruncatedChunk(c) if c.header == [0x83]));
+        let err = AdcSliceDecoder::decompress_into(&[0x81, 0xab, 0xcd, 0x40, 0x00], &mut output)
+            .unwrap_err();
+        assert!(
+            matches!(AdcError::from(err), AdcError::TruncatedChunk(c) if c.header == [0x40, 0x00])
+        );
+    }
+}
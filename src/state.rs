@@ -0,0 +1,231 @@
+//! Snapshots of the decoder for suspending and resuming decoding.
+
+use crate::{AdcDecoder, ChunkStart, RawHeader, Window};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read};
+
+/// Version of the state format, increased when the meaning of the fields changes.
+const VERSION: u32 = 1;
+
+/// Everything an [`AdcDecoder`] needs to continue decoding, apart from its input.
+///
+/// Taken with [`AdcDecoder::state`] and turned back into a decoder with
+/// [`AdcDecoder::resume`], given an input positioned at
+/// [`compressed_offset`](DecoderState::compressed_offset). With the `serde` feature the state
+/// can be serialized, so decoding can continue in another process. Serialized states carry a
+/// `version` field, states of other versions are rejected by `resume`. The damaged regions
+/// found in recovery mode and the [`stats`](AdcDecoder::stats) are not part of it.
+///
+/// ```
+/// use adc::AdcDecoder;
+/// use std::io::Read;
+///
+/// let input: &[u8] = &[0x81, 0xab, 0xcd, 0x00, 0x01];
+/// let mut d = AdcDecoder::new(input);
+/// let mut data = vec![0; 3];
+/// d.read_exact(&mut data).unwrap();
+/// let state = d.state();
+///
+/// let rest = &input[state.compressed_offset() as usize..];
+/// AdcDecoder::resume(rest, &state).unwrap().read_to_end(&mut data).unwrap();
+/// assert_eq!(data, [0xab, 0xcd, 0xab, 0xcd, 0xab]);
+/// ```
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DecoderState {
+    /// Version of the state format, [`VERSION`] for states taken by this crate
+    version: u32,
+    total_in: u64,
+    total_out: u64,
+    chunks: u64,
+    /// Position and header of the chunk being decoded, for error context
+    chunk_index: u64,
+    chunk_compressed_offset: u64,
+    chunk_decompressed_offset: u64,
+    chunk_header: Vec<u8>,
+    /// Bytes left in the chunk being decoded, 0 at a chunk boundary
+    chunk_remaining: u8,
+    /// Offset of the run being decoded, changed in recovery mode
+    run_offset: u16,
+    /// Header of the next chunk read before the input failed
+    partial_header: Vec<u8>,
+    /// Window of output, oldest byte first
+    window: Vec<u8>,
+    /// Decompressed data buffered for `BufRead` but not consumed yet
+    buffered: Vec<u8>,
+    recover: bool,
+    max_output: Option<u64>,
+    expected_size: Option<u64>,
+}
+
+impl DecoderState {
+    /// Get the offset in the compressed stream to resume reading the input at
+    pub fn compressed_offset(&self) -> u64 {
+        self.total_in
+    }
+
+    /// Get the offset in the decompressed data the resumed decoder continues at
+    pub fn decompressed_offset(&self) -> u64 {
+        self.total_out - self.buffered.len() as u64
+    }
+}
+
+impl<R: Read> AdcDecoder<R> {
+    /// Take a snapshot of the decoding position, see [`DecoderState`]
+    pub fn state(&self) -> DecoderState {
+        let chunk = self.current_chunk.as_ref();
+        DecoderState {
+            version: VERSION,
+            total_in: self.total_in,
+            total_out: self.total_out,
+            chunks: self.chunks,
+            chunk_index: self.start.index,
+            chunk_compressed_offset: self.start.compressed_offset,
+            chunk_decompressed_offset: self.start.decompressed_offset,
+            chunk_header: self.start.header.as_bytes().to_vec(),
+            chunk_remaining: chunk.map_or(0, |chunk| chunk.size),
+            run_offset: chunk.map_or(0, |chunk| chunk.offset),
+            partial_header: self.header.as_bytes().to_vec(),
+            window: self.window.snapshot(),
+            buffered: self.buf[self.pos..self.filled].to_vec(),
+            recover: self.recover,
+            max_output: self.max_output,
+            expected_size: self.expected_size,
+        }
+    }
+
+    /// Create a decoder continuing where `state` was taken
+    ///
+    /// `input` has to be positioned at the
+    /// [`compressed_offset`](DecoderState::compressed_offset) of the state. Fails with
+    /// `InvalidInput` if the state is inconsistent, which can only happen if it was modified
+    /// after serializing it, or if it was serialized by a version of the crate with another
+    /// state format.
+    pub fn resume(input: R, state: &DecoderState) -> io::Result<AdcDecoder<R>> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidInput, "invalid decoder state");
+        if state.version != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unsupported decoder state version {}", state.version),
+            ));
+        }
+
+        let header = raw_header(&state.chunk_header).ok_or_else(invalid)?;
+        let current_chunk = match (header.parse(), state.chunk_remaining) {
+            (_, 0) => None,
+            (Some(mut chunk), remaining) if remaining <= chunk.size => {
+                chunk.size = remaining;
+                chunk.offset = state.run_offset;
+                Some(chunk)
+            }
+            _ => return Err(invalid()),
+        };
+        let partial_header = raw_header(&state.partial_header).ok_or_else(invalid)?;
+        if partial_header.parse().is_some() || state.buffered.len() as u64 > state.total_out {
+            return Err(invalid());
+        }
+
+        trace_event!(
+            debug,
+            compressed_offset = state.total_in,
+            window_len = state.window.len(),
+            "decoder resumed"
+        );
+        let mut window = Window::new();
+        window.extend(&state.window);
+        let filled = state.buffered.len();
+        Ok(AdcDecoder {
+            current_chunk,
+            header: partial_header,
+            window,
+            total_in: state.total_in,
+            total_out: state.total_out,
+            chunks: state.chunks,
+            start: ChunkStart {
+                index: state.chunk_index,
+                compressed_offset: state.chunk_compressed_offset,
+                decompressed_offset: state.chunk_decompressed_offset,
+                header,
+            },
+            recover: state.recover,
+            max_output: state.max_output,
+            expected_size: state.expected_size,
+            buf: state.buffered.clone(),
+            pos: 0,
+            filled,
+            ..AdcDecoder::new(input)
+        })
+    }
+}
+
+/// Turn saved header bytes back into a header, `None` if there are too many.
+fn raw_header(bytes: &[u8]) -> Option<RawHeader> {
+    let mut header = RawHeader::default();
+    header.bytes.get_mut(..bytes.len())?.copy_from_slice(bytes);
+    header.len = bytes.len() as u8;
+    Some(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::StreamGenerator;
+    use std::io::BufRead;
+
+    #[test]
+    fn resume_anywhere() {
+        let stream = StreamGenerator::new(20).text(20_000).zeros(3000).finish();
+        for &split in &[0, 1, 2, 3, 100, 9999, stream.decompressed.len()] {
+            let mut d = AdcDecoder::new(&stream.compressed[..]);
+            let mut data = vec![0; split];
+            d.read_exact(&mut data).unwrap();
+            // leave some data in the buffer
+            d.fill_buf().unwrap();
+            let state = d.state();
+            assert_eq!(state.decompressed_offset(), split as u64);
+
+            let rest = &stream.compressed[state.compressed_offset() as usize..];
+            let mut d = AdcDecoder::resume(rest, &state).unwrap();
+            d.read_to_end(&mut data).unwrap();
+            assert!(data == stream.decompressed, "split {}", split);
+            assert_eq!(d.total_in(), stream.compressed.len() as u64);
+        }
+    }
+
+    #[test]
+    fn invalid_state() {
+        let input: &[u8] = &[0x83, 0xfe, 0xed, 0xfa, 0xce];
+        let mut d = AdcDecoder::new(input);
+        d.read_exact(&mut [0; 2]).unwrap();
+        let mut state = d.state();
+        state.chunk_remaining = 5;
+        assert!(AdcDecoder::resume(input, &state).is_err());
+        state.chunk_header = vec![0; 4];
+        assert!(AdcDecoder::resume(input, &state).is_err());
+
+        let mut state = d.state();
+        state.version = 2;
+        let err = AdcDecoder::resume(input, &state).err().unwrap();
+        assert_eq!(err.to_string(), "unsupported decoder state version 2");
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn serde_round_trip() {
+        let input: &[u8] = &[0x83, 0xfe, 0xed, 0xfa, 0xce, 0x00, 0x01];
+        let mut d = AdcDecoder::new(input).max_output_size(100);
+        d.read_exact(&mut [0; 2]).unwrap();
+        let state = d.state();
+
+        let json = serde_json::to_string(&state).unwrap();
+        assert!(json.starts_with("{\"version\":1,"));
+        let state: DecoderState = serde_json::from_str(&json).unwrap();
+        let mut data = Vec::new();
+        AdcDecoder::resume(&input[3..], &state)
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap();
+        assert_eq!(data, [0xfa, 0xce, 0xfa, 0xce, 0xfa]);
+    }
+}
@@ -0,0 +1,114 @@
+//! Statistics about the chunks of a stream.
+
+use crate::{AdcChunkType, StreamMap};
+
+/// Counts of the chunk types and bytes in a stream.
+///
+/// Collected while decoding by [`AdcDecoder::stats`](crate::AdcDecoder::stats) or from a
+/// chunk map with [`StreamMap::stats`]. A chunk counts once its header was read, with all of
+/// its bytes.
+///
+/// ```
+/// use adc::AdcDecoder;
+/// use std::io::Read;
+///
+/// let input: &[u8] = &[0x81, 0xab, 0xcd, 0x00, 0x01];
+/// let mut d = AdcDecoder::new(input);
+/// d.read_to_end(&mut Vec::new()).unwrap();
+/// let stats = d.stats();
+/// assert_eq!((stats.plain_chunks, stats.two_byte_chunks), (1, 1));
+/// assert_eq!((stats.literal_bytes, stats.match_bytes), (2, 3));
+/// assert_eq!(stats.ratio(), 1.0);
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[non_exhaustive]
+pub struct DecodeStats {
+    /// Number of plain chunks
+    pub plain_chunks: u64,
+    /// Number of runs with a two byte header
+    pub two_byte_chunks: u64,
+    /// Number of runs with a three byte header
+    pub three_byte_chunks: u64,
+    /// Decompressed bytes copied from plain chunks
+    pub literal_bytes: u64,
+    /// Decompressed bytes copied from the window by runs
+    pub match_bytes: u64,
+    /// Compressed bytes of the chunks, headers and literal bytes
+    pub compressed_len: u64,
+}
+
+impl DecodeStats {
+    /// Get the total number of chunks
+    pub fn chunks(&self) -> u64 {
+        self.plain_chunks + self.two_byte_chunks + self.three_byte_chunks
+    }
+
+    /// Get the number of decompressed bytes
+    pub fn decompressed_len(&self) -> u64 {
+        self.literal_bytes + self.match_bytes
+    }
+
+    /// Get the compressed size relative to the decompressed size, 0 for an empty stream
+    pub fn ratio(&self) -> f64 {
+        match self.decompressed_len() {
+            0 => 0.0,
+            len => self.compressed_len as f64 / len as f64,
+        }
+    }
+
+    /// Count a chunk of `compressed_len` bytes including its header.
+    pub(crate) fn record(&mut self, kind: AdcChunkType, compressed_len: u64, len: u64) {
+        self.compressed_len += compressed_len;
+        match kind {
+            AdcChunkType::Plain => {
+                self.plain_chunks += 1;
+                self.literal_bytes += len;
+            }
+            AdcChunkType::TwoByte => {
+                self.two_byte_chunks += 1;
+                self.match_bytes += len;
+            }
+            AdcChunkType::ThreeByte => {
+                self.three_byte_chunks += 1;
+                self.match_bytes += len;
+            }
+        }
+    }
+}
+
+impl StreamMap {
+    /// Count the chunk types and bytes of the stream
+    pub fn stats(&self) -> DecodeStats {
+        let mut stats = DecodeStats::default();
+        for chunk in &self.chunks {
+            stats.record(chunk.kind, chunk.compressed_len, chunk.decompressed_len);
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{generator::StreamGenerator, inspect, AdcDecoder};
+    use std::io::Read;
+
+    #[test]
+    fn decoder_matches_map() {
+        let stream = StreamGenerator::new(25)
+            .text(20_000)
+            .zeros(5000)
+            .random(1000)
+            .finish();
+        let mut d = AdcDecoder::new(&stream.compressed[..]);
+        d.read_to_end(&mut Vec::new()).unwrap();
+        let stats = d.stats();
+        let map = inspect(&stream.compressed[..]).unwrap();
+
+        assert_eq!(stats, map.stats());
+        assert_eq!(stats.chunks(), map.chunks.len() as u64);
+        assert_eq!(stats.compressed_len, stream.compressed.len() as u64);
+        assert_eq!(stats.decompressed_len(), stream.decompressed.len() as u64);
+        assert!(stats.plain_chunks > 0 && stats.match_bytes > 0);
+        assert!(stats.ratio() < 1.0);
+    }
+}
@@ -0,0 +1,354 @@
+//! Push-based decompression of byte slices, without `std::io`.
+//!
+//! The caller owns the buffers and feeds them to [`decompress`] piece by piece, all state
+//! in between lives in an [`AdcState`]. This suits event loops and FFI callers that only
+//! have byte slices. The API follows the low-level interface of `miniz_oxide`.
+//!
+//! ADC streams have no end marker, an empty `input` tells [`decompress`] that the stream
+//! ended.
+//!
+//! ```
+//! use adc::stream::{decompress, AdcState, Status};
+//!
+//! let input: &[u8] = &[0x81, 0xab, 0xcd, 0x00, 0x01];
+//! let mut state = AdcState::new();
+//! let mut output = [0; 3];
+//!
+//! assert_eq!(decompress(&mut state, input, &mut output), (Status::NeedsMoreOutput, 5, 3));
+//! assert_eq!(output, [0xab, 0xcd, 0xab]);
+//! assert_eq!(decompress(&mut state, &[], &mut output), (Status::Done, 0, 2));
+//! assert_eq!(output[..2], [0xcd, 0xab]);
+//! ```
+
+#[cfg(feature = "std")]
+use crate::AdcError;
+use crate::{header_len, AdcChunk, AdcChunkType, ChunkStart, RawHeader, Window, WINDOW_SIZE};
+use core::cmp;
+
+/// Outcome of a call to [`decompress`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub enum Status {
+    /// The stream ended at a chunk boundary, all output was written
+    Done,
+    /// All of the input was consumed, pass more to continue
+    NeedsMoreInput,
+    /// The output is full and more decompressed data is pending
+    NeedsMoreOutput,
+    /// A run referenced data before the start of the output
+    InvalidOffset,
+    /// The stream ended in the middle of a chunk
+    Truncated,
+    /// A run reached further back than the window of the state
+    WindowTooSmall,
+}
+
+impl Status {
+    /// Check if the stream is corrupt
+    pub fn is_error(self) -> bool {
+        matches!(
+            self,
+            Status::InvalidOffset | Status::Truncated | Status::WindowTooSmall
+        )
+    }
+}
+
+/// Decompression state carried between calls to [`decompress`].
+///
+/// Holds the window of previous output and a partially processed chunk, including header
+/// bytes split across input slices.
+///
+/// The window keeps the last `N` bytes of output, the [`WINDOW_SIZE`] needed for any stream
+/// by default. A smaller window saves memory if the streams are known to only use short run
+/// offsets, runs reaching further back fail with [`Status::WindowTooSmall`].
+///
+/// ```
+/// use adc::stream::{decompress, AdcState, Status};
+///
+/// let mut state = AdcState::<2>::sized();
+/// let mut output = [0; 8];
+/// let (status, _, written) = decompress(&mut state, &[0x81, 0xab, 0xcd, 0x00, 0x01], &mut output);
+/// assert_eq!((status, written), (Status::NeedsMoreInput, 5));
+///
+/// let (status, _, _) = decompress(&mut state, &[0x00, 0x02], &mut output);
+/// assert_eq!(status, Status::WindowTooSmall);
+/// ```
+#[derive(Clone, Debug)]
+pub struct AdcState<const N: usize = WINDOW_SIZE> {
+    window: Window<N>,
+    header: RawHeader,
+    chunk: Option<AdcChunk>,
+    total_in: u64,
+    total_out: u64,
+    chunks: u64,
+    start: ChunkStart,
+}
+
+impl AdcState {
+    /// Create the state for the start of a stream
+    pub fn new() -> AdcState {
+        AdcState::sized()
+    }
+}
+
+impl<const N: usize> AdcState<N> {
+    /// Create the state for the start of a stream, with a window of `N` bytes
+    pub fn sized() -> AdcState<N> {
+        AdcState {
+            window: Window::sized(),
+            header: RawHeader::default(),
+            chunk: None,
+            total_in: 0,
+            total_out: 0,
+            chunks: 0,
+            start: ChunkStart::default(),
+        }
+    }
+
+    /// Preload the window with `dictionary`, as if it was output before the start of the
+    /// stream
+    ///
+    /// Runs of the stream can then refer back into the last `N` bytes of the dictionary,
+    /// like streams continuing the data of a previous block. Replaces any dictionary set
+    /// before.
+    ///
+    /// ```
+    /// use adc::stream::{decompress, AdcState, Status};
+    ///
+    /// let mut state = AdcState::new();
+    /// state.set_dictionary(b"abc");
+    /// let mut output = [0; 4];
+    /// assert_eq!(decompress(&mut state, &[0x04, 0x02], &mut output), (Status::NeedsMoreInput, 2, 4));
+    /// assert_eq!(output, *b"abca");
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if decompression already started.
+    pub fn set_dictionary(&mut self, dictionary: &[u8]) {
+        assert!(
+            self.total_in == 0,
+            "the dictionary has to be set before decompressing"
+        );
+        self.window.clear();
+        self.window.extend(dictionary);
+    }
+
+    /// Get the number of compressed bytes consumed so far
+    pub fn total_in(&self) -> u64 {
+        self.total_in
+    }
+
+    /// Get the number of decompressed bytes written so far
+    pub fn total_out(&self) -> u64 {
+        self.total_out
+    }
+
+    /// Turn an error status into an error locating the corrupt chunk
+    #[cfg(feature = "std")]
+    pub(crate) fn error(&self, status: Status) -> AdcError {
+        debug_assert!(status.is_error(), "{:?} is not an error", status);
+        let mut start = self.start;
+        if self.header.len > 0 {
+            start.header = self.header;
+        }
+        match status {
+            Status::Truncated => AdcError::TruncatedChunk(start.context()),
+            _ => AdcError::InvalidOffset(start.context()),
+        }
+    }
+
+    /// Take the next chunk header from `input`, returns the number of bytes consumed.
+    fn fill_header(&mut self, input: &[u8]) -> usize {
+        let first = match self.header.len {
+            0 => match input.first() {
+                Some(&byte) => {
+                    self.start = ChunkStart {
+                        index: self.chunks,
+                        compressed_offset: self.total_in,
+                        decompressed_offset: self.total_out,
+                        header: RawHeader::default(),
+                    };
+                    self.chunks += 1;
+                    byte
+                }
+                None => return 0,
+            },
+            _ => self.header.bytes[0],
+        };
+        let filled = usize::from(self.header.len);
+        let len = cmp::min(header_len(first) - filled, input.len());
+        self.header.bytes[filled..][..len].copy_from_slice(&input[..len]);
+        self.header.len += len as u8;
+        self.total_in += len as u64;
+
+        if let Some(chunk) = self.header.parse() {
+            self.chunk = Some(chunk);
+            self.start.header = self.header;
+            self.header = RawHeader::default();
+        }
+        len
+    }
+}
+
+impl Default for AdcState {
+    fn default() -> AdcState {
+        AdcState::new()
+    }
+}
+
+/// Decompress from `input` into `output`
+///
+/// Returns the status along with the number of bytes consumed from `input` and written to
+/// `output`. Unconsumed input must be passed again on the next call. An empty `input`
+/// signals the end of the stream, which is [`Status::Done`] at a chunk boundary and
+/// [`Status::Truncated`] otherwise.
+///
+/// After an error the state stays at the corrupt chunk, further calls fail again.
+pub fn decompress<const N: usize>(
+    state: &mut AdcState<N>,
+    input: &[u8],
+    output: &mut [u8],
+) -> (Status, usize, usize) {
+    let mut in_pos = 0;
+    let mut out_pos = 0;
+
+    loop {
+        if state.chunk.is_none() {
+            in_pos += state.fill_header(&input[in_pos..]);
+        }
+        let chunk = match state.chunk {
+            Some(ref mut chunk) => chunk,
+            None if !input.is_empty() => return (Status::NeedsMoreInput, in_pos, out_pos),
+            None if state.header.len == 0 => return (Status::Done, in_pos, out_pos),
+            None => return (Status::Truncated, in_pos, out_pos),
+        };
+
+        let out = &mut output[out_pos..];
+        if out.is_empty() {
+            return (Status::NeedsMoreOutput, in_pos, out_pos);
+        }
+
+        let mut len = cmp::min(usize::from(chunk.size), out.len());
+        if chunk.r#type == AdcChunkType::Plain {
+            len = cmp::min(len, input.len() - in_pos);
+            if len == 0 {
+                let status = if input.is_empty() {
+                    Status::Truncated
+                } else {
+                    Status::NeedsMoreInput
+                };
+                return (status, in_pos, out_pos);
+            }
+            out[..len].copy_from_slice(&input[in_pos..][..len]);
+            state.window.extend(&out[..len]);
+            state.total_in += len as u64;
+            in_pos += len;
+        } else {
+            if usize::from(chunk.offset) >= N {
+                return (Status::WindowTooSmall, in_pos, out_pos);
+            }
+            // the window only grows, if the first byte of the run is there all of it is
+            if !state.window.copy_run(chunk.offset, &mut out[..len]) {
+                return (Status::InvalidOffset, in_pos, out_pos);
+            }
+        }
+        out_pos += len;
+        state.total_out += len as u64;
+
+        chunk.size -= len as u8;
+        if chunk.size == 0 {
+            state.chunk = None;
+        }
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+    use crate::generator::StreamGenerator;
+
+    /// Decompress `input` feeding at most `in_step` bytes and `out_step` bytes of room.
+    fn decompress_steps(input: &[u8], in_step: usize, out_step: usize) -> (Status, Vec<u8>) {
+        let mut state = AdcState::new();
+        let mut data = Vec::new();
+        let mut buf = vec![0; out_step];
+        let mut pos = 0;
+        loop {
+            let end = cmp::min(pos + in_step, input.len());
+            let (status, read, written) = decompress(&mut state, &input[pos..end], &mut buf);
+            pos += read;
+            data.extend_from_slice(&buf[..written]);
+            match status {
+                Status::NeedsMoreInput | Status::NeedsMoreOutput => {}
+                status => return (status, data),
+            }
+        }
+    }
+
+    #[test]
+    fn split_buffers() {
+        let stream = StreamGenerator::new(12)
+            .text(20_000)
+            .zeros(5000)
+            .random(3000)
+            .finish();
+
+        for &(in_step, out_step) in &[(1, 1), (2, 3), (7, 64), (4096, 1), (1 << 20, 1 << 20)] {
+            let (status, data) = decompress_steps(&stream.compressed, in_step, out_step);
+            assert_eq!(status, Status::Done, "{} {}", in_step, out_step);
+            assert!(data == stream.decompressed, "{} {}", in_step, out_step);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        // offset is too big
+        let (status, data) = decompress_steps(&[0x81, 0xab, 0xcd, 0x40, 0x00, 0x05], 2, 10);
+        assert_eq!(status, Status::InvalidOffset);
+        assert_eq!(data, [0xab, 0xcd]);
+        assert!(status.is_error());
+
+        // missing 2nd header byte and missing plain bytes
+        for input in &[&[0x81, 0xab, 0xcd, 0x00][..], &[0x83, 0xfe, 0xed]] {
+            let (status, _) = decompress_steps(input, 1, 10);
+            assert_eq!(status, Status::Truncated);
+        }
+
+        let mut state = AdcState::new();
+        let input = [0x81, 0xab, 0xcd, 0x40, 0x00, 0x05];
+        let (status, _, _) = decompress(&mut state, &input, &mut [0; 10]);
+        assert_eq!(status, Status::InvalidOffset);
+        assert_eq!(state.total_in(), 6);
+        assert_eq!(state.total_out(), 2);
+        assert_eq!(
+            state.error(status).to_string(),
+            "invalid chunk offset in chunk 1 at compressed offset 3, decompressed offset 2 \
+             (header 40 00 05)"
+        );
+
+        let (status, data) = decompress_steps(&[], 1, 10);
+        assert_eq!(status, Status::Done);
+        assert!(data.is_empty());
+    }
+
+    #[test]
+    fn dictionary() {
+        // the dictionary is longer than the window, only its end is kept
+        let mut state = AdcState::<4>::sized();
+        state.set_dictionary(b"xxabcd");
+        let mut output = [0; 8];
+        let input = [0x00, 0x03, 0x00, 0x04];
+        let (status, _, written) = decompress(&mut state, &input, &mut output);
+        assert_eq!(status, Status::WindowTooSmall);
+        assert_eq!(output[..written], *b"abc");
+    }
+
+    #[test]
+    #[should_panic(expected = "before decompressing")]
+    fn dictionary_after_start() {
+        let mut state = AdcState::new();
+        decompress(&mut state, &[0x80], &mut [0; 1]);
+        state.set_dictionary(b"abc");
+    }
+}
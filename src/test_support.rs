@@ -0,0 +1,228 @@
+//! Builders for ADC chunks and streams, for tests of code consuming ADC data.
+//!
+//! Available with the `test-support` feature. The chunk functions encode exactly the chunk
+//! asked for, [`StreamBuilder`] assembles whole streams and keeps track of the output they
+//! decode to. All of them panic on parameters the format can't encode.
+//!
+//! ```
+//! use adc::{test_support::StreamBuilder, AdcDecoder};
+//! use std::io::Read;
+//!
+//! let stream = StreamBuilder::new().plain(b"abc").run(2, 6).finish();
+//! assert_eq!(stream.decompressed, b"abcabcabc");
+//!
+//! let mut data = Vec::new();
+//! AdcDecoder::new(&stream.compressed[..]).read_to_end(&mut data).unwrap();
+//! assert_eq!(data, stream.decompressed);
+//! ```
+
+use crate::{
+    encoder::{min_run_len, write_run},
+    generator::GeneratedStream,
+    MAX_PLAIN_CHUNK_LEN, MAX_RUN_CHUNK_LEN, MAX_TWO_BYTE_OFFSET, MAX_TWO_BYTE_RUN_LEN,
+    MIN_RUN_CHUNK_LEN, MIN_THREE_BYTE_RUN_LEN,
+};
+
+/// Encode a plain chunk holding `bytes`
+///
+/// # Panics
+///
+/// Panics unless `bytes` holds 1 to 128 bytes.
+pub fn plain_chunk(bytes: &[u8]) -> Vec<u8> {
+    assert!(
+        (1..=MAX_PLAIN_CHUNK_LEN).contains(&bytes.len()),
+        "plain chunks hold 1 to 128 bytes"
+    );
+    let mut chunk = vec![0x80 | (bytes.len() - 1) as u8];
+    chunk.extend_from_slice(bytes);
+    chunk
+}
+
+/// Encode a two byte run chunk copying `len` bytes from `offset + 1` bytes back
+///
+/// # Panics
+///
+/// Panics unless `len` is 3 to 18 and `offset` at most 0x3ff.
+pub fn two_byte_chunk(offset: u16, len: usize) -> Vec<u8> {
+    assert!(
+        (MIN_RUN_CHUNK_LEN..=MAX_TWO_BYTE_RUN_LEN).contains(&len),
+        "two byte runs are 3 to 18 bytes"
+    );
+    assert!(
+        offset <= MAX_TWO_BYTE_OFFSET,
+        "two byte runs have offsets up to 0x3ff"
+    );
+    vec![(((len - 3) as u8) << 2) | (offset >> 8) as u8, offset as u8]
+}
+
+/// Encode a three byte run chunk copying `len` bytes from `offset + 1` bytes back
+///
+/// # Panics
+///
+/// Panics unless `len` is 4 to 67.
+pub fn three_byte_chunk(offset: u16, len: usize) -> Vec<u8> {
+    assert!(
+        (MIN_THREE_BYTE_RUN_LEN..=MAX_RUN_CHUNK_LEN).contains(&len),
+        "three byte runs are 4 to 67 bytes"
+    );
+    let [hi, lo] = offset.to_be_bytes();
+    vec![0x40 | (len - 4) as u8, hi, lo]
+}
+
+/// Builder for streams together with their expected output.
+#[derive(Clone, Debug, Default)]
+pub struct StreamBuilder {
+    stream: GeneratedStream,
+}
+
+impl StreamBuilder {
+    /// Create a builder for an empty stream
+    pub fn new() -> StreamBuilder {
+        StreamBuilder::default()
+    }
+
+    /// Append literal bytes, split into as many plain chunks as needed
+    pub fn plain(&mut self, bytes: &[u8]) -> &mut StreamBuilder {
+        for part in bytes.chunks(MAX_PLAIN_CHUNK_LEN) {
+            self.stream.compressed.extend(plain_chunk(part));
+            self.stream.decompressed.extend_from_slice(part);
+        }
+        self
+    }
+
+    /// Append a run of `len` bytes from `offset + 1` bytes back, split into as many chunks
+    /// as needed
+    ///
+    /// Uses two byte chunks where possible.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the run reaches before the start of the output, or `len` is shorter than the
+    /// minimum run length for `offset` (3 bytes, 4 above offset 0x3ff).
+    pub fn run(&mut self, offset: u16, len: usize) -> &mut StreamBuilder {
+        if len == 0 {
+            return self;
+        }
+        assert!(
+            len >= min_run_len(offset),
+            "run is too short for its offset"
+        );
+        write_run(&mut self.stream.compressed, offset, len).expect("writing to a Vec can't fail");
+        self.copy(offset, len)
+    }
+
+    /// Append a single two byte run chunk, see [`two_byte_chunk`]
+    pub fn two_byte(&mut self, offset: u16, len: usize) -> &mut StreamBuilder {
+        self.stream.compressed.extend(two_byte_chunk(offset, len));
+        self.copy(offset, len)
+    }
+
+    /// Append a single three byte run chunk, see [`three_byte_chunk`]
+    pub fn three_byte(&mut self, offset: u16, len: usize) -> &mut StreamBuilder {
+        self.stream.compressed.extend(three_byte_chunk(offset, len));
+        self.copy(offset, len)
+    }
+
+    /// Append bytes to the compressed stream only, e.g. to build corrupt streams
+    ///
+    /// The expected output is not changed.
+    pub fn raw(&mut self, bytes: &[u8]) -> &mut StreamBuilder {
+        self.stream.compressed.extend_from_slice(bytes);
+        self
+    }
+
+    /// Get the stream built so far
+    pub fn finish(&self) -> GeneratedStream {
+        self.stream.clone()
+    }
+
+    fn copy(&mut self, offset: u16, len: usize) -> &mut StreamBuilder {
+        let data = &mut self.stream.decompressed;
+        assert!(
+            usize::from(offset) < data.len(),
+            "run reaches before the start of the output"
+        );
+        for _ in 0..len {
+            data.push(data[data.len() - 1 - usize::from(offset)]);
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{inspect, AdcChunkType, AdcDecoder};
+    use std::io::Read;
+
+    fn decode(compressed: &[u8]) -> std::io::Result<Vec<u8>> {
+        let mut data = Vec::new();
+        AdcDecoder::new(compressed).read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    #[test]
+    fn chunks() {
+        assert_eq!(plain_chunk(&[0xfe, 0xed]), [0x81, 0xfe, 0xed]);
+        assert_eq!(two_byte_chunk(0x3ff, 10), [0x1f, 0xff]);
+        assert_eq!(two_byte_chunk(0x3ff, MAX_TWO_BYTE_RUN_LEN), [0x3f, 0xff]);
+        assert_eq!(three_byte_chunk(0x1234, 67), [0x7f, 0x12, 0x34]);
+    }
+
+    #[test]
+    fn long_runs() {
+        for len in (3..200).chain(Some(0)) {
+            for &offset in &[0, 0x3ff, 0x400] {
+                if offset > 0x3ff && len == 3 {
+                    continue;
+                }
+                let stream = StreamBuilder::new()
+                    .plain(&vec![0x5a; 0x401])
+                    .run(offset, len)
+                    .finish();
+                assert_eq!(stream.decompressed.len(), 0x401 + len);
+                assert_eq!(decode(&stream.compressed).unwrap(), stream.decompressed);
+            }
+        }
+    }
+
+    #[test]
+    fn encodings() {
+        let stream = StreamBuilder::new()
+            .plain(&[1; 200])
+            .two_byte(1, 3)
+            .three_byte(1, 4)
+            .finish();
+        let kinds: Vec<_> = inspect(&stream.compressed[..])
+            .unwrap()
+            .chunks
+            .iter()
+            .map(|c| c.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                AdcChunkType::Plain,
+                AdcChunkType::Plain,
+                AdcChunkType::TwoByte,
+                AdcChunkType::ThreeByte
+            ]
+        );
+    }
+
+    #[test]
+    fn corrupt_stream() {
+        let stream = StreamBuilder::new()
+            .plain(b"ab")
+            .raw(&[0x00, 0x05])
+            .finish();
+        assert_eq!(stream.decompressed, b"ab");
+        assert!(decode(&stream.compressed).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "before the start")]
+    fn offset_out_of_range() {
+        StreamBuilder::new().plain(b"a").run(1, 3);
+    }
+}
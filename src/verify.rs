@@ -0,0 +1,194 @@
+//! Verification of decoded block lengths against container metadata.
+//!
+//! Disk image block tables record how many sectors every compressed run decodes to.
+//! [`VerifyingDecoder`] checks a run against that expectation and reports exactly which run
+//! is off and by how much, instead of a bare length error.
+
+use crate::{AdcDecoder, AdcError};
+use std::{
+    error::Error,
+    fmt,
+    io::{self, prelude::*},
+};
+
+/// Size of a disk image sector in bytes.
+pub const SECTOR_SIZE: u64 = 512;
+
+/// Decoded length of a run did not match the expected length.
+///
+/// Returned as the payload of an [`io::Error`] of kind `InvalidData`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[non_exhaustive]
+pub struct LengthMismatch {
+    /// Index of the run in the block table
+    pub run: usize,
+    /// Number of bytes the run should decode to
+    pub expected: u64,
+    /// Number of bytes the run actually decoded to
+    ///
+    /// Decoding stops at the first byte beyond the expected length, so for runs that are too
+    /// long this is only a lower bound.
+    pub actual: u64,
+}
+
+impl fmt::Display for LengthMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "run {}: expected {} bytes ({} sectors), ",
+            self.run,
+            self.expected,
+            self.expected / SECTOR_SIZE
+        )?;
+        if self.actual < self.expected {
+            write!(
+                f,
+                "decoded {} bytes ({} bytes short)",
+                self.actual,
+                self.expected - self.actual
+            )
+        } else {
+            write!(f, "decoded at least {} bytes (too long)", self.actual)
+        }
+    }
+}
+
+impl Error for LengthMismatch {}
+
+/// Decoder checking that a run decodes to exactly the expected number of bytes.
+///
+/// Decodes with [`AdcDecoder::with_expected_size`] and reports its length errors as a
+/// [`LengthMismatch`] for the run.
+pub struct VerifyingDecoder<R> {
+    inner: AdcDecoder<R>,
+    run: usize,
+    expected: u64,
+}
+
+impl<R: Read> VerifyingDecoder<R> {
+    /// Create a verifying decoder for run number `run`, expected to decode to `expected` bytes
+    pub fn new(input: R, run: usize, expected: u64) -> VerifyingDecoder<R> {
+        VerifyingDecoder {
+            inner: AdcDecoder::with_expected_size(input, expected),
+            run,
+            expected,
+        }
+    }
+
+    /// Create a verifying decoder for a run of `sector_count` sectors
+    ///
+    /// Fails with `InvalidInput` if the size of the sectors doesn't fit into a `u64`.
+    pub fn with_sectors(
+        input: R,
+        run: usize,
+        sector_count: u64,
+    ) -> io::Result<VerifyingDecoder<R>> {
+        match sector_count.checked_mul(SECTOR_SIZE) {
+            Some(expected) => Ok(Self::new(input, run, expected)),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "sector count out of range",
+            )),
+        }
+    }
+
+    /// Turn length errors of the decoder into a `LengthMismatch`.
+    fn mismatch(&self, err: io::Error) -> io::Error {
+        let actual = match err.get_ref().and_then(|e| e.downcast_ref::<AdcError>()) {
+            Some(&AdcError::TooShort { actual, .. }) => actual,
+            Some(&AdcError::TooLong { expected }) => expected.saturating_add(1),
+            _ => return err,
+        };
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            LengthMismatch {
+                run: self.run,
+                expected: self.expected,
+                actual,
+            },
+        )
+    }
+}
+
+impl<R: Read> Read for VerifyingDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf).map_err(|err| self.mismatch(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // decodes to 7 bytes
+    const INPUT: &[u8] = &[0x83, 0xfe, 0xed, 0xfa, 0xce, 0x00, 0x00];
+
+    fn mismatch(err: io::Error) -> LengthMismatch {
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        err.into_inner()
+            .unwrap()
+            .downcast::<LengthMismatch>()
+            .map(|m| *m)
+            .unwrap()
+    }
+
+    #[test]
+    fn exact_length() {
+        let mut d = VerifyingDecoder::new(INPUT, 0, 7);
+        let mut data = Vec::new();
+        d.read_to_end(&mut data).unwrap();
+
+        assert_eq!(data.len(), 7);
+    }
+
+    #[test]
+    fn too_short() {
+        let mut d = VerifyingDecoder::with_sectors(INPUT, 3, 1).unwrap();
+        let err = d.read_to_end(&mut Vec::new()).unwrap_err();
+
+        let expected = LengthMismatch {
+            run: 3,
+            expected: 512,
+            actual: 7,
+        };
+        assert_eq!(mismatch(err), expected);
+        assert_eq!(
+            expected.to_string(),
+            "run 3: expected 512 bytes (1 sectors), decoded 7 bytes (505 bytes short)"
+        );
+    }
+
+    #[test]
+    fn too_long() {
+        let mut d = VerifyingDecoder::new(INPUT, 1, 4);
+        let mut data = Vec::new();
+        let err = d.read_to_end(&mut data).unwrap_err();
+
+        assert_eq!(data.len(), 4);
+        let expected = LengthMismatch {
+            run: 1,
+            expected: 4,
+            actual: 5,
+        };
+        assert_eq!(mismatch(err), expected);
+        assert_eq!(
+            expected.to_string(),
+            "run 1: expected 4 bytes (0 sectors), decoded at least 5 bytes (too long)"
+        );
+    }
+
+    #[test]
+    fn sector_count_out_of_range() {
+        let err = VerifyingDecoder::with_sectors(INPUT, 0, u64::MAX / 2)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn other_errors() {
+        let mut d = VerifyingDecoder::new(&[0x81, 0xab, 0xcd, 0x00, 0x05][..], 0, 7);
+        let err = d.read_to_end(&mut Vec::new()).unwrap_err();
+        assert!(matches!(AdcError::from(err), AdcError::InvalidOffset(_)));
+    }
+}
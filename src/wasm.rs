@@ -0,0 +1,148 @@
+//! JavaScript bindings, available with the `wasm` feature.
+//!
+//! Build for the browser with
+//! `cargo rustc --release --lib --target wasm32-unknown-unknown --features wasm --crate-type cdylib`
+//! and generate the JavaScript glue with `wasm-bindgen --target web`.
+//!
+//! [`decompress`] decodes a whole stream held in memory, [`Decoder`] decodes a stream piece
+//! by piece like the [`stream`] module:
+//!
+//! ```js
+//! const decoder = new Decoder();
+//! for await (const chunk of response.body) {
+//!     show(decoder.push(chunk));
+//! }
+//! show(decoder.finish());
+//! ```
+//!
+//! Both take and return `Uint8Array`s and throw an `Error` for corrupt streams.
+
+use crate::{
+    stream::{self, AdcState, Status},
+    AdcError,
+};
+use wasm_bindgen::prelude::*;
+
+/// Room added to the output for each call of the stream decoder.
+const BUF_SIZE: usize = 64 * 1024;
+
+/// Decompress a complete stream
+#[wasm_bindgen]
+pub fn decompress(input: &[u8]) -> Result<Vec<u8>, JsError> {
+    crate::decompress_to_vec(input).map_err(|err| JsError::new(&err.to_string()))
+}
+
+/// Streaming decoder, `new Decoder()` in JavaScript.
+#[wasm_bindgen]
+pub struct Decoder {
+    state: AdcState,
+}
+
+#[wasm_bindgen]
+impl Decoder {
+    /// Create a decoder at the start of a stream
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Decoder {
+        Decoder {
+            state: AdcState::new(),
+        }
+    }
+
+    /// Decode the next part of the stream, returns the data decompressed from it
+    ///
+    /// Output still pending at the end of `input` is returned by the next call or `finish`.
+    pub fn push(&mut self, input: &[u8]) -> Result<Vec<u8>, JsError> {
+        let mut output = Vec::new();
+        if !input.is_empty() {
+            decode(&mut self.state, input, &mut output).map_err(js_error)?;
+        }
+        Ok(output)
+    }
+
+    /// Mark the end of the stream, returns the remaining data
+    ///
+    /// Throws if the stream ended in the middle of a chunk.
+    pub fn finish(&mut self) -> Result<Vec<u8>, JsError> {
+        let mut output = Vec::new();
+        decode(&mut self.state, &[], &mut output).map_err(js_error)?;
+        Ok(output)
+    }
+
+    /// Number of compressed bytes decoded so far
+    #[wasm_bindgen(getter = totalIn)]
+    pub fn total_in(&self) -> f64 {
+        self.state.total_in() as f64
+    }
+
+    /// Number of bytes decompressed so far
+    #[wasm_bindgen(getter = totalOut)]
+    pub fn total_out(&self) -> f64 {
+        self.state.total_out() as f64
+    }
+}
+
+impl Default for Decoder {
+    fn default() -> Decoder {
+        Decoder::new()
+    }
+}
+
+fn js_error(err: AdcError) -> JsError {
+    JsError::new(&err.to_string())
+}
+
+/// Decode all of `input` onto the end of `output`, an empty `input` ends the stream.
+fn decode(state: &mut AdcState, mut input: &[u8], output: &mut Vec<u8>) -> Result<(), AdcError> {
+    let end = input.is_empty();
+    loop {
+        let len = output.len();
+        output.resize(len + BUF_SIZE, 0);
+        let (status, read, written) = stream::decompress(state, input, &mut output[len..]);
+        output.truncate(len + written);
+        input = &input[read..];
+        match status {
+            // an empty input would end the stream, leave the rest for the next call
+            Status::NeedsMoreOutput if input.is_empty() && !end => return Ok(()),
+            Status::NeedsMoreOutput => {}
+            Status::NeedsMoreInput | Status::Done => return Ok(()),
+            status => return Err(state.error(status)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::StreamGenerator;
+
+    #[test]
+    fn streaming() {
+        let stream = StreamGenerator::new(23)
+            .text(100_000)
+            .zeros(200_000)
+            .finish();
+        for &split in &[1, 2, 3, 1000, BUF_SIZE, stream.compressed.len()] {
+            let mut state = AdcState::new();
+            let mut data = Vec::new();
+            for part in stream.compressed.chunks(split) {
+                decode(&mut state, part, &mut data).unwrap();
+            }
+            decode(&mut state, &[], &mut data).unwrap();
+            assert!(data == stream.decompressed, "split {}", split);
+            assert_eq!(state.total_in(), stream.compressed.len() as u64);
+        }
+    }
+
+    #[test]
+    fn corrupt_streams() {
+        let mut state = AdcState::new();
+        let mut data = Vec::new();
+        decode(&mut state, &[0x83, 0xfe], &mut data).unwrap();
+        let err = decode(&mut state, &[], &mut data).unwrap_err();
+        assert!(matches!(err, AdcError::TruncatedChunk(_)));
+
+        let mut state = AdcState::new();
+        let err = decode(&mut state, &[0x00, 0x00], &mut data).unwrap_err();
+        assert!(matches!(err, AdcError::InvalidOffset(_)));
+    }
+}
@@ -0,0 +1,333 @@
+//! History buffer of the most recent output bytes.
+//!
+//! ADC runs copy from the last [`WINDOW_SIZE`] bytes of output. [`Window`] keeps exactly that
+//! history and can be reused by other LZ-style codecs with the same window semantics.
+
+use crate::WINDOW_SIZE;
+#[cfg(feature = "alloc")]
+use alloc::{collections::TryReserveError, vec, vec::Vec};
+use core::{cmp, fmt};
+
+/// Window into the decompressed output.
+///
+/// Holds the last `N` bytes written to it, [`WINDOW_SIZE`] by default, older bytes are
+/// dropped. Bytes are addressed by their distance from the most recent one, offset 0 being
+/// the last byte written, just like the offsets of ADC run chunks.
+///
+/// ```
+/// use adc::Window;
+///
+/// let mut window = Window::new();
+/// window.extend(b"abc");
+/// assert_eq!(window.get(0), Some(b'c'));
+/// assert_eq!(window.get(2), Some(b'a'));
+/// assert_eq!(window.get(3), None);
+/// assert_eq!(window.snapshot(), b"abc");
+/// ```
+///
+/// The history is a ring buffer of `N` bytes, writing to it never moves older bytes. Without
+/// the `alloc` feature it is a fixed array inside the window, so it needs no heap at all but
+/// takes up `N` bytes wherever the window is stored. Smaller windows save memory for streams
+/// known to only use short run offsets, a window larger than [`WINDOW_SIZE`] is never useful.
+#[derive(Clone)]
+pub struct Window<const N: usize = WINDOW_SIZE> {
+    /// Either empty, before a [`Default`] window is first written to, or exactly `N` bytes
+    #[cfg(feature = "alloc")]
+    buf: Vec<u8>,
+    #[cfg(not(feature = "alloc"))]
+    buf: [u8; N],
+    /// Index the next byte is written to
+    end: usize,
+    len: usize,
+}
+
+impl Window {
+    /// Create an empty window of [`WINDOW_SIZE`] bytes
+    pub fn new() -> Window {
+        Window::sized()
+    }
+}
+
+impl Default for Window {
+    #[cfg(feature = "alloc")]
+    fn default() -> Window {
+        Window {
+            buf: Vec::new(),
+            end: 0,
+            len: 0,
+        }
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    fn default() -> Window {
+        Window::new()
+    }
+}
+
+impl<const N: usize> Window<N> {
+    /// Create an empty window of `N` bytes
+    ///
+    /// ```
+    /// use adc::Window;
+    ///
+    /// let mut window = Window::<2>::sized();
+    /// window.extend(b"abc");
+    /// assert_eq!(window.get(1), Some(b'b'));
+    /// assert_eq!(window.get(2), None);
+    /// ```
+    ///
+    /// A window of zero bytes doesn't compile:
+    ///
+    /// ```compile_fail
+    /// let window = adc::Window::<0>::sized();
+    /// ```
+    pub fn sized() -> Window<N> {
+        // the ring buffer indices are taken modulo N
+        const { assert!(N > 0, "the window needs at least one byte") };
+        Window {
+            #[cfg(feature = "alloc")]
+            buf: vec![0; N],
+            #[cfg(not(feature = "alloc"))]
+            buf: [0; N],
+            end: 0,
+            len: 0,
+        }
+    }
+
+    /// Reserve memory for the full history, failing instead of aborting if that isn't possible
+    ///
+    /// [`new`](Window::new) reserves it infallibly, a [`Default`] window starts out without
+    /// any memory and reserves it when first written to.
+    #[cfg(feature = "alloc")]
+    pub fn try_reserve(&mut self) -> Result<(), TryReserveError> {
+        if self.buf.is_empty() {
+            self.buf.try_reserve_exact(N)?;
+            self.buf.resize(N, 0);
+        }
+        Ok(())
+    }
+
+    /// Get the ring buffer, allocating it if needed.
+    fn ring(&mut self) -> &mut [u8] {
+        #[cfg(feature = "alloc")]
+        if self.buf.is_empty() {
+            self.buf.resize(N, 0);
+        }
+        &mut self.buf
+    }
+
+    /// Append `bytes` to the history
+    pub fn extend(&mut self, bytes: &[u8]) {
+        // only the last N bytes can ever be addressed
+        let bytes = &bytes[bytes.len().saturating_sub(N)..];
+        let end = self.end;
+        let ring = self.ring();
+
+        // copy up to the end of the ring, then wrap around to its start
+        let (first, second) = bytes.split_at(cmp::min(bytes.len(), N - end));
+        ring[end..][..first.len()].copy_from_slice(first);
+        ring[..second.len()].copy_from_slice(second);
+
+        self.end = (end + bytes.len()) % N;
+        self.len = cmp::min(self.len + bytes.len(), N);
+    }
+
+    /// Append a single byte to the history
+    pub fn push(&mut self, byte: u8) {
+        let end = self.end;
+        self.ring()[end] = byte;
+        self.end = (end + 1) % N;
+        self.len = cmp::min(self.len + 1, N);
+    }
+
+    /// Get the byte `offset + 1` bytes back, `None` if the history is shorter
+    pub fn get(&self, offset: u16) -> Option<u8> {
+        let offset = usize::from(offset);
+        if offset >= self.len {
+            return None;
+        }
+        Some(self.buf[(self.end + N - 1 - offset) % N])
+    }
+
+    /// Copy a run of `output.len()` bytes starting `offset + 1` bytes back into `output` and
+    /// append it to the history
+    ///
+    /// Runs longer than `offset + 1` repeat their start, like ADC runs do. Returns `false`
+    /// without changing anything if the history is shorter than `offset + 1` bytes.
+    ///
+    /// ```
+    /// use adc::Window;
+    ///
+    /// let mut window = Window::new();
+    /// window.extend(b"xab");
+    /// let mut run = [0; 5];
+    /// assert!(window.copy_run(1, &mut run));
+    /// assert_eq!(&run, b"ababa");
+    /// assert_eq!(window.get(0), Some(b'a'));
+    /// ```
+    pub fn copy_run(&mut self, offset: u16, output: &mut [u8]) -> bool {
+        let distance = usize::from(offset) + 1;
+        if distance > self.len {
+            return false;
+        }
+
+        // the part of the run that is already in the history, in at most two pieces
+        let len = cmp::min(distance, output.len());
+        let start = (self.end + N - distance) % N;
+        let first = cmp::min(len, N - start);
+        output[..first].copy_from_slice(&self.buf[start..][..first]);
+        output[first..len].copy_from_slice(&self.buf[..len - first]);
+
+        // the rest repeats the bytes copied, doubling the copied part each time
+        let mut filled = len;
+        while filled < output.len() {
+            // copying whole periods keeps the pattern in place
+            let period = filled - filled % distance;
+            let n = cmp::min(period, output.len() - filled);
+            output.copy_within(filled - period..filled - period + n, filled);
+            filled += n;
+        }
+
+        self.extend(output);
+        true
+    }
+
+    /// Number of bytes in the history, at most `N`
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if nothing was written yet
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Copy the history in output order, oldest byte first
+    #[cfg(feature = "alloc")]
+    pub fn snapshot(&self) -> Vec<u8> {
+        let start = (self.end + N - self.len) % N;
+        let mut bytes = Vec::with_capacity(self.len);
+        if start + self.len <= N {
+            bytes.extend_from_slice(&self.buf[start..][..self.len]);
+        } else {
+            bytes.extend_from_slice(&self.buf[start..]);
+            bytes.extend_from_slice(&self.buf[..self.end]);
+        }
+        bytes
+    }
+
+    /// Forget all history
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl<const N: usize> fmt::Debug for Window<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Window")
+            .field("len", &self.len())
+            .field("size", &N)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wraps_around() {
+        let mut window = Window::new();
+        for i in 0..WINDOW_SIZE + 10 {
+            window.push(i as u8);
+        }
+        assert_eq!(window.len(), WINDOW_SIZE);
+        assert_eq!(window.get(0), Some((WINDOW_SIZE + 9) as u8));
+        assert_eq!(window.get(u16::MAX), Some(10));
+
+        window.extend(&[0xaa; 3]);
+        assert_eq!(window.get(2), Some(0xaa));
+        assert_eq!(window.get(3), Some((WINDOW_SIZE + 9) as u8));
+    }
+
+    #[test]
+    fn small_window() {
+        let mut window = Window::<3>::sized();
+        window.extend(&[1, 2]);
+        window.extend(&[3, 4]);
+        assert_eq!(window.len(), 3);
+        assert_eq!(window.get(0), Some(4));
+        assert_eq!(window.get(2), Some(2));
+        assert_eq!(window.get(3), None);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn drops_old_bytes() {
+        let mut window = Window::new();
+        let data: Vec<u8> = (0..WINDOW_SIZE + 10).map(|i| i as u8).collect();
+        window.extend(&data[..20]);
+        window.extend(&data[20..]);
+
+        assert_eq!(window.len(), WINDOW_SIZE);
+        assert_eq!(window.get(0), Some(data[data.len() - 1]));
+        assert_eq!(window.get(u16::MAX), Some(data[10]));
+        assert_eq!(window.snapshot(), &data[10..]);
+
+        // a single extend larger than the window
+        let mut window = Window::new();
+        window.extend(&data);
+        assert_eq!(window.snapshot(), &data[10..]);
+    }
+
+    #[test]
+    fn copy_runs() {
+        let mut window = Window::<5>::sized();
+        window.extend(&[1, 2, 3, 4, 5, 6, 7]);
+        let mut run = [0; 4];
+        assert!(!window.copy_run(5, &mut run));
+        assert_eq!(window.len(), 5);
+
+        // wrapping around the end of the ring
+        assert!(window.copy_run(4, &mut run));
+        assert_eq!(run, [3, 4, 5, 6]);
+
+        for distance in 1..=5 {
+            let mut expected = [0; 13];
+            for i in 0..expected.len() {
+                expected[i] = match i.checked_sub(distance) {
+                    Some(j) => expected[j],
+                    None => window.get((distance - 1 - i) as u16).unwrap(),
+                };
+            }
+            let mut run = [0; 13];
+            let mut copy = window.clone();
+            assert!(copy.copy_run(distance as u16 - 1, &mut run));
+            assert_eq!(run, expected, "distance {}", distance);
+            assert_eq!(copy.get(0), Some(expected[12]));
+        }
+    }
+
+    #[test]
+    fn push_and_clear() {
+        let mut window = Window::new();
+        assert!(window.is_empty());
+        window.push(1);
+        window.push(2);
+        assert_eq!(window.get(1), Some(1));
+
+        window.clear();
+        assert_eq!(window.get(0), None);
+    }
+
+    #[test]
+    #[cfg(feature = "alloc")]
+    fn reserve_default() {
+        let mut window = Window::default();
+        window.try_reserve().unwrap();
+        window.extend(&[7; WINDOW_SIZE + 1]);
+        assert_eq!(window.len(), WINDOW_SIZE);
+        // the history is full, nothing more to reserve
+        window.try_reserve().unwrap();
+    }
+}
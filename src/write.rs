@@ -0,0 +1,19 @@
+//! Types writing compressed or decompressed data to a `Write`.
+//!
+//! Counterpart of the [`read`](crate::read) module.
+//!
+//! ```
+//! use adc::{read::Decoder, write::Encoder};
+//! use std::io::{Read, Write};
+//!
+//! let mut encoder = Encoder::new(Vec::new());
+//! encoder.write_all(b"abcabcabcabc").unwrap();
+//! let compressed = encoder.finish().unwrap();
+//!
+//! let mut data = Vec::new();
+//! Decoder::new(&compressed[..]).read_to_end(&mut data).unwrap();
+//! assert_eq!(data, b"abcabcabcabc");
+//! ```
+
+pub use crate::AdcDecoderWriter as Decoder;
+pub use crate::AdcEncoder as Encoder;
@@ -0,0 +1,31 @@
+#!/bin/sh
+# Regenerate the apple-* golden vectors from images compressed by hdiutil.
+#
+# Usage: regenerate.sh path/to/adc
+set -eu
+
+ADC=$(cd "$(dirname "$1")" && pwd)/$(basename "$1")
+OUT=$(cd "$(dirname "$0")" && pwd)
+WORK=$(mktemp -d)
+trap 'rm -rf "$WORK"' EXIT
+
+# sample inputs: text, zeros, incompressible data and a mix of them
+mkdir "$WORK/src"
+for i in $(seq 200); do cat "$OUT/README.md"; done > "$WORK/src/text.txt"
+dd if=/dev/zero of="$WORK/src/zeros.bin" bs=1024 count=256 2>/dev/null
+dd if=/dev/urandom of="$WORK/src/random.bin" bs=1024 count=64 2>/dev/null
+cat "$WORK/src/text.txt" "$WORK/src/zeros.bin" "$WORK/src/random.bin" > "$WORK/src/mixed.bin"
+
+hdiutil create -quiet -fs HFS+ -format UDCO -srcfolder "$WORK/src" "$WORK/adc.dmg"
+hdiutil convert -quiet -format UDRW "$WORK/adc.dmg" -o "$WORK/raw.dmg"
+
+rm -f "$OUT"/apple-*
+"$ADC" --json dmg-block --list "$WORK/adc.dmg" |
+    jq -r '.runs[] | select(.type == "adc") | "\(.index) \(.sector) \(.sectors) \(.offset) \(.length)"' |
+    while read -r index sector sectors offset length; do
+        name=$(printf 'apple-%04d' "$index")
+        dd if="$WORK/adc.dmg" of="$OUT/$name.adc" bs=1 skip="$offset" count="$length" 2>/dev/null
+        dd if="$WORK/raw.dmg" of="$OUT/$name.out" bs=512 skip="$sector" count="$sectors" 2>/dev/null
+    done
+
+ls "$OUT"/apple-*.adc | wc -l | xargs echo "vectors written:"